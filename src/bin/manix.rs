@@ -2,90 +2,958 @@ use anyhow::{
     Context,
     Result,
 };
+use bookmarks::Bookmarks;
+use boolean_query::BooleanQuery;
+use builtins_docsource::BuiltinsDatabase;
+use cache_bundle::CacheBundle;
+use cache_status::CacheStatus;
+use clap::{
+    Args,
+    Command,
+    CommandFactory,
+    Parser,
+    ValueEnum,
+    ValueHint,
+};
+use clap_complete::{
+    generate,
+    Generator,
+    Shell,
+};
+use clap_mangen::Man;
 use colored::*;
 use comments_docsource::CommentsDatabase;
-use strum::VariantNames;
+use config::Config;
+use glob_query::GlobQuery;
+use highlight::highlight;
+use history::History;
+use indicatif::{
+    MultiProgress,
+    ProgressBar,
+    ProgressStyle,
+};
+use key_tree::KeyTree;
+use lazy_docsource::LazyDocSource;
+use lazy_static::lazy_static;
 use manix::*;
+use manual_docsource::{
+    ManualDatabase,
+    ManualSectionType,
+};
 use options_docsource::{
+    get_configured_value,
+    OptionDisplay,
+    OptionDocumentation,
     OptionsDatabase,
     OptionsDatabaseType,
 };
 use std::{
-    io::{self, Write},
-    path::PathBuf,
+    collections::HashSet,
+    io::{
+        self,
+        BufRead,
+        IsTerminal,
+        Write,
+    },
+    os::unix::net::{
+        UnixListener,
+        UnixStream,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        ExitCode,
+        Stdio,
+    },
+    sync::atomic::{
+        AtomicBool,
+        AtomicUsize,
+        Ordering,
+    },
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+use table::{
+    render_table,
+    Row,
 };
-use clap::{Parser, ValueEnum, ValueHint, Command, CommandFactory};
-use lazy_static::lazy_static;
-use clap_complete::{generate, Generator, Shell};
-use clap_mangen::Man;
 
-#[derive(Debug, PartialEq, Clone, ValueEnum, VariantNames)]
+#[derive(Debug, PartialEq, Clone, ValueEnum)]
 #[allow(non_camel_case_types)]
-#[strum(serialize_all = "kebab-case")]
 enum Source {
+    #[value(alias = "hm")]
     hm_options,
+    #[value(alias = "darwin")]
     nd_options,
+    #[value(alias = "nixos")]
     nixos_options,
     nixpkgs_doc,
     nixpkgs_tree,
+    #[value(alias = "comments")]
     nixpkgs_comments,
+    nix_builtins,
+    nixpkgs_packages,
+    nur,
+    nix_cli,
+    nix_conf,
+    devenv_options,
+    flake_parts_options,
+    nixvim_options,
+    disko_options,
+    sops_nix_options,
+    agenix_options,
+    treefmt_nix_options,
+    pre_commit_hooks_options,
+    impermanence_options,
+    colmena_options,
+    deploy_rs_options,
+    nixpkgs_lib,
+    nixos_wiki,
+    nix_rfcs,
+    release_notes,
+    stdenv_doc,
+    build_helpers_doc,
+    language_frameworks_doc,
+    image_builders_doc,
+    nix_language,
+    nixpkgs_maintainers,
+    nixpkgs_licenses,
+    nixos_tests_doc,
+    haskell_packages,
+    python_packages,
+    node_packages,
+    vim_plugins,
+    emacs_packages,
+    option_renames,
+}
+
+/// How many results are printed when no --limit is given
+const DEFAULT_LIMIT: usize = 20;
+
+/// How human readable results are written out
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+#[allow(non_camel_case_types)]
+enum Format {
+    /// Colored text for terminals
+    ansi,
+    /// Text without colors, for scripts
+    plain,
+    /// Markdown sections, for `glow` and the like
+    markdown,
+    /// A line per result with its key, type and description, fitted to the terminal
+    table,
+}
+
+impl Format {
+    /// Disables the colors of `colored` for formats that have none
+    fn apply(self) {
+        if self != Format::ansi {
+            colored::control::set_override(false);
+        }
+    }
+}
+
+/// What `manix export` writes
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+#[allow(non_camel_case_types)]
+enum ExportFormat {
+    /// A man page of the options, like configuration.nix(5)
+    man,
+    /// A Dash/Zeal docset, which needs `sqlite3` to build its index
+    docset,
+}
+
+/// What `--copy` puts on the clipboard
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+#[allow(non_camel_case_types)]
+enum CopyWhat {
+    /// The attribute path of the entry
+    key,
+    /// Its documentation as plain text
+    doc,
+}
+
+/// What a result documents, coarser than `DocEntry::kind`
+#[derive(Debug, PartialEq, Clone, Copy, ValueEnum)]
+#[allow(non_camel_case_types)]
+enum Kind {
+    option,
+    function,
+    package,
+    attrset,
+    doc,
+}
+
+impl Kind {
+    fn of(entry: &DocEntry) -> Self {
+        match entry {
+            DocEntry::OptionDoc(_, _) | DocEntry::OptionRenameDoc(_) | DocEntry::NixConfDoc(_) => {
+                Kind::option
+            }
+            DocEntry::CommentDoc(_)
+            | DocEntry::XmlFuncDoc(_)
+            | DocEntry::BuiltinDoc(_)
+            | DocEntry::LibFuncDoc(_) => Kind::function,
+            DocEntry::PackageDoc(_) | DocEntry::NurPackageDoc(_) => Kind::package,
+            DocEntry::NixpkgsTreeDoc(_) | DocEntry::MaintainerDoc(_) | DocEntry::LicenseDoc(_) => {
+                Kind::attrset
+            }
+            DocEntry::CliDoc(_)
+            | DocEntry::WikiDoc(_)
+            | DocEntry::RfcDoc(_)
+            | DocEntry::ChangelogDoc(_)
+            | DocEntry::ManualDoc(_, _)
+            | DocEntry::LanguageDoc(_) => Kind::doc,
+        }
+    }
 }
 
+/// Option databases of module systems living outside of nixpkgs, along with their cache file
+const MODULE_OPTIONS_SOURCES: &[(Source, OptionsDatabaseType, &str)] = &[
+    (
+        Source::devenv_options,
+        OptionsDatabaseType::Devenv,
+        "options_devenv_database.bin",
+    ),
+    (
+        Source::flake_parts_options,
+        OptionsDatabaseType::FlakeParts,
+        "options_flake_parts_database.bin",
+    ),
+    (
+        Source::nixvim_options,
+        OptionsDatabaseType::Nixvim,
+        "options_nixvim_database.bin",
+    ),
+    (
+        Source::disko_options,
+        OptionsDatabaseType::Disko,
+        "options_disko_database.bin",
+    ),
+    (
+        Source::sops_nix_options,
+        OptionsDatabaseType::SopsNix,
+        "options_sops_nix_database.bin",
+    ),
+    (
+        Source::agenix_options,
+        OptionsDatabaseType::Agenix,
+        "options_agenix_database.bin",
+    ),
+    (
+        Source::treefmt_nix_options,
+        OptionsDatabaseType::TreefmtNix,
+        "options_treefmt_nix_database.bin",
+    ),
+    (
+        Source::pre_commit_hooks_options,
+        OptionsDatabaseType::PreCommitHooks,
+        "options_pre_commit_hooks_database.bin",
+    ),
+    (
+        Source::impermanence_options,
+        OptionsDatabaseType::Impermanence,
+        "options_impermanence_database.bin",
+    ),
+    (
+        Source::colmena_options,
+        OptionsDatabaseType::Colmena,
+        "options_colmena_database.bin",
+    ),
+    (
+        Source::deploy_rs_options,
+        OptionsDatabaseType::DeployRs,
+        "options_deploy_rs_database.bin",
+    ),
+];
+
+/// Package sets too big to be searched by default, along with their cache file
+const PACKAGE_SET_SOURCES: &[(Source, &str, &str)] = &[
+    (
+        Source::haskell_packages,
+        "haskellPackages",
+        "packages_haskell_database.bin",
+    ),
+    (
+        Source::python_packages,
+        "python3Packages",
+        "packages_python_database.bin",
+    ),
+    (
+        Source::node_packages,
+        "nodePackages",
+        "packages_node_database.bin",
+    ),
+    (
+        Source::vim_plugins,
+        "vimPlugins",
+        "packages_vim_plugins_database.bin",
+    ),
+    (
+        Source::emacs_packages,
+        "emacsPackages",
+        "packages_emacs_database.bin",
+    ),
+];
+
+/// Chapters of the nixpkgs manual, along with their cache file
+const MANUAL_SOURCES: &[(Source, ManualSectionType, &str)] = &[
+    (
+        Source::stdenv_doc,
+        ManualSectionType::Stdenv,
+        "manual_stdenv_database.bin",
+    ),
+    (
+        Source::build_helpers_doc,
+        ManualSectionType::BuildHelpers,
+        "manual_build_helpers_database.bin",
+    ),
+    (
+        Source::language_frameworks_doc,
+        ManualSectionType::LanguageFrameworks,
+        "manual_language_frameworks_database.bin",
+    ),
+    (
+        Source::image_builders_doc,
+        ManualSectionType::Images,
+        "manual_images_database.bin",
+    ),
+    (
+        Source::nixos_tests_doc,
+        ManualSectionType::NixosTests,
+        "manual_nixos_tests_database.bin",
+    ),
+];
+
 lazy_static! {
-    static ref SOURCE_VARIANTS: String = Source::VARIANTS.join(",").to_string();
+    /// Sources searched by default. Opt-in sources are expensive to build, so they're only built
+    /// and searched when selected
+    static ref SOURCE_VARIANTS: String = Source::value_variants()
+        .iter()
+        .filter(|source| {
+            !matches!(source, Source::nur | Source::nixos_wiki | Source::nix_rfcs)
+                && (cfg!(target_os = "macos") || *source != &Source::nd_options)
+                && !MODULE_OPTIONS_SOURCES
+                    .iter()
+                    .any(|(module_source, _, _)| module_source == *source)
+                && !PACKAGE_SET_SOURCES
+                    .iter()
+                    .any(|(set_source, _, _)| set_source == *source)
+        })
+        .filter_map(|source| source.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(",");
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
 pub enum ShellCompletion {
-	Bash,
-	Elvish,
-	Fish,
-	Nu,
-	Powershell,
-	Zsh,
+    Bash,
+    Elvish,
+    Fish,
+    Nu,
+    Powershell,
+    Zsh,
 }
 
 #[derive(Parser)]
-#[clap(name = "manix")]
+#[command(
+    name = "manix",
+    args_conflicts_with_subcommands = true,
+    arg_required_else_help = true
+)]
 struct Opt {
+    #[command(subcommand)]
+    command: Option<Subcommand>,
+
+    #[command(flatten)]
+    search: SearchArgs,
+
+    /// Generate completions for the specified shell
+    #[arg(long = "generate", value_enum)]
+    generator: Option<ShellCompletion>,
+
+    #[arg(long = "print-man")]
+    man: bool,
+}
+
+#[derive(clap::Subcommand)]
+enum Subcommand {
+    /// Search the documentation, which is what a bare `manix QUERY` does
+    Search(SearchArgs),
+    /// Show everything known about the entries of an exact key
+    Show(ShowArgs),
+    /// Rebuild the caches, only the ones of the sources given if any
+    Update(UpdateArgs),
+    /// Manage the cache files
+    #[command(subcommand)]
+    Cache(CacheCommand),
+    /// Keep entries at hand, to search among them with --bookmarks
+    #[command(subcommand)]
+    Bookmark(BookmarkCommand),
+    /// Print the keys of the chosen sources, one per line, for fzf and completions
+    List(ListArgs),
+    /// Draw the options and attributes below a prefix as a tree
+    Tree(TreeArgs),
+    /// Print the completion script of a shell, which also completes keys
+    Completions {
+        #[arg(value_enum)]
+        shell: ShellCompletion,
+    },
+    /// Write the documentation of the chosen sources out for other tools to read offline
+    Export(ExportArgs),
+    /// Keep the caches loaded and answer searches over a unix socket. Searches whose output goes
+    /// to another program, like an editor, are handed over to it while it runs
+    Daemon(SourceArgs),
+    /// Check that nix, <nixpkgs>, the cache directory, the caches and the bundled expressions
+    /// work, telling how to fix what doesn't
+    Doctor,
+}
+
+#[derive(clap::Subcommand)]
+enum BookmarkCommand {
+    /// Bookmark an entry by its key
+    Add {
+        #[arg(name = "KEY", value_hint = ValueHint::Other)]
+        key: String,
+    },
+    /// Print the bookmarked keys
+    List,
+    /// Forget a bookmark
+    Remove {
+        #[arg(name = "KEY", value_hint = ValueHint::Other)]
+        key: String,
+    },
+}
+
+#[derive(clap::Subcommand)]
+enum CacheCommand {
+    /// Print the directory the caches are stored in
+    Path,
+    /// Tell how big, how old and how full each cache is, and which nixpkgs it was built from
+    Status,
+    /// Delete every cache, they're built again on the next search
+    Clear,
+    /// Bundle every cache into a file, to be imported on another machine
+    Export {
+        #[arg(name = "FILE", value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+    },
+    /// Replace the caches with the ones of a file written by `manix cache export`
+    Import {
+        #[arg(name = "FILE", value_hint = ValueHint::FilePath)]
+        file: PathBuf,
+    },
+}
+
+// Which sources are loaded, shared by every subcommand looking at them
+#[derive(Args, Clone)]
+struct SourceArgs {
     /// Force update cache
     #[arg(short, long)]
     update_cache: bool,
 
+    /// Restrict search to chosen sources
+    #[arg(long, value_enum, default_value = &**SOURCE_VARIANTS, use_value_delimiter = true, value_hint = ValueHint::Other)]
+    source: Vec<Source>,
+
+    /// Skip chosen sources, on top of the ones --source restricts to
+    #[arg(long, value_enum, use_value_delimiter = true, value_hint = ValueHint::Other)]
+    exclude_source: Vec<Source>,
+
+    /// Also search the NixOS/home-manager module options exposed by a flake
+    #[arg(long, value_name = "FLAKEREF", value_hint = ValueHint::Other)]
+    flake: Option<String>,
+
+    /// Also search the options.json built by a nix file
+    #[arg(long, value_name = "FILE", value_hint = ValueHint::FilePath)]
+    options_expr: Option<PathBuf>,
+
+    /// Read the NixOS options from an options.json built already, or an output containing one
+    /// like `config.system.build.manual.optionsJSON`, instead of building them.
+    /// `options_json` in the config does the same
+    #[arg(long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    nixos_options_json: Option<PathBuf>,
+
+    /// Query search.nixos.org when the local NixOS options or packages caches are missing, or
+    /// along with them when they're stale
+    #[arg(long)]
+    online: bool,
+
+    /// Use the documentation of a nixpkgs channel, `unstable` or `24.05` for instance, instead of
+    /// the nixpkgs of the NIX_PATH. Each channel has caches of its own
+    #[arg(long, value_name = "CHANNEL", value_hint = ValueHint::Other)]
+    channel: Option<String>,
+
+    /// Build the caches from this nixpkgs instead of the one of the NIX_PATH, a flake ref like
+    /// `github:NixOS/nixpkgs/REV`, a revision or a path. `nixpkgs` in the config does the same
+    #[arg(long, value_name = "FLAKEREF", conflicts_with = "channel", value_hint = ValueHint::Other)]
+    nixpkgs: Option<String>,
+
+    /// In a directory with a flake.lock, or below one, use caches of the nixpkgs and home-manager
+    /// it locks. `project_caches` in the config does the same
+    #[arg(long, conflicts_with_all = ["channel", "nixpkgs"])]
+    project: bool,
+
+    /// Sources whose caches are rebuilt whether they're up to date or not
+    #[arg(skip)]
+    rebuild: Vec<Source>,
+}
+
+#[derive(Args)]
+struct UpdateArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
+    /// Sources to rebuild the caches of, leaving the other caches as they are
+    #[arg(name = "SOURCE", value_enum, value_hint = ValueHint::Other)]
+    only: Vec<Source>,
+
+    /// Download signed prebuilt caches from where `prebuilt_caches` in the config says instead of
+    /// building them
+    #[arg(long, conflicts_with = "SOURCE")]
+    fetch: bool,
+}
+
+#[derive(Args)]
+struct ShowArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
+    /// Key of the entry to show
+    #[arg(name = "KEY", value_hint = ValueHint::Other)]
+    key: String,
+
+    /// How to write the entry out
+    #[arg(long, value_enum, default_value = "ansi")]
+    format: Format,
+
+    /// Print straight to the terminal instead of through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Open the file defining the entry in $EDITOR instead
+    #[arg(long)]
+    open: bool,
+    /// Copy the key to the clipboard, or the documentation with --copy=doc
+    #[arg(long, value_enum, value_name = "WHAT", num_args = 0..=1, require_equals = true, default_missing_value = "key")]
+    copy: Option<CopyWhat>,
+}
+
+#[derive(Args)]
+struct ListArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
+    /// Only print the keys starting with this
+    #[arg(long, value_name = "PREFIX", value_hint = ValueHint::Other)]
+    prefix: Option<String>,
+    /// Cut the keys after the segment following the prefix, for completing a segment at a time
+    #[arg(long)]
+    next_segment: bool,
+}
+
+#[derive(Args)]
+struct TreeArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
+    /// Only draw this many levels below the prefix
+    #[arg(long, value_name = "N")]
+    depth: Option<usize>,
+
+    /// Attribute path to draw the tree below, everything when empty
+    #[arg(name = "PREFIX", value_hint = ValueHint::Other, default_value = "")]
+    prefix: String,
+}
+
+#[derive(Args)]
+struct ExportArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
+    /// What to write
+    #[arg(long, value_enum, required_unless_present = "html")]
+    format: Option<ExportFormat>,
+
+    /// File of the man page, stdout by default, or directory of the docset, manix.docset by
+    /// default
+    #[arg(short, long, value_name = "PATH", value_hint = ValueHint::AnyPath)]
+    output: Option<PathBuf>,
+
+    /// Write a static site with a page per source and a search box to this directory instead
+    #[arg(long, value_name = "DIR", value_hint = ValueHint::DirPath, conflicts_with_all = ["format", "output"])]
+    html: Option<PathBuf>,
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    #[command(flatten)]
+    sources: SourceArgs,
+
     /// Matches entries stricly
     #[arg(short, long)]
     strict: bool,
 
-    /// Restrict search to chosen sources
-    #[arg(long, value_enum, default_value = &**SOURCE_VARIANTS, use_value_delimiter = true, value_hint = ValueHint::Other)]
-    source: Vec<Source>,
+    /// Also search descriptions and comments, every word of the query has to show up
+    #[arg(long, conflicts_with = "strict")]
+    full_text: bool,
+
+    /// Treat the query as a regular expression matched against the keys, and against
+    /// descriptions as well with --full-text
+    #[arg(long, conflicts_with = "strict")]
+    regex: bool,
+
+    /// Match the query fuzzily, tolerating typos and words out of order, best matches first
+    #[arg(long, conflicts_with_all = ["strict", "regex", "full_text"])]
+    fuzzy: bool,
+
+    /// Match the case of the query exactly
+    #[arg(long, conflicts_with = "fuzzy")]
+    case_sensitive: bool,
+
+    /// Only match the query as a whole word, `map` doesn't match `mapAttrs`
+    #[arg(long, conflicts_with = "fuzzy")]
+    word: bool,
+
+    /// Pick one of the results to show from a numbered list, which is offered on terminals anyway
+    /// when several entries match the query exactly
+    #[arg(long, conflicts_with = "json")]
+    pick: bool,
+
+    /// Only show results of chosen kinds
+    #[arg(long, value_enum, use_value_delimiter = true, value_hint = ValueHint::Other)]
+    kind: Vec<Kind>,
 
     /// Query to search for
     #[arg(name = "QUERY", value_hint = ValueHint::CommandString)]
-    query: String,
+    query: Option<String>,
+
+    /// More terms that entries have to match as well, combined with AND/OR/NOT
+    #[arg(name = "TERMS", value_hint = ValueHint::Other)]
+    terms: Vec<String>,
+
+    /// Words after `--` narrowing the results down, to those having them in their key or text:
+    /// `manix nginx -- ssl`
+    #[arg(name = "REFINE", last = true, value_hint = ValueHint::Other)]
+    refine: Vec<String>,
+
+    /// Also print what your NixOS or home-manager configuration sets the queried option to
+    #[arg(long)]
+    show_value: bool,
+
+    /// Print the default values and examples of options
+    #[arg(long)]
+    show_defaults: bool,
+
+    /// Print the files declaring options
+    #[arg(long)]
+    show_declared_in: bool,
+
+    /// Print links to the online documentation of options
+    #[arg(long)]
+    urls: bool,
+
+    /// Only show options of this type, written like `listOf str` or the way their docs describe
+    /// it, every one of them without a query
+    #[arg(long = "type", value_name = "TYPE", value_hint = ValueHint::Other)]
+    option_type: Option<String>,
+
+    /// Only show read-only options, which configurations can't set
+    #[arg(long, conflicts_with = "writable_only")]
+    read_only: bool,
+
+    /// Leave read-only options out
+    #[arg(long)]
+    writable_only: bool,
+
+    /// Only show bookmarked entries, every one of them without a query
+    #[arg(long)]
+    bookmarks: bool,
+
+    /// Include internal and invisible options
+    #[arg(long)]
+    all: bool,
+
+    /// Show at most this many results, 0 for no limit. Defaults to 20 unless outputting JSON
+    #[arg(long, value_name = "N")]
+    limit: Option<usize>,
+
+    /// Skip this many results
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    offset: usize,
+
+    /// List the child options of submodule options
+    #[arg(long)]
+    expand: bool,
+
+    /// How to write results out
+    #[arg(long, value_enum, default_value = "ansi", conflicts_with_all = ["json", "ndjson"])]
+    format: Format,
 
-    /// Output results as JSON
+    /// Print straight to the terminal instead of through $PAGER
+    #[arg(long)]
+    no_pager: bool,
+
+    /// Output results as a JSON array. `name` is a deprecated alias of the `key` of results
     #[arg(short, long)]
     json: bool,
-    
-    /// Generate completions for the specified shell
-    #[arg(long = "generate", value_enum)]
-    generator: Option<ShellCompletion>,
 
-    #[arg(long = "print-man")]
-    man: bool,
+    /// Output results as JSON, one line per result, as soon as each source finds them
+    #[arg(long, conflicts_with_all = ["json", "pick"])]
+    ndjson: bool,
+
+    /// Read queries from stdin, one per line, and print the results of each under it, or as a
+    /// JSON object of the query and its results with --json and --ndjson
+    #[arg(long, conflicts_with_all = ["QUERY", "TERMS", "pick", "open", "copy", "show_value"])]
+    stdin: bool,
+    /// Open the file defining the first result, or the picked one, in $EDITOR
+    #[arg(long, conflicts_with_all = ["json", "ndjson"])]
+    open: bool,
+    /// Copy the key of the first result, or the picked one, to the clipboard, or its documentation
+    /// with --copy=doc
+    #[arg(long, value_enum, value_name = "WHAT", num_args = 0..=1, require_equals = true, default_missing_value = "key", conflicts_with = "ndjson")]
+    copy: Option<CopyWhat>,
+}
+
+impl SearchArgs {
+    fn option_display(&self) -> OptionDisplay {
+        OptionDisplay {
+            defaults: self.show_defaults,
+            declarations: self.show_declared_in,
+            urls: self.urls,
+        }
+    }
+
+    fn limit(&self) -> Option<usize> {
+        match self.limit {
+            Some(0) => None,
+            Some(limit) => Some(limit),
+            None if self.json || self.ndjson => None,
+            None => Some(DEFAULT_LIMIT),
+        }
+    }
+
+    /// Whether the search only writes results out, which `manix daemon` can do in its place
+    fn can_use_daemon(&self) -> bool {
+        !self.stdin && !self.pick && self.copy.is_none() && !self.open && !self.sources.update_cache
+    }
+}
+
+impl SourceArgs {
+    /// What the sources loaded depend on, for `manix daemon` to tell whether a search from another
+    /// directory or environment loads the ones it has. Paths are made absolute in the current
+    /// directory, flakes given as paths too
+    fn loading(&self) -> daemon::Loading {
+        let canonical = |path: &Path| std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        let mut args = self.clone();
+        args.options_expr = args.options_expr.as_deref().map(canonical);
+        args.nixos_options_json = args.nixos_options_json.as_deref().map(canonical);
+        args.flake = args.flake.map(|flake| {
+            let (scheme, reference) = match flake.strip_prefix("path:") {
+                Some(reference) => ("path:", reference),
+                None => ("", &*flake),
+            };
+            if !reference.starts_with(['.', '/']) {
+                return flake.clone();
+            }
+            let end = reference.find(['?', '#']).unwrap_or(reference.len());
+            let (path, rest) = reference.split_at(end);
+            format!("{}{}{}", scheme, canonical(Path::new(path)).display(), rest)
+        });
+        let mut sources = args.to_args();
+        if self.online {
+            sources.push("--online".into());
+        }
+        daemon::Loading {
+            sources: sources
+                .iter()
+                .map(|arg| arg.to_string_lossy().into_owned())
+                .collect(),
+            env: DAEMON_ENV
+                .iter()
+                .map(|name| (name.to_string(), std::env::var(name).ok()))
+                .collect(),
+        }
+    }
+
+    /// The arguments choosing the same sources in another manix, for `manix update` refreshing
+    /// their caches
+    fn to_args(&self) -> Vec<std::ffi::OsString> {
+        let names = |sources: &[Source]| {
+            sources
+                .iter()
+                .filter_map(|source| Some(source.to_possible_value()?.get_name().to_string()))
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+        let mut args = vec!["--source".into(), names(&self.source).into()];
+        if !self.exclude_source.is_empty() {
+            args.extend([
+                "--exclude-source".into(),
+                names(&self.exclude_source).into(),
+            ]);
+        }
+        if let Some(flake) = &self.flake {
+            args.extend(["--flake".into(), flake.into()]);
+        }
+        if let Some(expression) = &self.options_expr {
+            args.extend(["--options-expr".into(), expression.into()]);
+        }
+        if let Some(options_json) = &self.nixos_options_json {
+            args.extend(["--nixos-options-json".into(), options_json.into()]);
+        }
+        if let Some(channel) = &self.channel {
+            args.extend(["--channel".into(), channel.into()]);
+        }
+        if let Some(nixpkgs) = &self.nixpkgs {
+            args.extend(["--nixpkgs".into(), nixpkgs.into()]);
+        }
+        if self.project {
+            args.push("--project".into());
+        }
+        args
+    }
+
+    /// The flake.lock whose inputs the caches are built from, the one of the current directory
+    /// when project caches are asked for and no other nixpkgs is
+    fn project_lock(&self, config: &Config) -> Option<PathBuf> {
+        let other_nixpkgs = self.channel.is_some() || self.nixpkgs.is_some();
+        if !(self.project || config.project_caches) || other_nixpkgs {
+            return None;
+        }
+        flake_lock::find(&std::env::current_dir().ok()?)
+    }
+}
+
+/// Reports on caches built in parallel, with a spinner for each one being built that turns into
+/// a line once it's done. Only the lines are written when stderr isn't a terminal
+struct Progress {
+    bars: MultiProgress,
+    total: usize,
+    done: AtomicUsize,
+}
+
+impl Progress {
+    fn new(total: usize) -> Self {
+        eprintln!("Building {} caches...", total);
+        Self {
+            bars: MultiProgress::new(),
+            total,
+            done: AtomicUsize::new(0),
+        }
+    }
+
+    fn started(&self, name: &str) -> ProgressBar {
+        let bar = self.bars.add(ProgressBar::new_spinner());
+        if let Ok(style) = ProgressStyle::with_template("{spinner} {msg} {elapsed}") {
+            bar.set_style(style);
+        }
+        bar.set_message(format!("Building {} cache", name));
+        bar.enable_steady_tick(Duration::from_millis(100));
+        bar
+    }
+
+    fn finished(&self, bar: ProgressBar, name: &str, built: bool) {
+        let done = self.done.fetch_add(1, Ordering::SeqCst) + 1;
+        let line = if built {
+            format!(
+                "[{}/{}] Built {} cache in {:.1}s",
+                done,
+                self.total,
+                name,
+                bar.elapsed().as_secs_f32()
+            )
+        } else {
+            format!(
+                "[{}/{}] Failed to build {} cache",
+                done,
+                self.total,
+                name.red()
+            )
+        };
+        if self.bars.is_hidden() {
+            eprintln!("{}", line);
+        } else {
+            if let Ok(style) = ProgressStyle::with_template("{msg}") {
+                bar.set_style(style);
+            }
+            bar.finish_with_message(line);
+        }
+    }
+}
+
+/// A cache to be built along with the others, handing over the source once it's saved
+type BuildJob<'a> =
+    Box<dyn FnOnce(&Progress) -> Option<Box<dyn DocSource + Sync + Send>> + Send + 'a>;
+
+fn build_job<'a, T>(source: T, name: &'a str, path: &'a Path, lock: &'a CacheLock) -> BuildJob<'a>
+where
+    T: 'static + DocSource + Cache + Sync + Send,
+{
+    Box::new(move |progress| {
+        let bar = progress.started(name);
+        let built = build_source(source, name, path, lock);
+        progress.finished(bar, name, built.is_some());
+        Some(Box::new(built?) as Box<dyn DocSource + Sync + Send>)
+    })
+}
+
+/// Builds the caches at the same time, each in its thread. Handed over in the order of the jobs
+fn build_in_parallel<'a, K>(
+    jobs: Vec<(K, BuildJob<'a>)>,
+) -> Vec<(K, Option<Box<dyn DocSource + Sync + Send>>)>
+where
+    K: Send,
+{
+    let progress = Progress::new(jobs.len());
+    let progress = &progress;
+    std::thread::scope(|scope| {
+        let handles = jobs
+            .into_iter()
+            .map(|(key, job)| (key, scope.spawn(move || job(progress))))
+            .collect::<Vec<_>>();
+        handles
+            .into_iter()
+            .map(|(key, handle)| (key, handle.join().ok().flatten()))
+            .collect()
+    })
 }
 
 fn build_source_and_add<T>(
-    mut source: T,
+    source: T,
     name: &str,
-    path: &PathBuf,
-    aggregate: Option<&mut AggregateDocSource>,
+    path: &Path,
+    aggregate: &mut AggregateDocSource,
+    lock: &CacheLock,
 ) -> Option<()>
 where
     T: 'static + DocSource + Cache + Sync,
+{
+    let source = build_missing_source(source, name, path, lock)?;
+    aggregate.add_source(Box::new(source));
+    Some(())
+}
+
+/// Builds a source the cache of which can't be used, telling so since the search waits for it
+fn build_missing_source<T>(source: T, name: &str, path: &Path, lock: &CacheLock) -> Option<T>
+where
+    T: DocSource + Cache,
 {
     eprintln!("Building {} cache...", name);
+    build_source(source, name, path, lock)
+}
+
+/// Updates the source and saves its cache, telling what went wrong if anything did. The caches
+/// are only locked for the others while it's saved
+fn build_source<T>(mut source: T, name: &str, path: &Path, lock: &CacheLock) -> Option<T>
+where
+    T: DocSource + Cache,
+{
     if let Err(e) = source
         .update()
         .with_context(|| anyhow::anyhow!("Failed to update {}", name))
@@ -94,28 +962,23 @@ where
         return None;
     }
 
-    if let Err(e) = source
-        .save(path)
+    if let Err(e) = lock
+        .exclusively(|| Ok(source.save(path)?))
         .with_context(|| format!("Failed to save {} cache", name))
     {
         eprintln!("{:?}", e);
         return None;
     }
-
-    if let Some(aggregate) = aggregate {
-        aggregate.add_source(Box::new(source));
-    }
-    Some(())
+    Some(source)
 }
 
-fn load_source_and_add<T>(
+fn load_source<T>(
     load_result: Result<Result<T, Errors>, std::io::Error>,
     name: &str,
-    aggregate: &mut AggregateDocSource,
     ignore_file_io_error: bool,
-) -> Option<()>
+) -> Option<T>
 where
-    T: 'static + DocSource + Cache + Sync,
+    T: DocSource + Cache,
 {
     let load_result = match load_result {
         Err(e) => {
@@ -132,10 +995,101 @@ where
             eprintln!("{:?}", e);
             None
         }
-        Ok(source) => {
-            aggregate.add_source(Box::new(source));
-            Some(())
+        Ok(source) => Some(source),
+    }
+}
+
+/// Builds a source when `rebuild` is set, has it loaded from its cache once it's searched
+/// otherwise. None when the source couldn't be built or has no cache
+fn load_or_rebuild_source_and_add<T>(
+    build: impl FnOnce() -> T + Send + 'static,
+    name: &'static str,
+    path: &Path,
+    rebuild: bool,
+    aggregate: &mut AggregateDocSource,
+    ignore_file_io_error: bool,
+    lock: &CacheLock,
+) -> Option<()>
+where
+    T: 'static + DocSource + Cache + Sync + Send + serde::de::DeserializeOwned,
+{
+    if rebuild {
+        build_source_and_add(build(), name, path, aggregate, lock)
+    } else {
+        load_lazily_and_add(build, name, path, aggregate, ignore_file_io_error, lock)
+    }
+}
+
+/// Adds a source that's read from its cache only once it's first searched. A cache left behind by
+/// another manix or nixpkgs is told by its header, and the source rebuilt at once so that it's
+/// done while the caches are locked. A cache that turns out to be corrupt when it's read is moved
+/// aside, to be rebuilt by the next search. None when the source couldn't be built or has no
+/// cache
+fn load_lazily_and_add<T>(
+    build: impl FnOnce() -> T,
+    name: &'static str,
+    path: &Path,
+    aggregate: &mut AggregateDocSource,
+    ignore_file_io_error: bool,
+    lock: &CacheLock,
+) -> Option<()>
+where
+    T: 'static + DocSource + Cache + Sync + Send + serde::de::DeserializeOwned,
+{
+    if let Err(e) = std::fs::metadata(path) {
+        if !ignore_file_io_error {
+            eprintln!("Failed to load {} cache file: {:?}", name, e);
+        }
+        return None;
+    }
+    if T::is_outdated(path) {
+        return build_source_and_add(build(), name, path, aggregate, lock);
+    }
+    let path = path.to_path_buf();
+    aggregate.add_source(Box::new(LazyDocSource::new(move || {
+        let content = match std::fs::read(&path) {
+            Ok(content) => content,
+            Err(e) => {
+                eprintln!("Failed to load {} cache file: {:?}", name, e);
+                return None;
+            }
+        };
+        let loaded = T::load(&content);
+        if let Err(Errors::Bincode(_)) = loaded {
+            // replaced by rename only, so moving it aside doesn't take away one being written
+            match quarantine_cache(&path) {
+                Ok(quarantined) => eprintln!(
+                    "The {} cache is corrupt, moved it to {} to be rebuilt by the next search",
+                    name,
+                    quarantined.display()
+                ),
+                Err(e) => eprintln!("The {} cache is corrupt: {:?}", name, e),
+            }
+            return None;
         }
+        let source = load_source(Ok(loaded), name, false)?;
+        Some(Box::new(source) as Box<dyn DocSource + Sync + Send>)
+    })));
+    Some(())
+}
+
+/// Builds a source first if its cache is missing, outdated or `rebuild` is set, has it loaded
+/// from its cache once it's searched otherwise
+fn load_or_build_source_and_add<T>(
+    source: T,
+    name: &'static str,
+    path: &Path,
+    rebuild: bool,
+    aggregate: &mut AggregateDocSource,
+    lock: &CacheLock,
+) -> Option<()>
+where
+    T: 'static + DocSource + Cache + Sync + Send + serde::de::DeserializeOwned,
+{
+    if rebuild || !path.exists() {
+        build_source_and_add(source, name, path, aggregate, lock)
+    } else {
+        load_lazily_and_add(|| source, name, path, aggregate, false, lock)
     }
 }
 
@@ -143,7 +1097,146 @@ fn print_completions<G: Generator, W: Write>(gen: G, cmd: &mut Command, writer:
     generate(gen, cmd, cmd.get_name().to_string(), writer);
 }
 
-fn print_human_output<W: Write>(results: &SearchResults, writer: &mut W) -> Result<()> {
+/// Completes keys on top of the generated completions, unless an option's value is completed
+const BASH_KEY_COMPLETION: &str = r#"
+_manix_keys() {
+    _manix "$@"
+    local cur="${COMP_WORDS[COMP_CWORD]}" prev="${COMP_WORDS[COMP_CWORD-1]}"
+    case "$prev" in
+        VALUE_OPTIONS) return ;;
+    esac
+    if [[ "$cur" != -* ]]; then
+        local keys
+        mapfile -t keys < <(manix list --next-segment --prefix "$cur" 2>/dev/null)
+        COMPREPLY+=("${keys[@]}")
+        if [[ ${#COMPREPLY[@]} -eq 1 && "${COMPREPLY[0]}" == *. ]]; then
+            compopt -o nospace
+        fi
+    fi
+}
+
+complete -F _manix_keys -o bashdefault -o default manix
+"#;
+
+/// Used as the action of the arguments taking keys, prefixes of keys go without a space
+const ZSH_KEY_COMPLETION: &str = r#"(( $+functions[_manix_keys] )) ||
+_manix_keys() {
+    local -a keys
+    keys=(${(f)"$(manix list --next-segment --prefix "$PREFIX" 2>/dev/null)"})
+    compadd -S '' -- ${(M)keys:#*.}
+    compadd -- ${keys:#*.}
+}
+
+"#;
+
+const FISH_KEY_COMPLETION: &str = r#"
+function __manix_keys
+    manix list --next-segment --prefix (commandline -ct) 2>/dev/null
+end
+
+complete -c manix -f -n 'not string match -q -- "-*" (commandline -ct)' -a '(__manix_keys)'
+"#;
+
+/// Options of every subcommand that take a value, which keys aren't completed after
+fn value_options(cmd: &Command) -> Vec<String> {
+    let mut options = cmd
+        .get_arguments()
+        .filter(|arg| arg.get_action().takes_values() && !arg.is_positional())
+        .flat_map(|arg| {
+            let short = arg.get_short().map(|short| format!("-{}", short));
+            let long = arg.get_long().map(|long| format!("--{}", long));
+            short.into_iter().chain(long)
+        })
+        .collect::<Vec<_>>();
+    for subcommand in cmd.get_subcommands() {
+        options.extend(value_options(subcommand));
+    }
+    options.sort_unstable();
+    options.dedup();
+    options
+}
+
+/// The completion script of the shell. Those of bash, zsh and fish also complete keys, a segment
+/// at a time, by calling back into `manix list`
+fn print_shell_completions<W: Write>(shell: ShellCompletion, writer: &mut W) -> Result<()> {
+    let mut cmd = Opt::command();
+    let mut script = Vec::new();
+    match shell {
+        ShellCompletion::Bash => print_completions(Shell::Bash, &mut cmd, &mut script),
+        ShellCompletion::Elvish => print_completions(Shell::Elvish, &mut cmd, &mut script),
+        ShellCompletion::Fish => print_completions(Shell::Fish, &mut cmd, &mut script),
+        ShellCompletion::Nu => {
+            print_completions(clap_complete_nushell::Nushell, &mut cmd, &mut script)
+        }
+        ShellCompletion::Powershell => print_completions(Shell::PowerShell, &mut cmd, &mut script),
+        ShellCompletion::Zsh => print_completions(Shell::Zsh, &mut cmd, &mut script),
+    }
+    let script = String::from_utf8(script).context("Completion script isn't UTF-8")?;
+    match shell {
+        ShellCompletion::Bash => {
+            let options = value_options(&cmd).join("|");
+            write!(
+                writer,
+                "{}{}",
+                script,
+                BASH_KEY_COMPLETION.replace("VALUE_OPTIONS", &options)
+            )?;
+        }
+        ShellCompletion::Zsh => {
+            // arguments are specs like `'::QUERY -- Query to search for:_cmdstring' \`, the
+            // action after the last colon
+            for line in script.lines() {
+                let takes_keys = ["'::QUERY -- ", "':KEY -- ", "'::PREFIX -- "]
+                    .iter()
+                    .any(|spec| line.starts_with(spec));
+                match line.rfind(':') {
+                    Some(action) if takes_keys => {
+                        writeln!(writer, "{}:_manix_keys' \\", &line[..action])?
+                    }
+                    _ if line.starts_with("if [ \"$funcstack[1]\" = \"_manix\" ]") => {
+                        writeln!(writer, "{}{}", ZSH_KEY_COMPLETION, line)?
+                    }
+                    _ => writeln!(writer, "{}", line)?,
+                }
+            }
+        }
+        ShellCompletion::Fish => write!(writer, "{}{}", script, FISH_KEY_COMPLETION)?,
+        _ => write!(writer, "{}", script)?,
+    }
+    Ok(())
+}
+
+fn print_human_output<W: Write>(
+    results: &SearchResults,
+    format: Format,
+    option_display: OptionDisplay,
+    show_value_of: Option<&str>,
+    expand_from: Option<&AggregateDocSource>,
+    highlighted: Option<&Highlight>,
+    writer: &mut W,
+) -> Result<()> {
+    if format == Format::table {
+        let rows = results
+            .entries
+            .iter()
+            .chain(&results.key_only_entries)
+            .map(|entry| Row {
+                key: entry.name(),
+                typ: match entry {
+                    DocEntry::OptionDoc(_, option) => option.option_type().to_string(),
+                    _ => entry.kind().to_string(),
+                },
+                description: markup::render(&entry.text()),
+            })
+            .collect::<Vec<_>>();
+        let width = stdout_is_terminal()
+            .then(terminal_size)
+            .flatten()
+            .map(|(_, width)| width);
+        write!(writer, "{}", render_table(&rows, width))?;
+        return Ok(());
+    }
+
     if !results.key_only_entries.is_empty() {
         const SHOW_MAX_LEN: usize = 50;
         write!(writer, "{}", "Here's what I found in nixpkgs:".bold())?;
@@ -151,67 +1244,1326 @@ fn print_human_output<W: Write>(results: &SearchResults, writer: &mut W) -> Resu
             write!(writer, " {}", entry.name().white())?;
         }
         if results.key_only_entries.len() > SHOW_MAX_LEN {
-            write!(writer, " and {} more.", results.key_only_entries.len() - SHOW_MAX_LEN)?;
+            write!(
+                writer,
+                " and {} more.",
+                results.key_only_entries.len() - SHOW_MAX_LEN
+            )?;
         }
         writeln!(writer, "\n")?;
     }
 
-    for entry in &results.entries {
-        const LINE: &str = "────────────────────";
-        writeln!(
-            writer,
-            "{}\n{}\n{}",
-            entry.source().white(),
-            LINE.green(),
-            entry.pretty_printed()
-        )?;
+    let line = if format == Format::plain {
+        "--------------------"
+    } else {
+        "────────────────────"
+    };
+    for (source, entries) in results.by_source() {
+        let header = format!("{} ({})", source, entries.len());
+        if format == Format::markdown {
+            writeln!(writer, "# {}\n", header)?;
+        } else {
+            writeln!(writer, "{}\n{}", header.bold(), line.green())?;
+        }
+        for entry in entries {
+            print_entry(
+                entry,
+                format,
+                option_display,
+                show_value_of,
+                expand_from,
+                highlighted,
+                writer,
+            )?;
+        }
     }
 
     Ok(())
 }
 
-fn print_json_output<W: Write>(results: &SearchResults, writer: &mut W) -> Result<()> {
-    serde_json::to_writer(&mut *writer, results)
-        .context("Failed to serialize search results as JSON")?;
-    writeln!(writer)?;
-    Ok(())
-}
+fn print_entry<W: Write>(
+    entry: &DocEntry,
+    format: Format,
+    option_display: OptionDisplay,
+    show_value_of: Option<&str>,
+    expand_from: Option<&AggregateDocSource>,
+    highlighted: Option<&Highlight>,
+    writer: &mut W,
+) -> Result<()> {
+    if format == Format::markdown {
+        write!(writer, "{}", entry.markdown(option_display))?;
+    } else {
+        let printed = match entry {
+            DocEntry::OptionDoc(typ, option) => option.pretty_printed_with(*typ, option_display),
+            _ => entry.pretty_printed(),
+        };
+        let printed = match highlighted {
+            Some(highlighted) if colored::control::SHOULD_COLORIZE.should_colorize() => {
+                highlighted.apply(&printed)
+            }
+            _ => printed,
+        };
+        writeln!(writer, "{}", printed)?;
+    }
 
-fn main() -> Result<()> {
+    if let DocEntry::OptionDoc(typ, option) = entry {
+        if show_value_of == Some(option.name().as_str()) {
+            match get_configured_value(*typ, option) {
+                Ok(Some(value)) => writeln!(writer, "{}\n{}\n", "value:".bold(), value)?,
+                Ok(None) => {}
+                Err(e) => eprintln!("Failed to evaluate {}: {:?}", option.name(), e),
+            }
+        }
+    }
+
+    if let (Some(source), DocEntry::OptionDoc(typ, option)) = (expand_from, entry) {
+        if option.is_submodule() {
+            print_child_options(source, *typ, option, writer)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists the candidates, indices into the entries, and reads which one to show. Nothing chosen
+/// shows every entry
+fn pick_entry<W: Write>(
+    entries: &[DocEntry],
+    candidates: &[usize],
+    writer: &mut W,
+) -> Result<Option<usize>> {
+    for (number, &i) in candidates.iter().enumerate() {
+        let entry = &entries[i];
+        writeln!(
+            writer,
+            "{}) {} {}",
+            number + 1,
+            entry.name().blue().bold(),
+            format!("({})", entry.source()).white()
+        )?;
+    }
+    write!(writer, "Pick an entry [1-{}]: ", candidates.len())?;
+    writer.flush()?;
+
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    match line.parse::<usize>() {
+        Ok(number) if (1..=candidates.len()).contains(&number) => Ok(Some(candidates[number - 1])),
+        _ => anyhow::bail!("Invalid choice {:?}", line),
+    }
+}
+
+fn print_child_options<W: Write>(
+    source: &AggregateDocSource,
+    typ: OptionsDatabaseType,
+    option: &OptionDocumentation,
+    writer: &mut W,
+) -> Result<()> {
+    let name = option.name();
+    let prefix = format!("{}.", name).to_ascii_lowercase();
+    let mut children = source
+        .search(&Lowercase(prefix.as_bytes()))
+        .into_iter()
+        .filter_map(|child| match child {
+            DocEntry::OptionDoc(child_typ, child) if child_typ == typ => Some(child),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    if children.is_empty() {
+        return Ok(());
+    }
+    children.sort_by_key(|child| child.name());
+
+    writeln!(writer, "{}", "options:".bold())?;
+    for child in children {
+        let child_name = child.name();
+        writeln!(
+            writer,
+            "  {} ({})",
+            child_name[name.len() + 1..].white(),
+            child.option_type()
+        )?;
+    }
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Prints every result as a line of JSON as soon as its source is done, unranked
+fn print_ndjson_output<W: Write>(
+    aggregate_source: &AggregateDocSource,
+    search_source: &(dyn Fn(&dyn DocSource) -> Vec<DocEntry> + Sync),
+    args: &SearchArgs,
+    writer: &mut W,
+) -> Result<bool> {
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            aggregate_source.search_streaming(search_source, &|entries| {
+                // the receiver is only gone once enough results were printed
+                let _ = sender.send(entries);
+            })
+        });
+
+        let results = receiver.into_iter().flatten().skip(args.offset);
+        let mut found = false;
+        for entry in results.take(args.limit().unwrap_or(usize::MAX)) {
+            serde_json::to_writer(&mut *writer, &entry)
+                .context("Failed to serialize search result as JSON")?;
+            writeln!(writer)?;
+            writer.flush()?;
+            found = true;
+        }
+        Ok(found)
+    })
+}
+
+/// Rows and columns of the terminal. std can't tell, so they're asked from `stty` or taken from
+/// `$LINES` and `$COLUMNS`. In `manix daemon`, the ones of the terminal of the search
+fn terminal_size() -> Option<(usize, usize)> {
+    if IN_DAEMON.load(Ordering::SeqCst) {
+        return CLIENT_TERMINAL
+            .lock()
+            .unwrap()
+            .and_then(|terminal| terminal.size);
+    }
+    let from_stty = || {
+        let tty = std::fs::File::open("/dev/tty").ok()?;
+        let output = std::process::Command::new("stty")
+            .arg("size")
+            .stdin(tty)
+            .stderr(std::process::Stdio::null())
+            .output()
+            .ok()?;
+        let size = String::from_utf8(output.stdout).ok()?;
+        let mut size = size.split_whitespace().map(|n| n.parse().ok());
+        Some((size.next()??, size.next()??))
+    };
+    let from_env = || {
+        let var = |name| std::env::var(name).ok()?.parse().ok();
+        Some((var("LINES")?, var("COLUMNS")?))
+    };
+    from_stty().or_else(from_env)
+}
+
+/// Commands writing their stdin to the clipboard, tried in order
+const CLIPBOARD_COMMANDS: &[&[&str]] = &[
+    &["wl-copy"],
+    &["xclip", "-selection", "clipboard"],
+    &["xsel", "--clipboard", "--input"],
+    &["pbcopy"],
+    &["clip.exe"],
+];
+
+fn copy_to_clipboard(entry: &DocEntry, what: CopyWhat, format: Format) -> Result<()> {
+    let text = match what {
+        CopyWhat::key => entry.name(),
+        CopyWhat::doc => {
+            colored::control::set_override(false);
+            let doc = entry.pretty_printed();
+            if format == Format::ansi {
+                colored::control::unset_override();
+            }
+            doc.trim().to_string()
+        }
+    };
+    for command in CLIPBOARD_COMMANDS {
+        let child = std::process::Command::new(command[0])
+            .args(&command[1..])
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+        let Ok(mut child) = child else {
+            continue;
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+        if child.wait()?.success() {
+            eprintln!("Copied {} to the clipboard", entry.name());
+            return Ok(());
+        }
+    }
+    anyhow::bail!("Failed to copy, none of wl-copy, xclip, xsel, pbcopy or clip.exe worked")
+}
+
+/// Opens the file defining the first of the entries that has one in $VISUAL or $EDITOR, at the
+/// defining line when it's known
+fn open_in_editor(entries: &[DocEntry]) -> Result<()> {
+    let (path, line) = entries
+        .iter()
+        .find_map(|entry| entry.location())
+        .context("None of the results is known to be defined in a file")?;
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut words = editor.split_whitespace();
+    let program = words.next().context("$EDITOR is empty")?;
+    let mut command = std::process::Command::new(program);
+    command.args(words);
+    let name = std::path::Path::new(program)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(program);
+    // most editors take `+LINE FILE`, these take `FILE:LINE` instead
+    match (name, line) {
+        ("code" | "codium", Some(line)) => {
+            command
+                .arg("--goto")
+                .arg(format!("{}:{}", path.display(), line))
+        }
+        ("hx" | "helix" | "subl" | "zed", Some(line)) => {
+            command.arg(format!("{}:{}", path.display(), line))
+        }
+        (_, Some(line)) => command.arg(format!("+{}", line)).arg(&path),
+        (_, None) => command.arg(&path),
+    };
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run {}", editor))?;
+    if !status.success() {
+        anyhow::bail!("{} exited with {}", editor, status);
+    }
+    Ok(())
+}
+
+/// Writes the output through `$PAGER`, or `less -R`, when it doesn't fit on the terminal
+fn write_paged<W: Write>(output: &[u8], no_pager: bool, writer: &mut W) -> Result<()> {
+    let fits = || {
+        let lines = output.iter().filter(|&&b| b == b'\n').count();
+        terminal_size().is_none_or(|(height, _)| lines < height)
+    };
+    if no_pager || !stdout_is_terminal() || fits() {
+        writer.write_all(output)?;
+        return Ok(());
+    }
+
+    let pager = std::env::var("PAGER")
+        .ok()
+        .filter(|pager| !pager.trim().is_empty())
+        .unwrap_or_else(|| "less -R".to_string());
+    let mut words = pager.split_whitespace();
+    let spawned = std::process::Command::new(words.next().unwrap_or("less"))
+        .args(words)
+        .stdin(std::process::Stdio::piped())
+        .spawn();
+    let Ok(mut child) = spawned else {
+        writer.write_all(output)?;
+        return Ok(());
+    };
+    if let Some(mut stdin) = child.stdin.take() {
+        // quitting the pager early closes its input, which isn't an error
+        let _ = stdin.write_all(output);
+    }
+    child.wait().context("Failed to wait for the pager")?;
+    Ok(())
+}
+
+fn print_json_output<W: Write>(results: &SearchResults, writer: &mut W) -> Result<()> {
+    let entries = results
+        .entries
+        .iter()
+        .chain(&results.key_only_entries)
+        .collect::<Vec<_>>();
+    serde_json::to_writer(&mut *writer, &entries)
+        .context("Failed to serialize search results as JSON")?;
+    writeln!(writer)?;
+    Ok(())
+}
+
+/// Exit status when nothing was found, searches exit with 0 when something was
+const EXIT_NOT_FOUND: u8 = 1;
+/// Exit status of wrong usage, which clap also exits with
+const EXIT_USAGE: u8 = 2;
+/// Exit status when a cache or another file couldn't be read or written
+const EXIT_IO: u8 = 3;
+
+/// Set in `manix daemon`, the output of which goes to other processes rather than its stdout
+static IN_DAEMON: AtomicBool = AtomicBool::new(false);
+/// The terminal of the search `manix daemon` is answering, if its output goes to one
+static CLIENT_TERMINAL: std::sync::Mutex<Option<daemon::Terminal>> = std::sync::Mutex::new(None);
+
+/// Fails a search run by `manix daemon` that would ask which result to show, which the search
+/// then does on its own
+#[derive(Debug)]
+struct NeedsTerminal;
+
+impl std::fmt::Display for NeedsTerminal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the search asks which result to show")
+    }
+}
+
+impl std::error::Error for NeedsTerminal {}
+
+fn main() -> ExitCode {
     let opt: Opt = Opt::parse();
+    if let Some(status) = search_with_daemon(&opt) {
+        return status;
+    }
+    match run(opt) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(EXIT_NOT_FOUND),
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(exit_status(&err))
+        }
+    }
+}
+
+fn exit_status(err: &anyhow::Error) -> u8 {
+    let io = err
+        .chain()
+        .any(|cause| cause.is::<io::Error>() || cause.is::<Errors>());
+    if io {
+        EXIT_IO
+    } else {
+        EXIT_USAGE
+    }
+}
+
+/// Whether what's written goes to a terminal, from `manix daemon` the one of the search it answers
+fn stdout_is_terminal() -> bool {
+    if IN_DAEMON.load(Ordering::SeqCst) {
+        return CLIENT_TERMINAL.lock().unwrap().is_some();
+    }
+    io::stdout().is_terminal()
+}
+
+/// Whether results can be picked from on the terminal, which `manix daemon` leaves to the search
+fn is_interactive() -> bool {
+    if IN_DAEMON.load(Ordering::SeqCst) {
+        return CLIENT_TERMINAL
+            .lock()
+            .unwrap()
+            .is_some_and(|terminal| terminal.interactive);
+    }
+    io::stdin().is_terminal() && io::stdout().is_terminal()
+}
+
+/// Runs what the options ask for, returning whether anything was found
+fn run(opt: Opt) -> Result<bool> {
     let stdout = io::stdout();
     let mut stdout = stdout.lock();
 
     if let Some(generator) = opt.generator {
-        let mut cmd = Opt::command();
         eprintln!("Generating completion file for {generator:?}...");
 
-	    match generator {
-	    	ShellCompletion::Bash => print_completions(Shell::Bash, &mut cmd, &mut stdout),
-	    	ShellCompletion::Elvish => print_completions(Shell::Elvish, &mut cmd, &mut stdout),
-	    	ShellCompletion::Fish => print_completions(Shell::Fish , &mut cmd, &mut stdout),
-	    	ShellCompletion::Nu => print_completions(clap_complete_nushell::Nushell , &mut cmd, &mut stdout),
-	    	ShellCompletion::Powershell => print_completions(Shell::PowerShell, &mut cmd, &mut stdout),
-	    	ShellCompletion::Zsh => print_completions(Shell::Zsh , &mut cmd, &mut stdout)
-	    }
+        print_shell_completions(generator, &mut stdout)?;
 
-        Ok(())
-    } else {
+        return Ok(true);
+    }
 
     if opt.man {
         let cmd = Opt::command();
         eprintln!("Generating manpage...");
-        
+
         Man::new(cmd)
             .render(&mut stdout)
             .context("Failed to render manpage")?;
 
-        Ok(())
-    } else {
+        return Ok(true);
+    }
 
     let cache_dir =
         xdg::BaseDirectories::with_prefix("manix").context("Failed to get a cache directory")?;
 
+    match opt.command {
+        None => search(opt.search, &cache_dir, &mut stdout),
+        Some(Subcommand::Search(args)) => search(args, &cache_dir, &mut stdout),
+        Some(Subcommand::Show(args)) => show(args, &cache_dir, &mut stdout),
+        Some(Subcommand::Update(UpdateArgs {
+            fetch: true,
+            sources,
+            ..
+        })) => {
+            fetch_caches(&sources, &cache_dir)?;
+            Ok(true)
+        }
+        Some(Subcommand::Update(UpdateArgs { sources, only, .. })) => {
+            let args = if only.is_empty() {
+                SourceArgs {
+                    update_cache: true,
+                    ..sources
+                }
+            } else {
+                SourceArgs {
+                    source: only.clone(),
+                    rebuild: only,
+                    ..sources
+                }
+            };
+            load_sources(&args, &cache_dir)?;
+            Ok(true)
+        }
+        Some(Subcommand::Cache(CacheCommand::Path)) => {
+            writeln!(stdout, "{}", cache_dir.get_cache_home().display())?;
+            Ok(true)
+        }
+        Some(Subcommand::Cache(CacheCommand::Status)) => {
+            let cache_home = cache_dir.get_cache_home();
+            let now = history::now();
+            let mut missing = Vec::new();
+            for (name, file, status_of) in known_caches(&cache_home) {
+                let path = cache_home.join(&file);
+                match status_of(&path) {
+                    Some(status) => writeln!(
+                        stdout,
+                        "{}\n  {}\n  {}",
+                        name.bold(),
+                        path.display(),
+                        status.describe(now)
+                    )?,
+                    None => missing.push(name),
+                }
+            }
+            if !missing.is_empty() {
+                writeln!(stdout, "{} {}", "Not built:".bold(), missing.join(", "))?;
+            }
+            Ok(true)
+        }
+        Some(Subcommand::Cache(CacheCommand::Clear)) => {
+            let cache_home = cache_dir.get_cache_home();
+            if cache_home.exists() {
+                let _lock = CacheLock::exclusive(&cache_dir)?;
+                clear_cache_dir(&cache_home)?;
+            }
+            Ok(true)
+        }
+        Some(Subcommand::Cache(CacheCommand::Export { file })) => {
+            let bundle = CacheBundle::from_dir(&cache_dir.get_cache_home())?;
+            if bundle.files.is_empty() {
+                eprintln!("There are no caches to export yet");
+                return Ok(false);
+            }
+            bundle.save(&file)?;
+            eprintln!(
+                "Exported {} caches to {}",
+                bundle.files.len(),
+                file.display()
+            );
+            Ok(true)
+        }
+        Some(Subcommand::Cache(CacheCommand::Import { file })) => {
+            let bundle = CacheBundle::load(&file)?;
+            let cache_home = cache_dir
+                .create_cache_directory("")
+                .context("Failed to create the cache directory")?;
+            let _lock = CacheLock::exclusive(&cache_dir)?;
+            bundle.unpack(&cache_home)?;
+            eprintln!("Imported {} caches", bundle.files.len());
+            if !bundle.is_current() {
+                eprintln!(
+                    "They were exported by manix {} with nixpkgs {}, so they'll be rebuilt",
+                    bundle.manix_version, bundle.nixpkgs
+                );
+            }
+            Ok(true)
+        }
+        Some(Subcommand::Bookmark(command)) => {
+            let path = bookmarks_path(&cache_dir)?;
+            let mut bookmarks = Bookmarks::load(&path)?;
+            match command {
+                BookmarkCommand::Add { key } => {
+                    if !bookmarks.add(&key) {
+                        eprintln!("{} is bookmarked already", key);
+                    }
+                }
+                BookmarkCommand::Remove { key } => {
+                    if !bookmarks.remove(&key) {
+                        eprintln!("{} isn't bookmarked", key);
+                        return Ok(false);
+                    }
+                }
+                BookmarkCommand::List => {
+                    for key in bookmarks.keys() {
+                        writeln!(stdout, "{}", key)?;
+                    }
+                    return Ok(bookmarks.keys().next().is_some());
+                }
+            }
+            bookmarks.save(&path)?;
+            Ok(true)
+        }
+        Some(Subcommand::Tree(args)) => {
+            let aggregate_source = load_sources(&args.sources, &cache_dir)?;
+            let tree = KeyTree::from_keys(aggregate_source.all_keys(), &args.prefix);
+            if tree.is_empty() {
+                eprintln!("Nothing is below {}", args.prefix);
+                return Ok(false);
+            }
+            write!(stdout, "{}", tree.render(&args.prefix, args.depth))?;
+            Ok(true)
+        }
+        Some(Subcommand::Completions { shell }) => {
+            print_shell_completions(shell, &mut stdout)?;
+            Ok(true)
+        }
+        Some(Subcommand::Daemon(args)) => run_daemon(args, &cache_dir),
+        Some(Subcommand::Doctor) => run_doctor(&cache_dir, &mut stdout),
+        Some(Subcommand::Export(args)) => {
+            let aggregate_source = load_sources(&args.sources, &cache_dir)?;
+            let entries = aggregate_source.all_entries();
+            // descriptions are written without escape codes
+            colored::control::set_override(false);
+            match (args.html, args.format, args.output) {
+                (Some(path), _, _) => {
+                    export::write_html_site(&entries, &path)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                }
+                (None, Some(ExportFormat::man), None) => {
+                    write!(stdout, "{}", export::man_page(&entries))?
+                }
+                (None, Some(ExportFormat::man), Some(path)) => {
+                    std::fs::write(&path, export::man_page(&entries))
+                        .with_context(|| format!("Failed to write {}", path.display()))?
+                }
+                (None, Some(ExportFormat::docset), path) => {
+                    let path = path.unwrap_or_else(|| PathBuf::from("manix.docset"));
+                    export::write_docset(&entries, &path)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                }
+                (None, None, _) => unreachable!("clap requires --format without --html"),
+            }
+            Ok(true)
+        }
+        Some(Subcommand::List(args)) => {
+            let aggregate_source = load_sources(&args.sources, &cache_dir)?;
+            let mut keys = aggregate_source.all_keys();
+            let prefix = args.prefix.as_deref().unwrap_or("");
+            keys.retain(|key| key.starts_with(prefix));
+            if args.next_segment {
+                // `services.ngi` lists `services.nginx.` rather than every option below it
+                for key in &mut keys {
+                    if let Some(dot) = key[prefix.len()..].find('.') {
+                        *key = &key[..prefix.len() + dot + 1];
+                    }
+                }
+            }
+            keys.sort_unstable();
+            keys.dedup();
+            for key in &keys {
+                writeln!(stdout, "{}", key)?;
+            }
+            Ok(!keys.is_empty())
+        }
+    }
+}
+
+/// Runs the checks of `manix doctor`, the caches being looked at besides what the library checks.
+/// Fails when any check finds an error, warnings being about optional sources
+fn run_doctor(cache_dir: &xdg::BaseDirectories, stdout: &mut impl Write) -> Result<bool> {
+    let mut checks = Vec::new();
+    match load_config(cache_dir) {
+        Ok(config) => {
+            checks.push(doctor::Check::ok("config", "loads"));
+            options_docsource::set_nix_settings(config.nix);
+            if let Some(nixpkgs) = config.nixpkgs {
+                nixpkgs::set_nixpkgs(&nixpkgs::nix_path_location(&nixpkgs));
+            }
+        }
+        Err(e) => checks.push(doctor::Check::error(
+            "config",
+            format!("{:#}", e),
+            "Fix the config file, or remove it to go with the defaults",
+        )),
+    }
+    checks.extend(doctor::check_nix());
+    checks.extend(doctor::check_nix_path());
+    let cache_home = cache_dir.get_cache_home();
+    checks.push(doctor::check_cache_dir(&cache_home));
+    let now = history::now();
+    for (name, file, status_of) in known_caches(&cache_home) {
+        let Some(status) = status_of(&cache_home.join(&file)) else {
+            continue;
+        };
+        checks.push(if status.outdated || status.entries.is_none() {
+            doctor::Check::error(
+                &name,
+                status.describe(now),
+                "Run `manix update` to rebuild it",
+            )
+        } else {
+            doctor::Check::ok(&name, status.describe(now))
+        });
+    }
+    // without nix-instantiate every one of them would fail the same way
+    if checks
+        .iter()
+        .all(|check| check.what != "nix-instantiate" || check.status == doctor::Status::Ok)
+    {
+        eprintln!("Evaluating the bundled expressions...");
+        checks.extend(doctor::check_expressions());
+    }
+
+    for check in &checks {
+        let mark = match check.status {
+            doctor::Status::Ok => "✓".green(),
+            doctor::Status::Warning => "!".yellow(),
+            doctor::Status::Error => "✗".red(),
+        };
+        writeln!(stdout, "{} {}: {}", mark, check.what.bold(), check.detail)?;
+        if let Some(fix) = &check.fix {
+            writeln!(stdout, "    {}", fix)?;
+        }
+    }
+    Ok(checks
+        .iter()
+        .all(|check| check.status != doctor::Status::Error))
+}
+
+/// The keys looked at with `show` and the picker, which is kept with the data rather than the
+/// caches so that clearing them doesn't lose it
+fn load_history(cache_dir: &xdg::BaseDirectories) -> History {
+    let Some(path) = cache_dir.find_data_file("history.json") else {
+        return History::default();
+    };
+    History::load(&path).unwrap_or_else(|e| {
+        eprintln!("Failed to load the history: {:?}", e);
+        History::default()
+    })
+}
+
+/// The config file, which doesn't have to exist
+fn load_config(cache_dir: &xdg::BaseDirectories) -> Result<Config> {
+    match cache_dir.find_config_file("config.json") {
+        Some(path) => Config::load(&path)
+            .with_context(|| format!("Failed to load the config from {}", path.display())),
+        None => Ok(Config::default()),
+    }
+}
+
+/// The sources `disabled_sources` in the config and MANIX_DISABLED_SOURCES, a comma separated
+/// list, turn off for good. They're neither built nor searched
+fn disabled_sources(config: &Config) -> Result<Vec<Source>> {
+    let from_env = std::env::var("MANIX_DISABLED_SOURCES").unwrap_or_default();
+    config
+        .disabled_sources
+        .iter()
+        .map(String::as_str)
+        .chain(
+            from_env
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty()),
+        )
+        .map(|name| {
+            <Source as ValueEnum>::from_str(name, true)
+                .map_err(|_| anyhow::anyhow!("{} isn't a source that can be disabled", name))
+        })
+        .collect()
+}
+
+/// Starts `manix update` in the background when the caches were built more than `max_age` days
+/// ago, this search being answered from them as they are meanwhile. The file written along with
+/// the caches tells how old they are. Returns whether they're stale
+fn refresh_stale_caches(args: &SourceArgs, last_version_path: &Path, max_age: u64) -> bool {
+    let age = std::fs::metadata(last_version_path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok());
+    let Some(age) = age else {
+        return false;
+    };
+    if age < Duration::from_secs(max_age * 24 * 60 * 60) {
+        return false;
+    }
+    // counted as refreshed from now on, so that a refresh that fails is only tried again after
+    // another `max_age` days instead of on every search
+    let touched = std::fs::File::options()
+        .append(true)
+        .open(last_version_path)
+        .and_then(|file| file.set_modified(SystemTime::now()));
+    let Ok(exe) = touched.and_then(|_| std::env::current_exe()) else {
+        return true;
+    };
+
+    // started in the same directory, which relative paths and --project look at
+    let spawned = std::process::Command::new(exe)
+        .arg("update")
+        .args(args.to_args())
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    match spawned {
+        Ok(_) => eprintln!(
+            "The caches are {} days old, refreshing them in the background",
+            age.as_secs() / (24 * 60 * 60)
+        ),
+        Err(e) => eprintln!("Failed to refresh the caches: {:?}", e),
+    }
+    true
+}
+
+type CacheStatusOf = fn(&Path) -> Option<CacheStatus>;
+
+/// The caches `manix cache status` tells about, by name and file
+fn known_caches(cache_home: &Path) -> Vec<(String, String, CacheStatusOf)> {
+    use changelog_docsource::ChangelogDatabase;
+    use cli_docsource::CliDatabase;
+    use licenses_docsource::LicensesDatabase;
+    use maintainers_docsource::MaintainersDatabase;
+    use nixconf_docsource::NixConfDatabase;
+    use nixpkgs_tree_docsource::NixpkgsTreeDatabase;
+    use packages_docsource::PackagesDatabase;
+
+    let mut caches: Vec<(String, String, CacheStatusOf)> = [
+        (
+            "Nixpkgs Comments",
+            "comments.bin",
+            CacheStatus::of::<CommentsDatabase> as CacheStatusOf,
+        ),
+        (
+            "NixOS Options",
+            "options_nixos_database.bin",
+            CacheStatus::of::<OptionsDatabase>,
+        ),
+        (
+            "Nix-Darwin Options",
+            "options_nd_database.bin",
+            CacheStatus::of::<OptionsDatabase>,
+        ),
+        (
+            "Home Manager Options",
+            "options_hm_database.bin",
+            CacheStatus::of::<OptionsDatabase>,
+        ),
+        (
+            "Nixpkgs Tree",
+            "nixpkgs_tree.bin",
+            CacheStatus::of::<NixpkgsTreeDatabase>,
+        ),
+        (
+            "Nixpkgs Documentation",
+            "nixpkgs_doc_database.bin",
+            CacheStatus::of::<xml_docsource::XmlFuncDocDatabase>,
+        ),
+        (
+            "Nix Builtins",
+            "builtins_database.bin",
+            CacheStatus::of::<BuiltinsDatabase>,
+        ),
+        (
+            "Nixpkgs Packages",
+            "packages_database.bin",
+            CacheStatus::of::<PackagesDatabase>,
+        ),
+        (
+            "Nix CLI",
+            "cli_database.bin",
+            CacheStatus::of::<CliDatabase>,
+        ),
+        (
+            "nix.conf Settings",
+            "nixconf_database.bin",
+            CacheStatus::of::<NixConfDatabase>,
+        ),
+        (
+            "Nixpkgs Lib",
+            "nixpkgs_lib_database.bin",
+            CacheStatus::of::<lib_docsource::LibDatabase>,
+        ),
+        (
+            "Release Notes",
+            "release_notes_database.bin",
+            CacheStatus::of::<ChangelogDatabase>,
+        ),
+        (
+            "Nixpkgs Maintainers",
+            "maintainers_database.bin",
+            CacheStatus::of::<MaintainersDatabase>,
+        ),
+        (
+            "Nixpkgs Licenses",
+            "licenses_database.bin",
+            CacheStatus::of::<LicensesDatabase>,
+        ),
+        (
+            "Renamed Options",
+            "option_renames_database.bin",
+            CacheStatus::of::<renames_docsource::RenamesDatabase>,
+        ),
+        (
+            "NixOS Wiki",
+            "nixos_wiki.bin",
+            CacheStatus::of::<wiki_docsource::WikiDatabase>,
+        ),
+        (
+            "Nix RFCs",
+            "nix_rfcs.bin",
+            CacheStatus::of::<rfc_docsource::RfcDatabase>,
+        ),
+        (
+            "NUR",
+            "nur_database.bin",
+            CacheStatus::of::<nur_docsource::NurDatabase>,
+        ),
+    ]
+    .into_iter()
+    .map(|(name, file, status_of)| (name.to_string(), file.to_string(), status_of))
+    .collect();
+    for (_, typ, file) in MODULE_OPTIONS_SOURCES {
+        caches.push((
+            typ.source_name().to_string(),
+            file.to_string(),
+            CacheStatus::of::<OptionsDatabase>,
+        ));
+    }
+    for (_, set, file) in PACKAGE_SET_SOURCES {
+        caches.push((
+            set.to_string(),
+            file.to_string(),
+            CacheStatus::of::<PackagesDatabase>,
+        ));
+    }
+    for (_, typ, file) in MANUAL_SOURCES {
+        caches.push((
+            typ.source_name().to_string(),
+            file.to_string(),
+            CacheStatus::of::<ManualDatabase>,
+        ));
+    }
+    // the ones of --flake, --options-expr and options.json files are named after what they're of
+    let mut options_files = std::fs::read_dir(cache_home)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter(|file| {
+            ["options_flake_", "options_expr_", "options_json_"]
+                .iter()
+                .any(|prefix| file.starts_with(prefix))
+                && file.ends_with(".bin")
+        })
+        .collect::<Vec<_>>();
+    options_files.sort();
+    for file in options_files {
+        let name = if file.starts_with("options_flake_") {
+            "Flake Options"
+        } else if file.starts_with("options_json_") {
+            "Options From options.json"
+        } else {
+            "Custom Options"
+        };
+        caches.push((name.to_string(), file, CacheStatus::of::<OptionsDatabase>));
+    }
+    caches
+}
+
+/// The environment variables changing where the sources come from, which a search has to share
+/// with `manix daemon` for it to answer
+const DAEMON_ENV: &[&str] = &[
+    "NIX_PATH",
+    "MANIX_DISABLED_SOURCES",
+    "MANIX_SEARCH_INDEX",
+    "HOME",
+    "USER",
+    "XDG_CACHE_HOME",
+    "XDG_CONFIG_HOME",
+];
+
+/// Where `manix daemon` listens, in the runtime directory that only the user can get to
+fn daemon_socket(cache_dir: &xdg::BaseDirectories) -> Result<PathBuf> {
+    cache_dir
+        .place_runtime_file("daemon.sock")
+        .or_else(|_| cache_dir.place_cache_file("daemon.sock"))
+        .context("Failed to place the daemon socket")
+}
+
+/// Has a running `manix daemon` answer the search, the output being paged here. None when the
+/// search is to be run here, when the daemon declines it or doesn't answer in time
+fn search_with_daemon(opt: &Opt) -> Option<ExitCode> {
+    let args = match &opt.command {
+        None if opt.generator.is_none() && !opt.man => &opt.search,
+        Some(Subcommand::Search(args)) => args,
+        _ => return None,
+    };
+    if !args.can_use_daemon() {
+        return None;
+    }
+    let cache_dir = xdg::BaseDirectories::with_prefix("manix").ok()?;
+    // the daemon has the caches of the directory it was started in
+    if args
+        .sources
+        .project_lock(&load_config(&cache_dir).ok()?)
+        .is_some()
+    {
+        return None;
+    }
+    let socket = daemon_socket(&cache_dir).ok()?;
+    if !socket.exists() {
+        return None;
+    }
+    let request = daemon::Request {
+        args: std::env::args_os()
+            .map(|arg| arg.into_string())
+            .collect::<Result<_, _>>()
+            .ok()?,
+        color: colored::control::SHOULD_COLORIZE.should_colorize(),
+        terminal: io::stdout().is_terminal().then(|| daemon::Terminal {
+            size: terminal_size(),
+            interactive: io::stdin().is_terminal(),
+        }),
+        loading: args.sources.loading(),
+    };
+    match daemon::send(&socket, &request).ok()? {
+        daemon::Response::Declined => None,
+        daemon::Response::Done {
+            status,
+            stdout,
+            error,
+        } => {
+            let _ = write_paged(stdout.as_bytes(), args.no_pager, &mut io::stdout());
+            if let Some(error) = error {
+                eprintln!("Error: {}", error);
+            }
+            Some(ExitCode::from(status))
+        }
+    }
+}
+
+/// Loads the sources and answers searches with them until killed, loading them again whenever a
+/// cache gets rebuilt
+fn run_daemon(args: SourceArgs, cache_dir: &xdg::BaseDirectories) -> Result<bool> {
+    if let Some(lock) = args.project_lock(&load_config(cache_dir)?) {
+        anyhow::bail!(
+            "The daemon answers searches from every directory, so it doesn't use the caches of {}",
+            lock.display()
+        );
+    }
+    let socket = daemon_socket(cache_dir)?;
+    if UnixStream::connect(&socket).is_ok() {
+        anyhow::bail!("A manix daemon is running already on {}", socket.display());
+    }
+    // left behind by a daemon that was killed
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket)
+        .with_context(|| format!("Failed to listen on {}", socket.display()))?;
+    IN_DAEMON.store(true, Ordering::SeqCst);
+    let loading = args.loading();
+
+    let load = || -> Result<(AggregateDocSource, Option<SystemTime>)> {
+        let aggregate_source = load_sources(&args, cache_dir)?;
+        // loads every lazy source
+        aggregate_source.all_keys();
+        Ok((aggregate_source, caches_modified(cache_dir)))
+    };
+    let (mut aggregate_source, mut modified) = load()?;
+    eprintln!("Answering searches on {}", socket.display());
+    daemon::serve(&listener, |request| {
+        if caches_modified(cache_dir) != modified {
+            match load() {
+                Ok(loaded) => (aggregate_source, modified) = loaded,
+                Err(e) => eprintln!("Failed to load the sources again: {:?}", e),
+            }
+        }
+        answer(request, &loading, &aggregate_source, cache_dir)
+    })
+    .context("Failed to accept a connection")?;
+    Ok(true)
+}
+
+/// When a cache was last written, the loaded sources being out of date once that changes
+fn caches_modified(cache_dir: &xdg::BaseDirectories) -> Option<SystemTime> {
+    walkdir::WalkDir::new(cache_dir.get_cache_home())
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry
+                .path()
+                .extension()
+                .is_some_and(|extension| extension == "bin")
+        })
+        .filter_map(|entry| entry.metadata().ok()?.modified().ok())
+        .max()
+}
+
+/// Runs a search handed over to `manix daemon`, unless it's one that has to be run by the
+/// process that got it
+fn answer(
+    request: daemon::Request,
+    loading: &daemon::Loading,
+    aggregate_source: &AggregateDocSource,
+    cache_dir: &xdg::BaseDirectories,
+) -> daemon::Response {
+    let Ok(opt) = Opt::try_parse_from(&request.args) else {
+        return daemon::Response::Declined;
+    };
+    let mut args = match opt.command {
+        None if opt.generator.is_none() && !opt.man => opt.search,
+        Some(Subcommand::Search(args)) => args,
+        _ => return daemon::Response::Declined,
+    };
+    if !args.can_use_daemon() || request.loading != *loading {
+        return daemon::Response::Declined;
+    }
+    args.no_pager = true;
+
+    colored::control::set_override(request.color);
+    *CLIENT_TERMINAL.lock().unwrap() = request.terminal;
+    let mut stdout = Vec::new();
+    let result = search_in(args, aggregate_source, cache_dir, &mut stdout);
+    *CLIENT_TERMINAL.lock().unwrap() = None;
+    colored::control::unset_override();
+    let (status, error) = match result {
+        Err(err) if err.is::<NeedsTerminal>() => return daemon::Response::Declined,
+        Ok(true) => (0, None),
+        Ok(false) => (EXIT_NOT_FOUND, None),
+        Err(err) => (exit_status(&err), Some(format!("{:?}", err))),
+    };
+    daemon::Response::Done {
+        status,
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        error,
+    }
+}
+
+/// Lock of the caches of a directory until dropped. The manix processes reading them share it,
+/// so that a search doesn't wait for a `manix update` building caches in the background, and one
+/// of them holds it alone only while it saves or moves some
+struct CacheLock {
+    file: std::fs::File,
+    path: PathBuf,
+    /// Saves of the threads of this process are made one after the other
+    saving: std::sync::Mutex<()>,
+}
+
+impl CacheLock {
+    /// Shared with the other processes reading the caches
+    fn shared(cache_dir: &xdg::BaseDirectories) -> Result<Self> {
+        let lock = Self::open(cache_dir)?;
+        lock.wait_for(std::fs::File::try_lock_shared, std::fs::File::lock_shared)?;
+        Ok(lock)
+    }
+
+    /// Held alone, for replacing all of the caches at once
+    fn exclusive(cache_dir: &xdg::BaseDirectories) -> Result<Self> {
+        let lock = Self::open(cache_dir)?;
+        lock.wait_for(std::fs::File::try_lock, std::fs::File::lock)?;
+        Ok(lock)
+    }
+
+    fn open(cache_dir: &xdg::BaseDirectories) -> Result<Self> {
+        let path = cache_dir
+            .place_cache_file("lock")
+            .context("Failed to place the cache lock file")?;
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            file,
+            path,
+            saving: std::sync::Mutex::new(()),
+        })
+    }
+
+    fn wait_for(
+        &self,
+        try_lock: fn(&std::fs::File) -> Result<(), std::fs::TryLockError>,
+        lock: fn(&std::fs::File) -> io::Result<()>,
+    ) -> Result<()> {
+        match try_lock(&self.file) {
+            Ok(()) => Ok(()),
+            Err(std::fs::TryLockError::WouldBlock) => {
+                eprintln!("Waiting for another manix to be done with the caches...");
+                lock(&self.file).with_context(|| format!("Failed to lock {}", self.path.display()))
+            }
+            Err(std::fs::TryLockError::Error(e)) => {
+                Err(e).with_context(|| format!("Failed to lock {}", self.path.display()))
+            }
+        }
+    }
+
+    /// Holds the lock alone while writing to the caches, sharing it again after
+    fn exclusively<R>(&self, write: impl FnOnce() -> Result<R>) -> Result<R> {
+        let _saving = self
+            .saving
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        self.wait_for(std::fs::File::try_lock, std::fs::File::lock)?;
+        let written = write();
+        self.file
+            .lock_shared()
+            .with_context(|| format!("Failed to lock {}", self.path.display()))?;
+        written
+    }
+}
+
+/// Deletes what's in the directory and below, the locks and the socket of the daemon aside since
+/// other manix processes may be waiting on them
+fn clear_cache_dir(dir: &Path) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let entry = entry.with_context(|| format!("Failed to read {}", dir.display()))?;
+        let path = entry.path();
+        if entry.file_type()?.is_dir() {
+            clear_cache_dir(&path)?;
+            // kept when it has a lock left
+            let _ = std::fs::remove_dir(&path);
+        } else if !matches!(entry.file_name().to_str(), Some("lock" | "daemon.sock")) {
+            std::fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Downloads the caches instead of building them, those of the nixpkgs the sources would be
+/// loaded from and into the directory they would be loaded from
+fn fetch_caches(args: &SourceArgs, cache_dir: &xdg::BaseDirectories) -> Result<()> {
+    let config = load_config(cache_dir)?;
+    options_docsource::set_nix_settings(config.nix.clone());
+    let other_cache_dir = select_nixpkgs(args, &config)?;
+    let cache_dir = other_cache_dir.as_ref().unwrap_or(cache_dir);
+    let prebuilt = config
+        .prebuilt_caches
+        .context("There are no prebuilt caches to fetch, prebuilt_caches isn't in the config")?;
+    let cache_home = cache_dir
+        .create_cache_directory("")
+        .context("Failed to create the cache directory")?;
+    let _lock = CacheLock::exclusive(cache_dir)?;
+    let revision = nixpkgs_revision();
+    eprintln!("Fetching caches from {}...", prebuilt.base_url(&revision));
+    let files = prebuilt
+        .fetch(&cache_home, &revision)
+        .context("Failed to fetch the prebuilt caches")?;
+    eprintln!("Fetched {} caches", files.len());
+    Ok(())
+}
+
+fn bookmarks_path(cache_dir: &xdg::BaseDirectories) -> Result<PathBuf> {
+    cache_dir
+        .place_data_file("bookmarks.json")
+        .context("Failed to place bookmarks file")
+}
+
+fn load_bookmarks(cache_dir: &xdg::BaseDirectories) -> Result<Bookmarks> {
+    Ok(Bookmarks::load(&bookmarks_path(cache_dir)?)?)
+}
+
+/// Remembers that the key was looked at, failing to doesn't keep it from being shown
+fn record_history(cache_dir: &xdg::BaseDirectories, key: &str) {
+    let saved = cache_dir
+        .place_data_file("history.json")
+        .context("Failed to place history file")
+        .and_then(|path| {
+            let mut history = History::load(&path)?;
+            history.record(key, history::now());
+            history.save(&path)?;
+            Ok(())
+        });
+    if let Err(e) = saved {
+        eprintln!("Failed to save the history: {:?}", e);
+    }
+}
+
+/// Points `<nixpkgs>` at the one the caches are to be built from, the one of `--nixpkgs`, of the
+/// config, of `--channel` or locked by the project, and has its revision looked up again. Gives
+/// the directory of its caches when it isn't the default one
+fn select_nixpkgs(args: &SourceArgs, config: &Config) -> Result<Option<xdg::BaseDirectories>> {
+    let project_inputs = match args.project_lock(config) {
+        Some(lock) => Some(
+            flake_lock::LockedInputs::load(&lock)
+                .with_context(|| format!("Failed to read {}", lock.display()))?,
+        ),
+        None => None,
+    };
+    let nixpkgs = match &args.nixpkgs {
+        Some(nixpkgs) => Some(nixpkgs.clone()),
+        None if args.channel.is_none() => config.nixpkgs.clone(),
+        None => None,
+    };
+    if let Some(nixpkgs) = nixpkgs {
+        nixpkgs::set_nixpkgs(&nixpkgs::nix_path_location(&nixpkgs));
+    }
+    if let Some(inputs) = &project_inputs {
+        if let Some(nixpkgs) = &inputs.nixpkgs {
+            nixpkgs::set_nixpkgs(nixpkgs);
+        }
+        if let Some(home_manager) = &inputs.home_manager {
+            nixpkgs::set_nix_path_entry("home-manager", home_manager);
+        }
+    }
+    let cache_dir = match (&args.channel, &project_inputs) {
+        (Some(channel), _) => {
+            let channel = nixpkgs::channel_name(channel)
+                .with_context(|| format!("{} isn't the name of a channel", channel))?;
+            nixpkgs::set_nixpkgs(&format!("channel:{}", channel));
+            Some(
+                xdg::BaseDirectories::with_profile("manix", Path::new("channels").join(&channel))
+                    .context("Failed to get a cache directory")?,
+            )
+        }
+        // projects locking the same inputs share their caches
+        (None, Some(inputs)) => Some(
+            xdg::BaseDirectories::with_profile(
+                "manix",
+                Path::new("projects").join(inputs.cache_key()),
+            )
+            .context("Failed to get a cache directory")?,
+        ),
+        (None, None) => None,
+    };
+    // the daemon loads the sources again after `nix-channel --update` or a nix upgrade
+    forget_nix_versions();
+    Ok(cache_dir)
+}
+
+/// Loads the chosen sources from their caches, building the ones that are missing or outdated
+fn load_sources(args: &SourceArgs, cache_dir: &xdg::BaseDirectories) -> Result<AggregateDocSource> {
+    let config = load_config(cache_dir)?;
+    options_docsource::set_nix_settings(config.nix.clone());
+    let other_cache_dir = select_nixpkgs(args, &config)?;
+    let cache_dir = other_cache_dir.as_ref().unwrap_or(cache_dir);
+    let lock = CacheLock::shared(cache_dir)?;
+    let lock = &lock;
+    if let Ok(gc_roots) = cache_dir.create_cache_directory("gcroots") {
+        options_docsource::set_gc_roots_dir(gc_roots);
+    }
+    let disabled = disabled_sources(&config)?;
+    let sources = args
+        .source
+        .iter()
+        .filter(|source| !args.exclude_source.contains(source) && !disabled.contains(source))
+        .cloned()
+        .collect::<Vec<_>>();
+    // the chosen sources of options that come from options.json files instead of nix builds
+    let options_json = [
+        (
+            Source::nixos_options,
+            OptionsDatabaseType::NixOS,
+            args.nixos_options_json
+                .as_ref()
+                .or(config.options_json.nixos.as_ref()),
+        ),
+        (
+            Source::hm_options,
+            OptionsDatabaseType::HomeManager,
+            config.options_json.home_manager.as_ref(),
+        ),
+        (
+            Source::nd_options,
+            OptionsDatabaseType::NixDarwin,
+            config.options_json.darwin.as_ref(),
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(source, typ, path)| sources.contains(&source).then_some((source, typ, path?)))
+    .collect::<Vec<_>>();
+    let read_from_json = |source: &Source| options_json.iter().any(|(read, ..)| read == source);
+    let sources = sources
+        .into_iter()
+        .filter(|source| !read_from_json(source))
+        .collect::<Vec<_>>();
+
     let last_version_path = cache_dir
         .place_cache_file("last_version.txt")
         .context("Failed to place last version file")?;
@@ -234,168 +2586,1040 @@ fn main() -> Result<()> {
     let nixpkgs_doc_cache_path = cache_dir
         .place_cache_file("nixpkgs_doc_database.bin")
         .context("Failed to place Nixpkgs Documentation cache file")?;
+    let builtins_cache_path = cache_dir
+        .place_cache_file("builtins_database.bin")
+        .context("Failed to place Nix builtins cache file")?;
+    let packages_cache_path = cache_dir
+        .place_cache_file("packages_database.bin")
+        .context("Failed to place Nixpkgs Packages cache file")?;
+    let nur_cache_path = cache_dir
+        .place_cache_file("nur_database.bin")
+        .context("Failed to place NUR cache file")?;
+    let cli_cache_path = cache_dir
+        .place_cache_file("cli_database.bin")
+        .context("Failed to place Nix CLI cache file")?;
+    let nixconf_cache_path = cache_dir
+        .place_cache_file("nixconf_database.bin")
+        .context("Failed to place nix.conf Settings cache file")?;
+    let lib_cache_path = cache_dir
+        .place_cache_file("nixpkgs_lib_database.bin")
+        .context("Failed to place Nixpkgs Lib cache file")?;
+    let wiki_cache_path = cache_dir
+        .place_cache_file("nixos_wiki.bin")
+        .context("Failed to place NixOS Wiki cache file")?;
+    let rfcs_cache_path = cache_dir
+        .place_cache_file("nix_rfcs.bin")
+        .context("Failed to place Nix RFCs cache file")?;
+    let changelog_cache_path = cache_dir
+        .place_cache_file("release_notes_database.bin")
+        .context("Failed to place Release Notes cache file")?;
+    let maintainers_cache_path = cache_dir
+        .place_cache_file("maintainers_database.bin")
+        .context("Failed to place Nixpkgs Maintainers cache file")?;
+    let licenses_cache_path = cache_dir
+        .place_cache_file("licenses_database.bin")
+        .context("Failed to place Nixpkgs Licenses cache file")?;
+    let renames_cache_path = cache_dir
+        .place_cache_file("option_renames_database.bin")
+        .context("Failed to place Renamed Options cache file")?;
 
     let version = std::env!("CARGO_PKG_VERSION");
     let last_version = std::fs::read(&last_version_path)
         .map(String::from_utf8)
         .unwrap_or(Ok(version.to_string()))?;
 
-    let should_invalidate_cache = version != last_version;
-
-    let mut aggregate_source = AggregateDocSource::default();
-
-    let mut comment_db = if !should_invalidate_cache && comment_cache_path.exists() {
-        CommentsDatabase::load(&std::fs::read(&comment_cache_path)?)
-            .map_err(|e| anyhow::anyhow!("Failed to load Nixpkgs comments database: {:?}", e))?
-    } else {
-        CommentsDatabase::new()
-    };
-    if comment_db.hash_to_defs.is_empty() {
-        eprintln!("Building Nixpkgs comments cache...");
+    // the caches loaded below are rebuilt all at once, the other ones on their own. The comments
+    // and nix-darwin ones are left out unless searched, since they're only rebuilt then, and the
+    // ones of disabled sources or options.json files are never rebuilt
+    let rebuilt = |source: &Source| !disabled.contains(source) && !read_from_json(source);
+    let outdated_cache = (rebuilt(&Source::nixos_options)
+        && OptionsDatabase::is_outdated(&options_nixos_cache_path))
+        || (sources.contains(&Source::nixpkgs_comments)
+            && CommentsDatabase::is_outdated(&comment_cache_path))
+        || (sources.contains(&Source::nd_options)
+            && OptionsDatabase::is_outdated(&options_nd_cache_path))
+        || (rebuilt(&Source::hm_options) && OptionsDatabase::is_outdated(&options_hm_cache_path))
+        || (rebuilt(&Source::nixpkgs_tree)
+            && nixpkgs_tree_docsource::NixpkgsTreeDatabase::is_outdated(&nixpkgs_tree_cache_path))
+        || (rebuilt(&Source::nixpkgs_doc)
+            && xml_docsource::XmlFuncDocDatabase::is_outdated(&nixpkgs_doc_cache_path))
+        || (rebuilt(&Source::nix_builtins) && BuiltinsDatabase::is_outdated(&builtins_cache_path))
+        || (rebuilt(&Source::nixpkgs_packages)
+            && packages_docsource::PackagesDatabase::is_outdated(&packages_cache_path))
+        || (rebuilt(&Source::nix_cli) && cli_docsource::CliDatabase::is_outdated(&cli_cache_path))
+        || (rebuilt(&Source::nix_conf)
+            && nixconf_docsource::NixConfDatabase::is_outdated(&nixconf_cache_path))
+        || (rebuilt(&Source::nixpkgs_lib)
+            && lib_docsource::LibDatabase::is_outdated(&lib_cache_path))
+        || (rebuilt(&Source::release_notes)
+            && changelog_docsource::ChangelogDatabase::is_outdated(&changelog_cache_path))
+        || (rebuilt(&Source::nixpkgs_maintainers)
+            && maintainers_docsource::MaintainersDatabase::is_outdated(&maintainers_cache_path))
+        || (rebuilt(&Source::nixpkgs_licenses)
+            && licenses_docsource::LicensesDatabase::is_outdated(&licenses_cache_path))
+        || (rebuilt(&Source::option_renames)
+            && renames_docsource::RenamesDatabase::is_outdated(&renames_cache_path));
+    if outdated_cache {
+        eprintln!("The caches are of another version of manix or nixpkgs, rebuilding them...");
     }
+    let should_invalidate_cache = version != last_version || outdated_cache;
 
-    let cache_invalid = comment_db
-        .update()
-        .map_err(|e| anyhow::anyhow!(e))
-        .context("Failed to update cache")?;
-    comment_db.save(&comment_cache_path)?;
-    if opt.source.contains(&Source::nixpkgs_comments) {
-        aggregate_source.add_source(Box::new(comment_db));
-    }
+    let mut aggregate_source = AggregateDocSource::default();
+    let mut missing_local_cache = false;
+    let mut stale_caches = false;
 
-    if should_invalidate_cache || opt.update_cache || cache_invalid {
-        if build_source_and_add(
-            OptionsDatabase::new(OptionsDatabaseType::HomeManager),
-            "Home Manager Options",
-            &options_hm_cache_path,
-            if opt.source.contains(&Source::hm_options) {
-                Some(&mut aggregate_source)
-            } else {
-                None
-            },
-        )
-        .is_none()
+    // walking nixpkgs to update the comments takes a while, so only when they're searched
+    let cache_invalid = if sources.contains(&Source::nixpkgs_comments) {
+        // kept when nixpkgs changes, only the files that changed are parsed again
+        let loaded = if version == last_version
+            && !args.rebuild.contains(&Source::nixpkgs_comments)
+            && comment_cache_path.exists()
         {
-            eprintln!(
-                "Tip: If you installed your home-manager through configuration.nix you can fix this error \
-                by adding the home-manager channel with this command: {}\n\
-                Otherwise, make sure you have `manual.json.enable` set in your home configuration",
-                "nix-channel --add https://github.com/rycee/home-manager/archive/master.tar.gz home-manager && nix-channel --update".bold(),
-            );
+            match CommentsDatabase::open(&comment_cache_path) {
+                Ok(comment_db) => Some(comment_db),
+                Err(Errors::Bincode(_)) => {
+                    let quarantined =
+                        lock.exclusively(|| Ok(quarantine_cache(&comment_cache_path)?))?;
+                    eprintln!(
+                        "The Nixpkgs comments cache is corrupt, moved it to {}",
+                        quarantined.display()
+                    );
+                    None
+                }
+                Err(Errors::OutdatedCache) => None,
+                Err(e) => {
+                    return Err(anyhow::anyhow!(
+                        "Failed to load Nixpkgs comments database: {:?}",
+                        e
+                    ))
+                }
+            }
+        } else {
+            None
+        };
+        // a cache built from scratch always changes, which says nothing of nixpkgs changing
+        let incremental = loaded.is_some();
+        let mut comment_db = loaded.unwrap_or_else(CommentsDatabase::new);
+        if comment_db.is_empty() {
+            eprintln!("Building Nixpkgs comments cache...");
         }
 
-        if build_source_and_add(
-            OptionsDatabase::new(OptionsDatabaseType::NixDarwin),
-            "Nix-Darwin Options",
-            &options_nd_cache_path,
-            if opt.source.contains(&Source::nd_options) {
-                Some(&mut aggregate_source)
-            } else {
-                None
-            },
-        )
-        .is_none()
-        {
-            eprintln!("Tip: Ensure darwin is set in your NIX_PATH");
+        let changed = comment_db
+            .update()
+            .map_err(|e| anyhow::anyhow!(e))
+            .context("Failed to update cache")?;
+        if changed {
+            lock.exclusively(|| Ok(comment_db.save(&comment_cache_path)?))?;
         }
+        aggregate_source.add_source(Box::new(comment_db));
+        incremental && changed
+    } else {
+        false
+    };
 
-        build_source_and_add(
-            OptionsDatabase::new(OptionsDatabaseType::NixOS),
-            "NixOS Options",
-            &options_nixos_cache_path,
-            if opt.source.contains(&Source::nixos_options) {
-                Some(&mut aggregate_source)
-            } else {
-                None
-            },
-        );
-
-        build_source_and_add(
-            nixpkgs_tree_docsource::NixpkgsTreeDatabase::new(),
-            "Nixpkgs Tree",
-            &nixpkgs_tree_cache_path,
-            if opt.source.contains(&Source::nixpkgs_tree) {
-                Some(&mut aggregate_source)
-            } else {
-                None
-            },
-        );
-
-        build_source_and_add(
-            xml_docsource::XmlFuncDocDatabase::new(),
-            "Nixpkgs Documentation",
-            &nixpkgs_doc_cache_path,
-            if opt.source.contains(&Source::nixpkgs_doc) {
-                Some(&mut aggregate_source)
-            } else {
-                None
-            },
-        );
+    if should_invalidate_cache || args.update_cache || cache_invalid {
+        // the slow parts are nix evaluations, which don't get in each other's way
+        let mut jobs = vec![
+            (
+                Source::hm_options,
+                build_job(
+                    OptionsDatabase::new(OptionsDatabaseType::HomeManager),
+                    "Home Manager Options",
+                    &options_hm_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixos_options,
+                build_job(
+                    OptionsDatabase::new(OptionsDatabaseType::NixOS),
+                    "NixOS Options",
+                    &options_nixos_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_tree,
+                build_job(
+                    nixpkgs_tree_docsource::NixpkgsTreeDatabase::new(),
+                    "Nixpkgs Tree",
+                    &nixpkgs_tree_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_doc,
+                build_job(
+                    xml_docsource::XmlFuncDocDatabase::new(),
+                    "Nixpkgs Documentation",
+                    &nixpkgs_doc_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nix_builtins,
+                build_job(
+                    BuiltinsDatabase::new(),
+                    "Nix Builtins",
+                    &builtins_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_packages,
+                build_job(
+                    packages_docsource::PackagesDatabase::new(),
+                    "Nixpkgs Packages",
+                    &packages_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nix_cli,
+                build_job(
+                    cli_docsource::CliDatabase::new(),
+                    "Nix CLI",
+                    &cli_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nix_conf,
+                build_job(
+                    nixconf_docsource::NixConfDatabase::new(),
+                    "nix.conf Settings",
+                    &nixconf_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_lib,
+                build_job(
+                    lib_docsource::LibDatabase::new(),
+                    "Nixpkgs Lib",
+                    &lib_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::release_notes,
+                build_job(
+                    changelog_docsource::ChangelogDatabase::new(),
+                    "Release Notes",
+                    &changelog_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_maintainers,
+                build_job(
+                    maintainers_docsource::MaintainersDatabase::new(),
+                    "Nixpkgs Maintainers",
+                    &maintainers_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::nixpkgs_licenses,
+                build_job(
+                    licenses_docsource::LicensesDatabase::new(),
+                    "Nixpkgs Licenses",
+                    &licenses_cache_path,
+                    lock,
+                ),
+            ),
+            (
+                Source::option_renames,
+                build_job(
+                    renames_docsource::RenamesDatabase::new(),
+                    "Renamed Options",
+                    &renames_cache_path,
+                    lock,
+                ),
+            ),
+        ];
+        // nix-darwin is hardly ever around off macOS, so only tried when asked for
+        if sources.contains(&Source::nd_options) {
+            jobs.push((
+                Source::nd_options,
+                build_job(
+                    OptionsDatabase::new(OptionsDatabaseType::NixDarwin),
+                    "Nix-Darwin Options",
+                    &options_nd_cache_path,
+                    lock,
+                ),
+            ));
+        }
+        jobs.retain(|(source, _)| rebuilt(source));
+        for (source, built) in build_in_parallel(jobs) {
+            match built {
+                Some(built) if sources.contains(&source) => aggregate_source.add_source(built),
+                Some(_) => {}
+                None => match source {
+                    Source::hm_options => {
+                        eprintln!(
+                            "Tip: If you installed your home-manager through configuration.nix you can fix this error \
+                            by adding the home-manager channel with this command: {}\n\
+                            Otherwise, make sure you have `manual.json.enable` set in your home configuration",
+                            "nix-channel --add https://github.com/rycee/home-manager/archive/master.tar.gz home-manager && nix-channel --update".bold(),
+                        );
+                    }
+                    Source::nd_options => eprintln!("Tip: Ensure darwin is set in your NIX_PATH"),
+                    Source::nixos_options | Source::nixpkgs_packages => missing_local_cache = true,
+                    _ => {}
+                },
+            }
+        }
 
-        std::fs::write(&last_version_path, version)?;
+        lock.exclusively(|| Ok(std::fs::write(&last_version_path, version)?))?;
     } else {
-        if opt.source.contains(&Source::nixos_options) {
-            load_source_and_add(
-                std::fs::read(&options_nixos_cache_path).map(|c| OptionsDatabase::load(&c)),
+        if let Some(max_age) = config.max_cache_age_days {
+            stale_caches = refresh_stale_caches(args, &last_version_path, max_age);
+        }
+
+        if sources.contains(&Source::nixos_options) {
+            missing_local_cache |= load_or_rebuild_source_and_add(
+                || OptionsDatabase::new(OptionsDatabaseType::NixOS),
                 "NixOS Options",
+                &options_nixos_cache_path,
+                args.rebuild.contains(&Source::nixos_options),
                 &mut aggregate_source,
-                false,
-            );
+                args.online,
+                lock,
+            )
+            .is_none();
         }
 
-        if opt.source.contains(&Source::nd_options) {
-            load_source_and_add(
-                std::fs::read(&options_nd_cache_path).map(|c| OptionsDatabase::load(&c)),
+        if sources.contains(&Source::nd_options) {
+            load_or_rebuild_source_and_add(
+                || OptionsDatabase::new(OptionsDatabaseType::NixDarwin),
                 "Nix Darwin Options",
+                &options_nd_cache_path,
+                args.rebuild.contains(&Source::nd_options) || !options_nd_cache_path.exists(),
                 &mut aggregate_source,
                 true,
+                lock,
             );
         }
 
-        if opt.source.contains(&Source::hm_options) {
-            load_source_and_add(
-                std::fs::read(&options_hm_cache_path).map(|c| OptionsDatabase::load(&c)),
+        if sources.contains(&Source::hm_options) {
+            load_or_rebuild_source_and_add(
+                || OptionsDatabase::new(OptionsDatabaseType::HomeManager),
                 "Home Manager Options",
+                &options_hm_cache_path,
+                args.rebuild.contains(&Source::hm_options),
                 &mut aggregate_source,
                 true,
+                lock,
             );
         }
 
-        if opt.source.contains(&Source::nixpkgs_tree) {
-            load_source_and_add(
-                std::fs::read(&nixpkgs_tree_cache_path)
-                    .map(|c| nixpkgs_tree_docsource::NixpkgsTreeDatabase::load(&c)),
+        if sources.contains(&Source::nixpkgs_tree) {
+            load_or_rebuild_source_and_add(
+                nixpkgs_tree_docsource::NixpkgsTreeDatabase::new,
                 "Nixpkgs Tree",
+                &nixpkgs_tree_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_tree),
                 &mut aggregate_source,
                 false,
+                lock,
             );
         }
 
-        if opt.source.contains(&Source::nixpkgs_doc) {
-            load_source_and_add(
-                std::fs::read(&nixpkgs_doc_cache_path)
-                    .map(|c| xml_docsource::XmlFuncDocDatabase::load(&c)),
+        if sources.contains(&Source::nixpkgs_doc) {
+            load_or_rebuild_source_and_add(
+                xml_docsource::XmlFuncDocDatabase::new,
                 "Nixpkgs Documentation",
+                &nixpkgs_doc_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_doc),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nix_builtins) {
+            load_or_rebuild_source_and_add(
+                BuiltinsDatabase::new,
+                "Nix Builtins",
+                &builtins_cache_path,
+                args.rebuild.contains(&Source::nix_builtins),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nixpkgs_packages) {
+            missing_local_cache |= load_or_rebuild_source_and_add(
+                packages_docsource::PackagesDatabase::new,
+                "Nixpkgs Packages",
+                &packages_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_packages),
+                &mut aggregate_source,
+                args.online,
+                lock,
+            )
+            .is_none();
+        }
+
+        if sources.contains(&Source::nix_cli) {
+            load_or_rebuild_source_and_add(
+                cli_docsource::CliDatabase::new,
+                "Nix CLI",
+                &cli_cache_path,
+                args.rebuild.contains(&Source::nix_cli),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nix_conf) {
+            load_or_rebuild_source_and_add(
+                nixconf_docsource::NixConfDatabase::new,
+                "nix.conf Settings",
+                &nixconf_cache_path,
+                args.rebuild.contains(&Source::nix_conf),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nixpkgs_lib) {
+            load_or_rebuild_source_and_add(
+                lib_docsource::LibDatabase::new,
+                "Nixpkgs Lib",
+                &lib_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_lib),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::release_notes) {
+            load_or_rebuild_source_and_add(
+                changelog_docsource::ChangelogDatabase::new,
+                "Release Notes",
+                &changelog_cache_path,
+                args.rebuild.contains(&Source::release_notes),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nixpkgs_maintainers) {
+            load_or_rebuild_source_and_add(
+                maintainers_docsource::MaintainersDatabase::new,
+                "Nixpkgs Maintainers",
+                &maintainers_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_maintainers),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+
+        if sources.contains(&Source::nixpkgs_licenses) {
+            load_or_rebuild_source_and_add(
+                licenses_docsource::LicensesDatabase::new,
+                "Nixpkgs Licenses",
+                &licenses_cache_path,
+                args.rebuild.contains(&Source::nixpkgs_licenses),
                 &mut aggregate_source,
                 false,
+                lock,
             );
         }
+
+        if sources.contains(&Source::option_renames) {
+            load_or_rebuild_source_and_add(
+                renames_docsource::RenamesDatabase::new,
+                "Renamed Options",
+                &renames_cache_path,
+                args.rebuild.contains(&Source::option_renames),
+                &mut aggregate_source,
+                false,
+                lock,
+            );
+        }
+    }
+
+    let rebuild_caches = should_invalidate_cache || args.update_cache || cache_invalid;
+
+    if sources.contains(&Source::nix_rfcs) {
+        load_or_build_source_and_add(
+            rfc_docsource::RfcDatabase::new(),
+            "Nix RFCs",
+            &rfcs_cache_path,
+            rebuild_caches || args.rebuild.contains(&Source::nix_rfcs),
+            &mut aggregate_source,
+            lock,
+        );
     }
 
-    let query_lower = opt.query.to_ascii_lowercase();
-    let query = manix::Lowercase(query_lower.as_bytes());
-    let entries = if opt.strict {
-        aggregate_source.search(&query)
+    if sources.contains(&Source::nixos_wiki) {
+        load_or_build_source_and_add(
+            wiki_docsource::WikiDatabase::new(),
+            "NixOS Wiki",
+            &wiki_cache_path,
+            rebuild_caches || args.rebuild.contains(&Source::nixos_wiki),
+            &mut aggregate_source,
+            lock,
+        );
+    }
+
+    if sources.contains(&Source::nur) {
+        load_or_build_source_and_add(
+            nur_docsource::NurDatabase::new(),
+            "NUR",
+            &nur_cache_path,
+            rebuild_caches || args.rebuild.contains(&Source::nur),
+            &mut aggregate_source,
+            lock,
+        );
+    }
+
+    for (source, typ, cache_file) in MODULE_OPTIONS_SOURCES {
+        if sources.contains(source) {
+            let cache_path = cache_dir
+                .place_cache_file(cache_file)
+                .with_context(|| format!("Failed to place {} cache file", typ.source_name()))?;
+            load_or_build_source_and_add(
+                OptionsDatabase::new(*typ),
+                typ.source_name(),
+                &cache_path,
+                rebuild_caches || args.rebuild.contains(source),
+                &mut aggregate_source,
+                lock,
+            );
+        }
+    }
+
+    for (source, set, cache_file) in PACKAGE_SET_SOURCES {
+        if sources.contains(source) {
+            let cache_path = cache_dir
+                .place_cache_file(cache_file)
+                .with_context(|| format!("Failed to place {} cache file", set))?;
+            load_or_build_source_and_add(
+                packages_docsource::PackagesDatabase::for_set(set),
+                set,
+                &cache_path,
+                rebuild_caches || args.rebuild.contains(source),
+                &mut aggregate_source,
+                lock,
+            );
+        }
+    }
+
+    for (source, typ, cache_file) in MANUAL_SOURCES {
+        if sources.contains(source) {
+            let cache_path = cache_dir
+                .place_cache_file(cache_file)
+                .with_context(|| format!("Failed to place {} cache file", typ.source_name()))?;
+            load_or_build_source_and_add(
+                ManualDatabase::new(*typ),
+                typ.source_name(),
+                &cache_path,
+                rebuild_caches || args.rebuild.contains(source),
+                &mut aggregate_source,
+                lock,
+            );
+        }
+    }
+
+    if let Some(flake) = &args.flake {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(flake.as_bytes());
+        let flake_cache_path = cache_dir
+            .place_cache_file(format!("options_flake_{:08x}.bin", hasher.finalize()))
+            .context("Failed to place flake options cache file")?;
+
+        load_or_build_source_and_add(
+            OptionsDatabase::for_flake(flake.clone()),
+            "Flake Options",
+            &flake_cache_path,
+            args.update_cache,
+            &mut aggregate_source,
+            lock,
+        );
+    }
+
+    if let Some(expression) = &args.options_expr {
+        let content = std::fs::read(expression)
+            .with_context(|| format!("Failed to read {}", expression.display()))?;
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&content);
+        let expression_cache_path = cache_dir
+            .place_cache_file(format!("options_expr_{:08x}.bin", hasher.finalize()))
+            .context("Failed to place custom options cache file")?;
+
+        load_or_build_source_and_add(
+            OptionsDatabase::for_expression(expression.clone()),
+            "Custom Options",
+            &expression_cache_path,
+            args.update_cache,
+            &mut aggregate_source,
+            lock,
+        );
+    }
+
+    for (source, typ, path) in options_json {
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(path.display().to_string().as_bytes());
+        let json_cache_path = cache_dir
+            .place_cache_file(format!("options_json_{:08x}.bin", hasher.finalize()))
+            .context("Failed to place options.json cache file")?;
+        // files out of the store may be written again at the same path
+        let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let changed =
+            modified(&options_docsource::options_json_in(path)) > modified(&json_cache_path);
+
+        load_or_build_source_and_add(
+            OptionsDatabase::from_json(typ, path.clone()),
+            typ.source_name(),
+            &json_cache_path,
+            args.update_cache || args.rebuild.contains(&source) || changed,
+            &mut aggregate_source,
+            lock,
+        );
+    }
+
+    if sources.contains(&Source::nix_language) {
+        aggregate_source.add_source(Box::new(language_docsource::LanguageDocSource::new()));
+    }
+
+    if args.online && (missing_local_cache || stale_caches) {
+        if missing_local_cache {
+            eprintln!("Local caches are missing, searching search.nixos.org instead");
+        } else {
+            eprintln!("The caches are stale, searching search.nixos.org as well");
+        }
+        aggregate_source.add_source(Box::new(online_docsource::OnlineDocSource::new(
+            config.online_index.clone(),
+        )));
+    }
+
+    Ok(aggregate_source)
+}
+
+/// What the query matched, emphasized in the results
+struct Highlight {
+    pattern: regex::Regex,
+    /// Whether the descriptions are highlighted as well as the keys
+    text: bool,
+}
+
+impl Highlight {
+    fn apply(&self, printed: &str) -> String {
+        if self.text {
+            return highlight(printed, &self.pattern);
+        }
+        // the key is on the first line
+        match printed.split_once('\n') {
+            Some((key, rest)) => format!("{}\n{}", highlight(key, &self.pattern), rest),
+            None => highlight(printed, &self.pattern),
+        }
+    }
+}
+
+/// A query along with how the arguments say to match it, searched for in each source
+struct Query<'a> {
+    args: &'a SearchArgs,
+    raw: &'a str,
+    lower: String,
+    match_options: MatchOptions,
+    regex: Option<regex::Regex>,
+    boolean: Option<BooleanQuery>,
+    glob: Option<GlobQuery>,
+    bookmarks: Option<&'a Bookmarks>,
+    /// What the query stands for in the synonyms of the config, lowercased
+    synonym: Option<String>,
+}
+
+impl<'a> Query<'a> {
+    fn new(
+        args: &'a SearchArgs,
+        raw_query: &'a str,
+        bookmarks: Option<&'a Bookmarks>,
+        config: &Config,
+    ) -> Result<Self> {
+        let match_options = MatchOptions {
+            case_sensitive: args.case_sensitive,
+            whole_word: args.word,
+        };
+        let regex = if args.regex {
+            let pattern = if args.word {
+                format!(r"\b(?:{})\b", raw_query)
+            } else {
+                raw_query.to_string()
+            };
+            let regex = regex::RegexBuilder::new(&pattern)
+                .case_insensitive(!args.case_sensitive)
+                .build()
+                .context("Invalid regular expression")?;
+            Some(regex)
+        } else {
+            None
+        };
+        let boolean = (!args.terms.is_empty() || BooleanQuery::is_boolean(raw_query))
+            .then(|| BooleanQuery::parse(&[raw_query.to_string(), args.terms.join(" ")].join(" ")));
+        let glob = GlobQuery::is_glob(raw_query).then(|| GlobQuery::parse(raw_query));
+
+        Ok(Self {
+            args,
+            raw: raw_query,
+            lower: raw_query.to_ascii_lowercase(),
+            match_options,
+            regex,
+            boolean,
+            glob,
+            bookmarks,
+            synonym: config.synonym(raw_query).map(str::to_ascii_lowercase),
+        })
+    }
+
+    /// Fuzzy, boolean and glob queries don't match a single substring, so aren't highlighted
+    fn highlight(&self) -> Option<Highlight> {
+        let pattern = if let Some(regex) = &self.regex {
+            regex.clone()
+        } else if self.args.fuzzy || self.boolean.is_some() || self.glob.is_some() {
+            return None;
+        } else {
+            // the words of the query are looked for on their own
+            let mut pattern = self
+                .raw
+                .split_whitespace()
+                .chain(self.synonym.as_deref())
+                .map(regex::escape)
+                .collect::<Vec<_>>()
+                .join("|");
+            if self.args.word {
+                pattern = format!(r"\b(?:{})\b", pattern);
+            }
+            regex::RegexBuilder::new(&pattern)
+                .case_insensitive(!self.args.case_sensitive)
+                .build()
+                .ok()?
+        };
+        Some(Highlight {
+            pattern,
+            text: self.args.full_text,
+        })
+    }
+
+    fn lowercase(&self) -> manix::Lowercase<'_> {
+        manix::Lowercase(self.lower.as_bytes())
+    }
+
+    fn search(&self, source: &dyn DocSource) -> Vec<DocEntry> {
+        let query = self.lowercase();
+        let mut entries = if let Some(regex) = &self.regex {
+            source.search_regex(regex, self.args.full_text)
+        } else if self.args.fuzzy {
+            let mut scored = source.search_fuzzy(&query);
+            scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+            scored.into_iter().map(|(_, entry)| entry).collect()
+        } else if let Some(boolean) = &self.boolean {
+            source.search_matching(&|entry| boolean.matches(entry, self.match_options))
+        } else if let Some(glob) = &self.glob {
+            source.search_matching(&|entry| glob.matches(&entry.name(), self.match_options))
+        } else if self.args.full_text {
+            source.search_matching(&|entry| entry.matches_full_text(self.raw, self.match_options))
+        } else {
+            let mut entries = if self.args.strict {
+                source.search(&query)
+            } else {
+                search_liberal_with_tokens(source, &query)
+            };
+            // the sources match case insensitive substrings, narrowed down here
+            if self.match_options != MatchOptions::default() {
+                entries.retain(|entry| self.match_options.finds(&entry.name(), self.raw));
+            }
+            if let Some(synonym) = &self.synonym {
+                let synonym = manix::Lowercase(synonym.as_bytes());
+                let found = entries.iter().map(DocEntry::name).collect::<HashSet<_>>();
+                let more = if self.args.strict {
+                    source.search(&synonym)
+                } else {
+                    source.search_liberal(&synonym)
+                };
+                entries.extend(
+                    more.into_iter()
+                        .filter(|entry| !found.contains(&entry.name())),
+                );
+            }
+            entries
+        };
+        if !self.args.kind.is_empty() {
+            entries.retain(|entry| self.args.kind.contains(&Kind::of(entry)));
+        }
+        if let Some(typ) = &self.args.option_type {
+            entries.retain(|entry| matches!(entry, DocEntry::OptionDoc(_, x) if x.has_type(typ)));
+        }
+        if self.args.read_only || self.args.writable_only {
+            let read_only = self.args.read_only;
+            entries.retain(|entry| match entry {
+                DocEntry::OptionDoc(_, x) => x.is_read_only() == read_only,
+                // everything else is as writable as it gets
+                _ => !read_only,
+            });
+        }
+        if let Some(bookmarks) = self.bookmarks {
+            entries.retain(|entry| bookmarks.contains(&entry.name()));
+        }
+        if !self.args.refine.is_empty() {
+            let refine = self.args.refine.join(" ");
+            entries.retain(|entry| entry.matches_full_text(&refine, self.match_options));
+        }
+        entries
+    }
+}
+
+fn search<W: Write>(
+    args: SearchArgs,
+    cache_dir: &xdg::BaseDirectories,
+    stdout: &mut W,
+) -> Result<bool> {
+    if args.stdin {
+        return search_batch(args, cache_dir, stdout);
+    }
+    raw_query(&args)?;
+    let aggregate_source = load_sources(&args.sources, cache_dir)?;
+    search_in(args, &aggregate_source, cache_dir, stdout)
+}
+
+fn raw_query(args: &SearchArgs) -> Result<String> {
+    // every bookmark, or option of the type, is listed without a query
+    let listing = args.bookmarks || args.option_type.is_some();
+    args.query
+        .clone()
+        .or_else(|| listing.then(String::new))
+        .context("No query given")
+}
+
+/// Searches sources that are loaded already, as `manix daemon` keeps them
+fn search_in<W: Write>(
+    args: SearchArgs,
+    aggregate_source: &AggregateDocSource,
+    cache_dir: &xdg::BaseDirectories,
+    stdout: &mut W,
+) -> Result<bool> {
+    let raw_query = raw_query(&args)?;
+    let bookmarks = args
+        .bookmarks
+        .then(|| load_bookmarks(cache_dir))
+        .transpose()?;
+    let config = load_config(cache_dir)?;
+
+    options_docsource::set_show_hidden(args.all);
+    args.format.apply();
+
+    let parsed = Query::new(&args, &raw_query, bookmarks.as_ref(), &config)?;
+    let query = parsed.lowercase();
+    if args.ndjson {
+        return print_ndjson_output(
+            aggregate_source,
+            &|source| parsed.search(source),
+            &args,
+            stdout,
+        );
+    }
+
+    let mut entries = parsed.search(aggregate_source);
+    if !args.fuzzy {
+        sort_by_relevance_with_history(&mut entries, &query, &load_history(cache_dir));
+    }
+    let suggestions = if entries.is_empty() && !args.json && !args.regex {
+        fuzzy::suggestions(&aggregate_source.all_keys(), &raw_query)
+            .into_iter()
+            .map(|key| key.to_string())
+            .collect()
     } else {
-        aggregate_source.search_liberal(&query)
+        Vec::new()
     };
-    let results = SearchResults::from_entries(entries);
+    let mut results = SearchResults::from_entries(entries);
+    let mut left_out = results.paginate(args.offset, args.limit());
 
-    if opt.json {
-        print_json_output(&results, &mut stdout)?;
+    let interactive = is_interactive();
+    let candidates = (0..results.entries.len())
+        .filter(|&i| args.pick || is_exact_match(&results.entries[i].name(), &query))
+        .collect::<Vec<_>>();
+    if (args.pick && !candidates.is_empty()) || (interactive && !args.json && candidates.len() > 1)
+    {
+        if IN_DAEMON.load(Ordering::SeqCst) {
+            return Err(NeedsTerminal.into());
+        }
+        if let Some(i) = pick_entry(&results.entries, &candidates, stdout)? {
+            record_history(cache_dir, &results.entries[i].name());
+            results.entries = vec![results.entries.swap_remove(i)];
+            results.key_only_entries.clear();
+            left_out = 0;
+        }
+    }
+
+    if let (Some(what), Some(entry)) = (args.copy, results.entries.first()) {
+        copy_to_clipboard(entry, what, args.format)?;
+    }
+    if args.open {
+        open_in_editor(&results.entries)?;
+        return Ok(true);
+    }
+
+    if args.json {
+        print_json_output(&results, stdout)?;
     } else {
-        print_human_output(&results, &mut stdout)?;
+        let show_value_of = args.show_value.then_some(raw_query.as_str());
+        let expand_from = args.expand.then_some(aggregate_source);
+        let mut output = Vec::new();
+        print_human_output(
+            &results,
+            args.format,
+            args.option_display(),
+            show_value_of,
+            expand_from,
+            parsed.highlight().as_ref(),
+            &mut output,
+        )?;
+        if left_out > 0 {
+            writeln!(output, "... and {} more, use --limit 0", left_out)?;
+        }
+        if !suggestions.is_empty() {
+            writeln!(output, "Did you mean {}?", suggestions.join(", ").white())?;
+        }
+        write_paged(&output, args.no_pager, stdout)?;
     }
 
-    Ok(())
-}}}
+    Ok(!results.entries.is_empty() || !results.key_only_entries.is_empty())
+}
+
+/// The results of a query of `--stdin`, as written out with `--json` and `--ndjson`
+#[derive(serde::Serialize)]
+struct QueryResults<'a> {
+    query: &'a str,
+    results: Vec<&'a DocEntry>,
+}
+
+/// Searches for each line of stdin in turn, the sources only being loaded once for all of them
+fn search_batch<W: Write>(
+    args: SearchArgs,
+    cache_dir: &xdg::BaseDirectories,
+    stdout: &mut W,
+) -> Result<bool> {
+    let aggregate_source = load_sources(&args.sources, cache_dir)?;
+    options_docsource::set_show_hidden(args.all);
+    args.format.apply();
+    let history = load_history(cache_dir);
+    let bookmarks = args
+        .bookmarks
+        .then(|| load_bookmarks(cache_dir))
+        .transpose()?;
+    let config = load_config(cache_dir)?;
+
+    if args.json {
+        write!(stdout, "[")?;
+    }
+    let mut first = true;
+    let mut found = false;
+    for line in io::stdin().lock().lines() {
+        let line = line.context("Failed to read a query from stdin")?;
+        let raw_query = line.trim();
+        if raw_query.is_empty() {
+            continue;
+        }
+        let parsed = Query::new(&args, raw_query, bookmarks.as_ref(), &config)
+            .with_context(|| format!("Invalid query {}", raw_query))?;
+        let mut entries = parsed.search(&aggregate_source);
+        if !args.fuzzy {
+            sort_by_relevance_with_history(&mut entries, &parsed.lowercase(), &history);
+        }
+        let mut results = SearchResults::from_entries(entries);
+        let left_out = results.paginate(args.offset, args.limit());
+        found |= !results.entries.is_empty() || !results.key_only_entries.is_empty();
+
+        if args.json || args.ndjson {
+            let query_results = QueryResults {
+                query: raw_query,
+                results: results
+                    .entries
+                    .iter()
+                    .chain(&results.key_only_entries)
+                    .collect(),
+            };
+            if args.json && !first {
+                write!(stdout, ",")?;
+            }
+            serde_json::to_writer(&mut *stdout, &query_results)
+                .context("Failed to serialize search results as JSON")?;
+            if args.ndjson {
+                writeln!(stdout)?;
+            }
+        } else {
+            if !first {
+                writeln!(stdout)?;
+            }
+            writeln!(stdout, "==> {} <==", raw_query.bold())?;
+            let expand_from = args.expand.then_some(&aggregate_source);
+            print_human_output(
+                &results,
+                args.format,
+                args.option_display(),
+                None,
+                expand_from,
+                parsed.highlight().as_ref(),
+                stdout,
+            )?;
+            if left_out > 0 {
+                writeln!(stdout, "... and {} more, use --limit 0", left_out)?;
+            }
+        }
+        // scripts may wait for the results of a query before writing the next one
+        stdout.flush()?;
+        first = false;
+    }
+    if args.json {
+        writeln!(stdout, "]")?;
+    }
+    Ok(found)
+}
+
+fn show<W: Write>(
+    args: ShowArgs,
+    cache_dir: &xdg::BaseDirectories,
+    stdout: &mut W,
+) -> Result<bool> {
+    let aggregate_source = load_sources(&args.sources, cache_dir)?;
+    // the key was asked for explicitly, so it's shown even when hidden
+    options_docsource::set_show_hidden(true);
+    let key_lower = args.key.to_ascii_lowercase();
+    let mut entries = aggregate_source.search(&manix::Lowercase(key_lower.as_bytes()));
+    entries.retain(|entry| entry.name().eq_ignore_ascii_case(&args.key));
+    if entries.is_empty() {
+        eprintln!("No entry is named {}", args.key);
+        return Ok(false);
+    }
+    record_history(cache_dir, &entries[0].name());
+    if let Some(what) = args.copy {
+        copy_to_clipboard(&entries[0], what, args.format)?;
+    }
+    if args.open {
+        open_in_editor(&entries)?;
+        return Ok(true);
+    }
+    args.format.apply();
+    let mut output = Vec::new();
+    print_human_output(
+        &SearchResults::from_entries(entries),
+        args.format,
+        OptionDisplay::everything(),
+        None,
+        None,
+        None,
+        &mut output,
+    )?;
+    write_paged(&output, args.no_pager, stdout)?;
+    Ok(true)
+}
@@ -0,0 +1,71 @@
+use crate::Errors;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeSet,
+    path::Path,
+};
+
+/// Keys of the entries kept at hand, `manix --bookmarks` only searching among them
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Bookmarks {
+    keys: BTreeSet<String>,
+}
+
+impl Bookmarks {
+    /// Reads the bookmarks, of which there are none until one is added
+    pub fn load(path: &Path) -> Result<Self, Errors> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Errors::FileIo {
+                filename: path.display().to_string(),
+                err,
+            }),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Errors> {
+        let content = serde_json::to_vec_pretty(self)?;
+        std::fs::write(path, content).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })
+    }
+
+    /// Returns false when the key was bookmarked already
+    pub fn add(&mut self, key: &str) -> bool {
+        self.keys.insert(key.to_string())
+    }
+
+    /// Returns false when the key wasn't bookmarked
+    pub fn remove(&mut self, key: &str) -> bool {
+        self.keys.remove(key)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.contains(key)
+    }
+
+    /// The keys in order
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.keys.iter().map(String::as_str)
+    }
+}
+
+#[test]
+fn test_bookmarks() {
+    let mut bookmarks = Bookmarks::default();
+    assert!(bookmarks.add("services.nginx.enable"));
+    assert!(bookmarks.add("lib.mkIf"));
+    assert!(!bookmarks.add("lib.mkIf"));
+    assert_eq!(
+        bookmarks.keys().collect::<Vec<_>>(),
+        vec!["lib.mkIf", "services.nginx.enable"]
+    );
+    assert!(bookmarks.remove("lib.mkIf"));
+    assert!(!bookmarks.remove("lib.mkIf"));
+    assert!(!bookmarks.contains("lib.mkIf"));
+}
@@ -0,0 +1,83 @@
+use crate::{
+    DocEntry,
+    MatchOptions,
+};
+
+/// `systemd AND timer OR cron NOT fcron` style queries. NOT applies to the term following it,
+/// terms next to each other have to match all of them and OR separates alternatives
+#[derive(Debug, PartialEq, Eq)]
+pub struct BooleanQuery {
+    /// Alternatives, made of terms along with whether they're negated
+    alternatives: Vec<Vec<(bool, String)>>,
+}
+
+impl BooleanQuery {
+    /// Whether the query uses any of the operators
+    pub fn is_boolean(query: &str) -> bool {
+        query
+            .split_whitespace()
+            .any(|word| matches!(word, "AND" | "OR" | "NOT"))
+    }
+
+    pub fn parse(query: &str) -> Self {
+        let mut alternatives = vec![Vec::new()];
+        let mut negated = false;
+        for word in query.split_whitespace() {
+            match word {
+                "AND" => {}
+                "OR" => {
+                    if !alternatives.last().is_some_and(Vec::is_empty) {
+                        alternatives.push(Vec::new());
+                    }
+                }
+                "NOT" => negated = !negated,
+                term => {
+                    if let Some(alternative) = alternatives.last_mut() {
+                        alternative.push((negated, term.to_string()));
+                    }
+                    negated = false;
+                }
+            }
+        }
+        alternatives.retain(|alternative| !alternative.is_empty());
+        Self { alternatives }
+    }
+
+    /// Terms are looked up in the key and the text of the entry
+    pub fn matches(&self, entry: &DocEntry, options: MatchOptions) -> bool {
+        let name = entry.name();
+        let text = entry.text();
+        self.alternatives.iter().any(|alternative| {
+            alternative.iter().all(|(negated, term)| {
+                let found = options.finds(&name, term) || options.finds(&text, term);
+                found != *negated
+            })
+        })
+    }
+}
+
+#[test]
+fn test_boolean_query() {
+    assert!(BooleanQuery::is_boolean("systemd AND timer"));
+    assert!(!BooleanQuery::is_boolean("systemd and timer"));
+
+    let query = BooleanQuery::parse("systemd AND timer OR cron NOT fcron");
+    assert_eq!(
+        query.alternatives,
+        vec![
+            vec![(false, "systemd".to_string()), (false, "timer".to_string())],
+            vec![(false, "cron".to_string()), (true, "fcron".to_string())],
+        ]
+    );
+
+    let matches = |key: &str| {
+        query.matches(
+            &DocEntry::NixpkgsTreeDoc(key.to_string()),
+            MatchOptions::default(),
+        )
+    };
+    assert!(matches("systemd.timers"));
+    assert!(matches("services.cron"));
+    assert!(!matches("services.fcron"));
+    assert!(!matches("systemd.services"));
+}
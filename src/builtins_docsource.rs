@@ -0,0 +1,175 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BuiltinDocumentation {
+    name: String,
+    args: Vec<String>,
+    doc: String,
+}
+
+impl BuiltinDocumentation {
+    pub fn name(&self) -> String {
+        format!("builtins.{}", self.name)
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.doc
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let args = self.args.join(" ");
+        if args.is_empty() {
+            format!("# {}\n{}\n\n", self.name().blue().bold(), self.doc.trim())
+        } else {
+            format!(
+                "# {} {}\n{}\n\n",
+                self.name().blue().bold(),
+                args.cyan(),
+                self.doc.trim()
+            )
+        }
+    }
+}
+
+/// A single entry of `nix __dump-builtins`
+#[derive(Deserialize)]
+struct DumpedBuiltin {
+    #[serde(default)]
+    args: Vec<String>,
+    #[serde(default)]
+    doc: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BuiltinsDatabase {
+    pub builtins: HashMap<String, BuiltinDocumentation>,
+}
+
+impl Default for BuiltinsDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BuiltinsDatabase {
+    pub fn new() -> Self {
+        Self {
+            builtins: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for BuiltinsDatabase {
+    const BUILT_FROM_NIXPKGS: bool = false;
+    const BUILT_FROM_NIX: bool = true;
+}
+
+impl DocSource for BuiltinsDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.builtins.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.builtins
+            .iter()
+            .filter(|(key, _)| {
+                // match both `mapAttrs` and `builtins.mapAttrs`
+                starts_with_insensitive_ascii(key.as_bytes(), query)
+                    || starts_with_insensitive_ascii(
+                        key.trim_start_matches("builtins.").as_bytes(),
+                        query,
+                    )
+            })
+            .map(|(_, value)| DocEntry::BuiltinDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.builtins
+            .iter()
+            .filter(|(key, _)| contains_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::BuiltinDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.builtins
+            .values()
+            .map(|value| DocEntry::BuiltinDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let new = parse_dumped_builtins(&dump_builtins()?)?;
+        let old = std::mem::replace(&mut self.builtins, new);
+
+        Ok(!self.builtins.keys().eq(old.keys()))
+    }
+}
+
+fn parse_dumped_builtins(content: &[u8]) -> Result<HashMap<String, BuiltinDocumentation>, Errors> {
+    let mut value: serde_json::Value = serde_json::from_slice(content)?;
+    // `nix __dump-language` nests the builtins under a `builtins` key
+    if let Some(builtins) = value.get_mut("builtins") {
+        value = builtins.take();
+    }
+    let dumped: HashMap<String, DumpedBuiltin> = serde_json::from_value(value)?;
+
+    Ok(dumped
+        .into_iter()
+        .map(|(name, builtin)| {
+            let doc = BuiltinDocumentation {
+                name,
+                args: builtin.args,
+                doc: builtin.doc,
+            };
+            (doc.name(), doc)
+        })
+        .collect())
+}
+
+fn dump_builtins() -> Result<Vec<u8>, std::io::Error> {
+    for subcommand in ["__dump-builtins", "__dump-language"] {
+        let output = run_nix(
+            Command::new("nix")
+                .arg("--extra-experimental-features")
+                .arg("nix-command")
+                .arg(subcommand),
+        )?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+    }
+
+    Err(std::io::Error::other("Failed to dump the Nix builtins"))
+}
+
+#[test]
+fn test_parse_dumped_builtins() {
+    let dumped = br#"{
+        "mapAttrs": { "args": ["f", "attrset"], "arity": 2, "doc": "Apply f to every element." },
+        "currentSystem": { "doc": "The current system." }
+    }"#;
+    let builtins = parse_dumped_builtins(dumped).unwrap();
+    assert_eq!(builtins.len(), 2);
+    assert_eq!(builtins["builtins.mapAttrs"].args, vec!["f", "attrset"]);
+    assert!(builtins["builtins.currentSystem"].args.is_empty());
+
+    let language = br#"{ "builtins": { "abort": { "args": ["s"], "doc": "Abort." } } }"#;
+    let builtins = parse_dumped_builtins(language).unwrap();
+    assert_eq!(builtins["builtins.abort"].doc, "Abort.");
+}
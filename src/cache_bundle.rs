@@ -0,0 +1,122 @@
+use crate::{
+    nixpkgs_revision,
+    prebuilt::is_cache_file_name,
+    write_atomically,
+    Errors,
+    CACHE_FORMAT_VERSION,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+};
+
+/// Every cache file in a single one, for `manix cache export` and `manix cache import`
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CacheBundle {
+    pub format_version: u32,
+    pub manix_version: String,
+    /// The revision of nixpkgs where the bundle was exported
+    pub nixpkgs: String,
+    /// The content of each cache file by its name
+    pub files: BTreeMap<String, Vec<u8>>,
+}
+
+impl CacheBundle {
+    /// Bundles the cache files of the directory
+    pub fn from_dir(dir: &Path) -> Result<Self, Errors> {
+        let file_io = |err| Errors::FileIo {
+            filename: dir.display().to_string(),
+            err,
+        };
+        let mut files = BTreeMap::new();
+        for entry in std::fs::read_dir(dir).map_err(file_io)? {
+            let entry = entry.map_err(file_io)?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if !is_cache_file_name(&name) || !entry.path().is_file() {
+                continue;
+            }
+            let content = std::fs::read(entry.path()).map_err(|err| Errors::FileIo {
+                filename: entry.path().display().to_string(),
+                err,
+            })?;
+            files.insert(name, content);
+        }
+        Ok(Self {
+            format_version: CACHE_FORMAT_VERSION,
+            manix_version: env!("CARGO_PKG_VERSION").to_string(),
+            nixpkgs: nixpkgs_revision(),
+            files,
+        })
+    }
+
+    pub fn load(path: &Path) -> Result<Self, Errors> {
+        let content = std::fs::read(path).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })?;
+        Ok(bincode::deserialize(&content)?)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Errors> {
+        let content = bincode::serialize(self)?;
+        write_atomically(path, &content).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })
+    }
+
+    /// Writes the cache files into the directory, replacing the ones that are there. Nothing is
+    /// written unless every name is one of a cache file, and each file is replaced at once since
+    /// searches may have the old one mapped
+    pub fn unpack(&self, dir: &Path) -> Result<(), Errors> {
+        if let Some(name) = self.files.keys().find(|name| !is_cache_file_name(name)) {
+            return Err(std::io::Error::other(format!("Unexpected cache file {:?}", name)).into());
+        }
+        for (name, content) in &self.files {
+            let path = dir.join(name);
+            write_atomically(&path, content).map_err(|err| Errors::FileIo {
+                filename: path.display().to_string(),
+                err,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Whether the caches would be taken as they are here, instead of being rebuilt
+    pub fn is_current(&self) -> bool {
+        self.format_version == CACHE_FORMAT_VERSION
+            && self.manix_version == env!("CARGO_PKG_VERSION")
+            && self.nixpkgs == nixpkgs_revision()
+    }
+}
+
+#[test]
+fn test_cache_bundle() {
+    let dir = std::env::temp_dir().join(format!("manix-bundle-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("cli_database.bin"), b"cli").unwrap();
+    std::fs::write(dir.join("last_version.txt"), b"0.8.0").unwrap();
+    let bundle = CacheBundle::from_dir(&dir).unwrap();
+    assert_eq!(
+        bundle.files.keys().collect::<Vec<_>>(),
+        vec!["cli_database.bin"]
+    );
+    assert!(bundle.is_current());
+
+    let path = dir.join("caches.bundle");
+    bundle.save(&path).unwrap();
+    std::fs::remove_file(dir.join("cli_database.bin")).unwrap();
+    CacheBundle::load(&path).unwrap().unpack(&dir).unwrap();
+    assert_eq!(std::fs::read(dir.join("cli_database.bin")).unwrap(), b"cli");
+
+    let mut bundle = CacheBundle::load(&path).unwrap();
+    bundle.files.insert("a.bin".to_string(), b"a".to_vec());
+    bundle.files.insert("b/../c.bin".to_string(), b"c".to_vec());
+    assert!(bundle.unpack(&dir).is_err());
+    assert!(!dir.join("a.bin").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,163 @@
+use crate::{
+    Cache,
+    CacheHeader,
+    DocSource,
+};
+use std::path::Path;
+
+const MINUTE: u64 = 60;
+const HOUR: u64 = 60 * MINUTE;
+const DAY: u64 = 24 * HOUR;
+
+/// What `manix cache status` tells about a cache file
+#[derive(Debug)]
+pub struct CacheStatus {
+    pub size: u64,
+    /// None for caches written before headers told when they were built
+    pub header: Option<CacheHeader>,
+    /// None when the cache can't be loaded
+    pub entries: Option<usize>,
+    pub outdated: bool,
+}
+
+impl CacheStatus {
+    /// None when there's no cache at the path
+    pub fn of<T>(path: &Path) -> Option<Self>
+    where
+        T: DocSource + Cache + serde::de::DeserializeOwned,
+    {
+        let content = std::fs::read(path).ok()?;
+        Some(Self {
+            size: content.len() as u64,
+            header: CacheHeader::read(content.as_slice()).ok(),
+            entries: T::load(&content).ok().map(|source| source.all_keys().len()),
+            outdated: T::is_outdated(path),
+        })
+    }
+
+    /// A line telling all of it, `now` being in seconds since the epoch
+    pub fn describe(&self, now: u64) -> String {
+        let mut parts = vec![format_size(self.size)];
+        match self.entries {
+            Some(entries) => parts.push(format!("{} entries", entries)),
+            None if !self.outdated => parts.push("unreadable".to_string()),
+            None => {}
+        }
+        if let Some(header) = &self.header {
+            if let Some(nixpkgs) = &header.nixpkgs {
+                parts.push(format!("nixpkgs {}", short_revision(nixpkgs)));
+            }
+            if let Some(nix) = header.nix.as_ref().filter(|nix| !nix.is_empty()) {
+                parts.push(format!("nix {}", nix));
+            }
+            parts.push(format!(
+                "built {} ({} ago)",
+                format_date(header.built_at),
+                format_age(now.saturating_sub(header.built_at))
+            ));
+        }
+        if self.outdated {
+            parts.push("outdated, rebuilt on the next search".to_string());
+        }
+        parts.join(", ")
+    }
+}
+
+/// Git revisions cut to their first 12 characters, store paths left as they are
+fn short_revision(revision: &str) -> &str {
+    if revision.len() == 40 && revision.chars().all(|c| c.is_ascii_hexdigit()) {
+        &revision[..12]
+    } else {
+        revision
+    }
+}
+
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 3] = ["KiB", "MiB", "GiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// How long ago something was, in the largest unit it spans twice
+pub fn format_age(seconds: u64) -> String {
+    match seconds {
+        s if s < 2 * MINUTE => format!("{} seconds", s),
+        s if s < 2 * HOUR => format!("{} minutes", s / MINUTE),
+        s if s < 2 * DAY => format!("{} hours", s / HOUR),
+        s => format!("{} days", s / DAY),
+    }
+}
+
+/// The date and time, in UTC, of seconds since the epoch
+pub fn format_date(seconds: u64) -> String {
+    // from the days since the epoch to the civil date, as in Howard Hinnant's `civil_from_days`
+    let days = (seconds / DAY) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    let time = seconds % DAY;
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        time / HOUR,
+        time % HOUR / MINUTE
+    )
+}
+
+#[test]
+fn test_format() {
+    assert_eq!(format_size(512), "512 B");
+    assert_eq!(format_size(12 * 1024 * 1024 + 300 * 1024), "12.3 MiB");
+    assert_eq!(format_age(90), "90 seconds");
+    assert_eq!(format_age(3 * DAY + HOUR), "3 days");
+    assert_eq!(format_date(0), "1970-01-01 00:00 UTC");
+    assert_eq!(format_date(951_827_696), "2000-02-29 12:34 UTC");
+    assert_eq!(format_date(1_790_000_000), "2026-09-21 14:13 UTC");
+    assert_eq!(
+        short_revision("0c19708cf035f50d28eb4b2b8e7a79d4dc52f6bb"),
+        "0c19708cf035"
+    );
+}
+
+#[test]
+fn test_cache_status() {
+    use crate::cli_docsource::CliDatabase;
+
+    let path = std::env::temp_dir().join(format!("manix-status-{}.bin", std::process::id()));
+    assert!(CacheStatus::of::<CliDatabase>(&path).is_none());
+    CliDatabase::new().save(&path).unwrap();
+    let status = CacheStatus::of::<CliDatabase>(&path).unwrap();
+    assert_eq!(status.entries, Some(0));
+    assert!(!status.outdated);
+    let now = crate::history::now();
+    assert!(status.describe(now).contains(", 0 entries, "));
+    // dumped from the nix binary, whichever nixpkgs is in use
+    assert!(!status.describe(now).contains("nixpkgs "));
+
+    let status = CacheStatus::of::<crate::lib_docsource::LibDatabase>(&path).unwrap();
+    assert!(status.outdated);
+    assert!(status
+        .describe(now)
+        .ends_with("outdated, rebuilt on the next search"));
+    std::fs::remove_file(&path).unwrap();
+}
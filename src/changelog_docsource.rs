@@ -0,0 +1,280 @@
+use crate::{
+    comments_docsource::{
+        find_nix_path_entry,
+        get_nixpkgs_root,
+    },
+    contains_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use rnix::{
+    ast::{
+        AttrSet,
+        Expr,
+        HasEntry,
+        InterpolPart,
+    },
+    Root,
+};
+use rowan::ast::AstNode;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChangelogDocumentation {
+    key: String,
+    release: String,
+    section: String,
+    text: String,
+}
+
+impl ChangelogDocumentation {
+    pub fn name(&self) -> String {
+        self.key.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}", self.release.blue().bold());
+        if !self.section.is_empty() {
+            output.push_str(&format!(" ({})", self.section.white()));
+        }
+        output.push_str(&format!("\n{}\n\n", self.text.trim()));
+        output
+    }
+}
+
+/// NixOS release notes and home-manager news, one entry per item
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangelogDatabase {
+    pub entries: HashMap<String, ChangelogDocumentation>,
+}
+
+impl Default for ChangelogDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ChangelogDatabase {
+    pub fn new() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for ChangelogDatabase {}
+
+impl DocSource for ChangelogDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.entries.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.entries
+            .values()
+            .filter(|value| mentions(&value.text, query))
+            .map(|value| DocEntry::ChangelogDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.entries
+            .values()
+            .filter(|value| contains_insensitive_ascii(value.text.as_bytes(), query))
+            .map(|value| DocEntry::ChangelogDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.entries
+            .values()
+            .map(|value| DocEntry::ChangelogDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let mut new = Vec::new();
+
+        let notes_dir = get_nixpkgs_root().join("nixos/doc/manual/release-notes");
+        for file in walkdir::WalkDir::new(&notes_dir)
+            .into_iter()
+            .filter_map(Result::ok)
+        {
+            let Some(release) = release_of(file.path()) else {
+                continue;
+            };
+            if let Ok(content) = std::fs::read_to_string(file.path()) {
+                new.extend(parse_release_notes(&release, &content));
+            }
+        }
+
+        // home-manager is optional, its news live either in news.nix or in a news/ directory
+        if let Some(root) = find_nix_path_entry("home-manager") {
+            for file in walkdir::WalkDir::new(root.join("modules/misc"))
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|e| e.path().to_string_lossy().contains("news"))
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("nix"))
+            {
+                if let Ok(content) = std::fs::read_to_string(file.path()) {
+                    new.extend(parse_home_manager_news(&content));
+                }
+            }
+        }
+
+        let new = new
+            .into_iter()
+            .map(|entry| (entry.key.clone(), entry))
+            .collect();
+        let old = std::mem::replace(&mut self.entries, new);
+
+        Ok(!self.entries.keys().eq(old.keys()))
+    }
+}
+
+/// Whether one of the words of the text starts with the query, option paths count as one word
+fn mentions(text: &str, query: &Lowercase) -> bool {
+    let text = text.to_ascii_lowercase();
+    let query = String::from_utf8_lossy(query.0);
+    text.match_indices(query.as_ref()).any(|(i, _)| {
+        text[..i]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !(c.is_ascii_alphanumeric() || "-_.".contains(c)))
+    })
+}
+
+/// `rl-2405.section.md` holds the notes of NixOS 24.05
+fn release_of(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let version = name.strip_prefix("rl-")?.split('.').next()?;
+    if version.len() != 4 || !version.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("NixOS {}.{}", &version[..2], &version[2..]))
+}
+
+/// Every top-level bullet point of the release notes is one entry
+fn parse_release_notes(release: &str, content: &str) -> Vec<ChangelogDocumentation> {
+    let mut entries = Vec::new();
+    let mut section = String::new();
+    let mut item: Option<String> = None;
+
+    let mut finish = |item: Option<String>, section: &str| {
+        if let Some(text) = item {
+            entries.push(ChangelogDocumentation {
+                key: format!("{} #{}", release, entries.len() + 1),
+                release: release.to_string(),
+                section: section.to_string(),
+                text: text.trim().to_string(),
+            });
+        }
+    };
+
+    for line in content.lines() {
+        if let Some(heading) = line.strip_prefix("## ") {
+            finish(item.take(), &section);
+            section = heading
+                .split(" {")
+                .next()
+                .unwrap_or(heading)
+                .trim()
+                .to_string();
+        } else if let Some(text) = line.strip_prefix("- ") {
+            finish(item.take(), &section);
+            item = Some(text.to_string());
+        } else if let Some(text) = item.as_mut() {
+            if line.starts_with(' ') || line.is_empty() {
+                text.push('\n');
+                text.push_str(line.trim_start());
+            } else {
+                finish(item.take(), &section);
+            }
+        }
+    }
+    finish(item.take(), &section);
+
+    entries
+}
+
+fn str_value(set: &AttrSet, name: &str) -> Option<String> {
+    let value = set.attrpath_values().find(|entry| {
+        entry
+            .attrpath()
+            .map(|path| path.to_string() == name)
+            .unwrap_or(false)
+    })?;
+    match value.value()? {
+        Expr::Str(s) => Some(
+            s.normalized_parts()
+                .into_iter()
+                .map(|part| match part {
+                    InterpolPart::Literal(text) => text,
+                    InterpolPart::Interpolation(i) => i.to_string(),
+                })
+                .collect(),
+        ),
+        _ => None,
+    }
+}
+
+/// News are `{ time = "2024-05-10T21:28:38+00:00"; message = ''...''; }` attribute sets
+fn parse_home_manager_news(content: &str) -> Vec<ChangelogDocumentation> {
+    let Ok(ast) = Root::parse(content).ok() else {
+        return Vec::new();
+    };
+
+    ast.syntax()
+        .descendants()
+        .filter_map(AttrSet::cast)
+        .filter_map(|set| {
+            let time = str_value(&set, "time")?;
+            let message = str_value(&set, "message")?;
+            let date = time.split('T').next().unwrap_or(&time).to_string();
+            Some(ChangelogDocumentation {
+                key: format!("Home Manager news {}", time),
+                release: format!("Home Manager news {}", date),
+                section: String::new(),
+                text: message,
+            })
+        })
+        .collect()
+}
+
+#[test]
+fn test_parse_release_notes() {
+    let content = "# Release 24.05 {#sec-release-24.05}
+
+## Backward Incompatibilities {#sec-release-24.05-incompatibilities}
+
+- `services.foo.settings` was renamed to
+  `services.foo.config`.
+
+- The `bar` package was removed.
+";
+    let entries = parse_release_notes("NixOS 24.05", content);
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].section, "Backward Incompatibilities");
+    assert_eq!(
+        entries[0].text,
+        "`services.foo.settings` was renamed to\n`services.foo.config`."
+    );
+    assert!(mentions(&entries[0].text, &Lowercase(b"services.foo")));
+    assert!(!mentions(&entries[1].text, &Lowercase(b"ar")));
+
+    assert_eq!(
+        release_of(Path::new("rl-2405.section.md")).as_deref(),
+        Some("NixOS 24.05")
+    );
+}
@@ -0,0 +1,223 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CliCommandDocumentation {
+    command: String,
+    description: String,
+    doc: String,
+    flags: Vec<(String, String)>,
+}
+
+impl CliCommandDocumentation {
+    pub fn name(&self) -> String {
+        self.command.clone()
+    }
+
+    pub(crate) fn text(&self) -> String {
+        format!("{}\n{}", self.description, self.doc)
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}\n", self.command.blue().bold());
+        if !self.description.is_empty() {
+            output.push_str(&format!("{}\n", self.description));
+        }
+        if !self.flags.is_empty() {
+            output.push_str("\nFlags:\n");
+            for (name, description) in &self.flags {
+                output.push_str(&format!("  {}: {}\n", name.green(), description));
+            }
+        }
+        if !self.doc.is_empty() {
+            output.push_str(&format!("\n{}\n", self.doc.trim()));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CliDatabase {
+    pub commands: HashMap<String, CliCommandDocumentation>,
+}
+
+impl Default for CliDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CliDatabase {
+    pub fn new() -> Self {
+        Self {
+            commands: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for CliDatabase {
+    const BUILT_FROM_NIXPKGS: bool = false;
+    const BUILT_FROM_NIX: bool = true;
+}
+
+impl DocSource for CliDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.commands.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.commands
+            .iter()
+            .filter(|(key, _)| starts_with_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::CliDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.commands
+            .iter()
+            .filter(|(key, _)| contains_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::CliDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.commands
+            .values()
+            .map(|value| DocEntry::CliDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let dumped: Value = serde_json::from_slice(&dump_cli()?)?;
+        let mut new = HashMap::new();
+        // depending on the Nix version the command tree is nested under `args`
+        let root = dumped.get("args").unwrap_or(&dumped);
+        collect_commands("nix", root, &mut new);
+        let old = std::mem::replace(&mut self.commands, new);
+
+        Ok(!self.commands.keys().eq(old.keys()))
+    }
+}
+
+fn str_field(node: &Value, field: &str) -> String {
+    node.get(field)
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn format_flag(name: &str, flag: &Value) -> String {
+    let mut formatted = format!("--{}", name);
+    if let Some(short) = flag.get("shortName").and_then(Value::as_str) {
+        formatted.push_str(&format!(" / -{}", short));
+    }
+    if let Some(labels) = flag.get("labels").and_then(Value::as_array) {
+        for label in labels.iter().filter_map(Value::as_str) {
+            formatted.push_str(&format!(" <{}>", label));
+        }
+    }
+    formatted
+}
+
+fn collect_commands(
+    command: &str,
+    node: &Value,
+    commands: &mut HashMap<String, CliCommandDocumentation>,
+) {
+    let mut flags = node
+        .get("flags")
+        .and_then(Value::as_object)
+        .map(|flags| {
+            flags
+                .iter()
+                .map(|(name, flag)| (format_flag(name, flag), str_field(flag, "description")))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    flags.sort();
+
+    commands.insert(
+        command.to_string(),
+        CliCommandDocumentation {
+            command: command.to_string(),
+            description: str_field(node, "description"),
+            doc: str_field(node, "doc"),
+            flags,
+        },
+    );
+
+    if let Some(subcommands) = node.get("commands").and_then(Value::as_object) {
+        for (name, subcommand) in subcommands {
+            collect_commands(&format!("{} {}", command, name), subcommand, commands);
+        }
+    }
+}
+
+fn dump_cli() -> Result<Vec<u8>, std::io::Error> {
+    let output = run_nix(
+        Command::new("nix")
+            .arg("--extra-experimental-features")
+            .arg("nix-command")
+            .arg("__dump-cli"),
+    )?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_collect_commands() {
+    let dumped: Value = serde_json::from_str(
+        r#"{
+            "flags": { "version": { "description": "Show version information." } },
+            "commands": {
+                "flake": {
+                    "description": "manage Nix flakes",
+                    "commands": {
+                        "lock": {
+                            "description": "create missing lock file entries",
+                            "doc": "Update the lock file.",
+                            "flags": {
+                                "override-input": {
+                                    "description": "Override a specific flake input.",
+                                    "labels": ["input-path", "flake-url"]
+                                },
+                                "quiet": { "description": "Decrease verbosity.", "shortName": "q" }
+                            }
+                        }
+                    }
+                }
+            }
+        }"#,
+    )
+    .unwrap();
+    let mut commands = HashMap::new();
+    collect_commands("nix", &dumped, &mut commands);
+
+    assert_eq!(commands.len(), 3);
+    let lock = &commands["nix flake lock"];
+    assert_eq!(lock.description, "create missing lock file entries");
+    assert_eq!(lock.flags[0].0, "--override-input <input-path> <flake-url>");
+    assert_eq!(lock.flags[1].0, "--quiet / -q");
+}
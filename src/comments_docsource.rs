@@ -1,7 +1,12 @@
 use crate::{
     contains_insensitive_ascii,
+    options_docsource::{
+        declaration_line,
+        run_nix,
+    },
     starts_with_insensitive_ascii,
     Cache,
+    CacheHeader,
     DocEntry,
     DocSource,
     Errors,
@@ -27,18 +32,26 @@ use rnix::{
 use rowan::ast::AstNode;
 use serde::{
     Deserialize,
+    Deserializer,
     Serialize,
+    Serializer,
 };
 use std::{
-    collections::HashMap,
-    path::PathBuf,
+    collections::{
+        HashMap,
+        HashSet,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
     process::Command,
 };
 lazy_static! {
     static ref NIXPKGS_PATH: PathBuf = get_nixpkgs_root();
 }
 
-fn find_comments(node: SyntaxNode) -> Option<Vec<String>> {
+pub(crate) fn find_comments(node: SyntaxNode) -> Option<Vec<String>> {
     let mut node = NodeOrToken::Node(node);
     let mut comments = Vec::<String>::new();
 
@@ -157,45 +170,158 @@ impl CommentDocumentation {
     pub fn name(&self) -> String {
         self.key.to_owned()
     }
+
+    pub(crate) fn text(&self) -> String {
+        self.comments.join("\n")
+    }
+    /// The file the comment was found in when the cache was built, and the line defining the key
+    pub fn location(&self) -> Option<(PathBuf, Option<usize>)> {
+        let path = self.path.clone()?;
+        let line = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| declaration_line(&content, std::slice::from_ref(&self.key)));
+        Some((path, line))
+    }
+}
+
+/// Where a string is in `CommentsDatabase::text`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+struct Span {
+    start: u32,
+    end: u32,
+}
+
+impl Span {
+    /// Empty when the span isn't a string of the text, which its checksum keeps from happening
+    fn get(self, text: &[u8]) -> &str {
+        text.get(self.start as usize..self.end as usize)
+            .and_then(|bytes| std::str::from_utf8(bytes).ok())
+            .unwrap_or_default()
+    }
+    fn is_empty(self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// Appends the string to the text, giving where it went
+fn push(text: &mut String, s: &str) -> Span {
+    let offset =
+        |text: &String| u32::try_from(text.len()).expect("the comments of nixpkgs fit in 4 GiB");
+    let start = offset(text);
+    text.push_str(s);
+    Span {
+        start,
+        end: offset(text),
+    }
+}
+
+/// A definition of the cache, the comments of which are separated by NULs
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct Definition {
+    key: Span,
+    comments: Span,
+}
+
+/// The definitions found in a nix file, along with where the file was
+#[derive(Debug, Serialize, Deserialize)]
+struct FileDefinitions {
+    path: PathBuf,
+    definitions: Vec<Definition>,
+}
+
+/// The keys and comments of all the definitions, one after the other
+#[derive(Debug)]
+enum Text {
+    Owned(String),
+    /// Read in place from the cache, which ends with the text
+    Mapped {
+        map: memmap2::Mmap,
+        start: usize,
+    },
+}
+
+impl Text {
+    fn as_bytes(&self) -> &[u8] {
+        match self {
+            Text::Owned(text) => text.as_bytes(),
+            Text::Mapped { map, start } => &map[*start..],
+        }
+    }
+
+    /// Copies the text out of the cache the first time it's changed
+    fn to_mut(&mut self) -> &mut String {
+        if let Text::Mapped { .. } = self {
+            *self = Text::Owned(String::from_utf8_lossy(self.as_bytes()).into_owned());
+        }
+        match self {
+            Text::Owned(text) => text,
+            Text::Mapped { .. } => unreachable!("the text was just copied"),
+        }
+    }
 }
 
+impl Serialize for Text {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // bincode writes bytes the way it writes strings, their length and then them
+        serializer.serialize_bytes(self.as_bytes())
+    }
+}
+
+impl<'de> Deserialize<'de> for Text {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(Text::Owned)
+    }
+}
+
+/// What comes before the text in the cache, the fields of `CommentsDatabase` in the same order
+#[derive(Deserialize)]
+struct Index {
+    hash_to_defs: HashMap<u32, FileDefinitions>,
+    files: HashMap<PathBuf, u32>,
+}
+
+/// The definitions of the nix files by the hash of their content, so that only the files whose
+/// content changed are parsed again. The keys and comments of all of them are in a single string
+/// at the end of the cache, which is left uncompressed so that `open` maps it instead of
+/// allocating a string for each. Comments are only copied out of it for the definitions that make
+/// it into the results
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CommentsDatabase {
-    pub hash_to_defs: HashMap<u32, Vec<CommentDocumentation>>,
+    hash_to_defs: HashMap<u32, FileDefinitions>,
+    /// The hash of each file, by its path relative to nixpkgs
+    pub files: HashMap<PathBuf, u32>,
+    /// Last, so that it ends the cache
+    text: Text,
 }
 
 impl DocSource for CommentsDatabase {
     fn all_keys(&self) -> Vec<&str> {
         self.hash_to_defs
             .values()
-            .flatten()
-            .map(|def| def.key.as_ref())
+            .flat_map(|file| &file.definitions)
+            .map(|def| self.key(def))
             .collect()
     }
     fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
-        self.hash_to_defs
-            .values()
-            .flatten()
-            .filter(|d| {
-                !d.comments.is_empty() && starts_with_insensitive_ascii(d.key.as_bytes(), query)
-            })
-            .cloned()
-            .map(DocEntry::CommentDoc)
+        self.documented()
+            .filter(|(_, def)| starts_with_insensitive_ascii(self.key(def).as_bytes(), query))
+            .map(|(path, def)| self.entry(path, def))
             .collect()
     }
     fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
-        self.hash_to_defs
-            .values()
-            .flatten()
-            .filter(|d| {
-                !d.comments.is_empty() && contains_insensitive_ascii(d.key.as_bytes(), query)
-            })
-            .cloned()
-            .map(DocEntry::CommentDoc)
+        self.documented()
+            .filter(|(_, def)| contains_insensitive_ascii(self.key(def).as_bytes(), query))
+            .map(|(path, def)| self.entry(path, def))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.documented()
+            .map(|(path, def)| self.entry(path, def))
             .collect()
     }
     fn update(&mut self) -> Result<bool, Errors> {
-        let files = find_nix_files(get_nixpkgs_root())
+        let root = get_nixpkgs_root();
+        let files = find_nix_files(root.clone())
             .par_iter()
             .filter_map(|f| match std::fs::read_to_string(f.path()) {
                 Ok(content) => {
@@ -219,31 +345,51 @@ impl DocSource for CommentsDatabase {
                     Ok(ast) => ast,
                     Err(e) => {
                         eprintln!("Error parsing {}: {}", path.display(), e);
-                        return (*hash, Vec::new());
+                        return (*hash, path.clone(), Vec::new());
                     }
                 };
 
-                let definitions = walk_ast(ast)
-                    .into_iter()
-                    .map(|def| def.with_path(path.clone()))
-                    .collect();
-
-                (*hash, definitions)
+                (*hash, path.clone(), walk_ast(ast))
             })
-            .collect::<Vec<(u32, Vec<CommentDocumentation>)>>();
-        if new_defs.is_empty() {
-            return Ok(false);
+            .collect::<Vec<(u32, PathBuf, Vec<CommentDocumentation>)>>();
+        for (hash, path, defs) in new_defs {
+            self.add_to_cache(hash, path, defs);
         }
 
-        for (hash, defs) in new_defs {
-            self.add_to_cache(hash, defs);
+        let files = files
+            .into_iter()
+            .map(|(hash, path, _)| (path, hash))
+            .collect::<HashMap<_, _>>();
+        // the files that are left move to the store path of the new nixpkgs
+        let mut moved = false;
+        for (path, hash) in &files {
+            if let Some(file) = self.hash_to_defs.get_mut(hash) {
+                if !file.path.starts_with(&root) {
+                    file.path = path.clone();
+                    moved = true;
+                }
+            }
         }
+        let relative = |path: &PathBuf| path.strip_prefix(&root).unwrap_or(path).to_path_buf();
+        let files = files
+            .iter()
+            .map(|(path, hash)| (relative(path), *hash))
+            .collect::<HashMap<_, _>>();
+        if files == self.files && !moved {
+            return Ok(false);
+        }
+        self.retain(&files.values().collect());
+        self.files = files;
 
         Ok(true)
     }
 }
 
-impl Cache for CommentsDatabase {}
+impl Cache for CommentsDatabase {
+    /// Kept across nixpkgs versions, updating it only parses the files that changed
+    const BUILT_FROM_NIXPKGS: bool = false;
+    const COMPRESSED: bool = false;
+}
 impl Default for CommentsDatabase {
     fn default() -> Self {
         Self::new()
@@ -254,19 +400,95 @@ impl CommentsDatabase {
     pub fn new() -> Self {
         Self {
             hash_to_defs: HashMap::new(),
+            files: HashMap::new(),
+            text: Text::Owned(String::new()),
+        }
+    }
+
+    /// Loads the cache at the path as `load` does, only deserializing the definitions. Their text
+    /// is read from the mapped cache when searched
+    pub fn open(path: &Path) -> Result<Self, Errors> {
+        let file = std::fs::File::open(path)?;
+        // SAFETY: caches are replaced by renaming a new file over them and never written in
+        // place, so the mapped file doesn't change while it's mapped
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        let (header, mut content) = CacheHeader::check_content(&map, &CacheHeader::of::<Self>())?;
+        if header.compressed {
+            return Self::load(&map);
+        }
+        let index: Index = bincode::deserialize_from(&mut content)?;
+        let len: u64 = bincode::deserialize_from(&mut content)?;
+        if len != content.len() as u64 {
+            let mismatch = "the text doesn't end the cache".to_string();
+            return Err(Box::new(bincode::ErrorKind::Custom(mismatch)).into());
         }
+        let start = map.len() - content.len();
+        Ok(Self {
+            hash_to_defs: index.hash_to_defs,
+            files: index.files,
+            text: Text::Mapped { map, start },
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hash_to_defs.is_empty()
     }
 
     fn is_in_cache(&self, hash: &u32) -> bool {
         self.hash_to_defs.contains_key(hash)
     }
 
-    fn add_to_cache(
-        &mut self,
-        hash: u32,
-        defs: Vec<CommentDocumentation>,
-    ) -> Option<Vec<CommentDocumentation>> {
-        self.hash_to_defs.insert(hash, defs)
+    fn key(&self, def: &Definition) -> &str {
+        def.key.get(self.text.as_bytes())
+    }
+
+    /// The definitions that have comments, along with the file they're in
+    fn documented(&self) -> impl Iterator<Item = (&Path, &Definition)> {
+        self.hash_to_defs.values().flat_map(|file| {
+            file.definitions
+                .iter()
+                .filter(|def| !def.comments.is_empty())
+                .map(|def| (file.path.as_path(), def))
+        })
+    }
+
+    fn entry(&self, path: &Path, def: &Definition) -> DocEntry {
+        let comments = def
+            .comments
+            .get(self.text.as_bytes())
+            .split('\0')
+            .map(str::to_string)
+            .collect();
+        DocEntry::CommentDoc(
+            CommentDocumentation::new(self.key(def).to_string(), comments)
+                .with_path(path.to_path_buf()),
+        )
+    }
+
+    fn add_to_cache(&mut self, hash: u32, path: PathBuf, defs: Vec<CommentDocumentation>) {
+        let text = self.text.to_mut();
+        let definitions = defs
+            .iter()
+            .map(|def| Definition {
+                key: push(text, &def.key),
+                comments: push(text, &def.comments.join("\0")),
+            })
+            .collect();
+        self.hash_to_defs
+            .insert(hash, FileDefinitions { path, definitions });
+    }
+
+    /// Keeps the files of the given hashes only, what the others had being dropped from the text
+    fn retain(&mut self, hashes: &HashSet<&u32>) {
+        self.hash_to_defs.retain(|hash, _| hashes.contains(hash));
+        let old = std::mem::replace(&mut self.text, Text::Owned(String::new()));
+        let text = self.text.to_mut();
+        for file in self.hash_to_defs.values_mut() {
+            for def in &mut file.definitions {
+                def.key = push(text, def.key.get(old.as_bytes()));
+                def.comments = push(text, def.comments.get(old.as_bytes()));
+            }
+        }
     }
 }
 
@@ -280,14 +502,15 @@ fn find_nix_files(path: PathBuf) -> Vec<walkdir::DirEntry> {
         .collect::<Vec<walkdir::DirEntry>>()
 }
 
-fn get_nixpkgs_root() -> PathBuf {
-    let channel_path = Command::new("nix-instantiate")
-        .arg("--eval")
-        .arg("--strict")
-        .arg("-E")
-        .arg("<nixpkgs>")
-        .output()
-        .map(|o| String::from_utf8(o.stdout));
+pub(crate) fn get_nixpkgs_root() -> PathBuf {
+    let channel_path = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--strict")
+            .arg("-E")
+            .arg("<nixpkgs>"),
+    )
+    .map(|o| String::from_utf8(o.stdout));
 
     if let Ok(Ok(path)) = channel_path {
         PathBuf::from(path.trim_end())
@@ -295,3 +518,72 @@ fn get_nixpkgs_root() -> PathBuf {
         PathBuf::from(".")
     }
 }
+
+/// Resolves `<name>` from the NIX_PATH, if it's there
+pub(crate) fn find_nix_path_entry(name: &str) -> Option<PathBuf> {
+    let output = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("-E")
+            .arg(format!("<{}>", name)),
+    )
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(PathBuf::from(
+        String::from_utf8(output.stdout).ok()?.trim_end(),
+    ))
+}
+
+#[test]
+fn test_comments_database() {
+    let mut db = CommentsDatabase::new();
+    let documented = |key: &str, comments: &[&str]| {
+        CommentDocumentation::new(
+            key.to_string(),
+            comments.iter().map(|c| c.to_string()).collect(),
+        )
+    };
+    db.add_to_cache(
+        1,
+        PathBuf::from("/nixpkgs/lib/lists.nix"),
+        vec![
+            documented("foldl", &["# Left fold", "# foldl op nul list"]),
+            documented("go", &[]),
+        ],
+    );
+    db.add_to_cache(
+        2,
+        PathBuf::from("/nixpkgs/lib/strings.nix"),
+        vec![documented("concatStrings", &["# Concatenates strings"])],
+    );
+    assert_eq!(db.all_keys().len(), 3);
+    assert!(db.search(&Lowercase(b"go")).is_empty());
+    match &db.search(&Lowercase(b"fold"))[..] {
+        [DocEntry::CommentDoc(def)] => assert_eq!(
+            def,
+            &documented("foldl", &["# Left fold", "# foldl op nul list"])
+                .with_path(PathBuf::from("/nixpkgs/lib/lists.nix"))
+        ),
+        entries => panic!("unexpected {:?}", entries),
+    }
+
+    let path = std::env::temp_dir().join(format!("manix-comments-{}.bin", std::process::id()));
+    db.save(&path).unwrap();
+    let mut opened = CommentsDatabase::open(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert!(matches!(opened.text, Text::Mapped { .. }));
+    assert_eq!(opened.text.as_bytes(), db.text.as_bytes());
+    assert_eq!(
+        opened.search(&Lowercase(b"fold")),
+        db.search(&Lowercase(b"fold"))
+    );
+
+    opened.retain(&[&2].into_iter().collect());
+    assert_eq!(
+        opened.text.as_bytes(),
+        b"concatStrings# Concatenates strings"
+    );
+    assert_eq!(opened.search_liberal(&Lowercase(b"strings")).len(), 1);
+}
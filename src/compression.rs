@@ -0,0 +1,21 @@
+use std::io;
+
+/// The default of the zstd command, fast enough to build on and plenty smaller
+const LEVEL: i32 = 3;
+
+pub fn compress(content: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::bulk::compress(content, LEVEL)
+}
+
+pub fn decompress(content: &[u8]) -> io::Result<Vec<u8>> {
+    zstd::decode_all(content)
+}
+
+#[test]
+fn test_compression() {
+    let content = b"services.nginx.enable ".repeat(1000);
+    let compressed = compress(&content).unwrap();
+    assert!(compressed.len() < content.len());
+    assert_eq!(decompress(&compressed).unwrap(), content);
+    assert!(decompress(&content).is_err());
+}
@@ -0,0 +1,112 @@
+use crate::{
+    options_docsource::NixSettings,
+    prebuilt::PrebuiltCaches,
+    Errors,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Settings read from `$XDG_CONFIG_HOME/manix/config.json`, everything being optional
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Queries along with what they stand for, `"docker": "virtualisation.docker"`, which is
+    /// searched for as well
+    pub synonyms: HashMap<String, String>,
+    /// Where `manix update --fetch` gets the caches from
+    pub prebuilt_caches: Option<PrebuiltCaches>,
+    /// The nixpkgs the caches are built from when no --nixpkgs or --channel is given, see
+    /// `nixpkgs::nix_path_location`
+    pub nixpkgs: Option<String>,
+    /// How many days old the caches may get before manix refreshes them in the background
+    pub max_cache_age_days: Option<u64>,
+    /// options.json files built already, read instead of building the options
+    pub options_json: OptionsJson,
+    /// Whether the caches of projects with a flake.lock are built from the inputs it locks, as
+    /// --project does
+    pub project_caches: bool,
+    /// Arguments, environment and timeout of the nix commands building the options
+    pub nix: NixSettings,
+    /// Sources that are never built nor searched, by the names --source takes
+    pub disabled_sources: Vec<String>,
+    /// The search.nixos.org index --online queries, `latest-42-nixos-unstable` for instance,
+    /// found on its own when missing
+    pub online_index: Option<String>,
+}
+
+/// Either options.json files or outputs containing one, like
+/// `config.system.build.manual.optionsJSON`, for the sources of module options
+#[derive(Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct OptionsJson {
+    pub nixos: Option<PathBuf>,
+    pub home_manager: Option<PathBuf>,
+    pub darwin: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, Errors> {
+        let content = std::fs::read(path).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })?;
+        Ok(serde_json::from_slice(&content)?)
+    }
+
+    /// What the query stands for, whatever its case
+    pub fn synonym(&self, query: &str) -> Option<&str> {
+        self.synonyms
+            .iter()
+            .find(|(synonym, _)| synonym.eq_ignore_ascii_case(query.trim()))
+            .map(|(_, expansion)| expansion.as_str())
+    }
+}
+
+#[test]
+fn test_synonym() {
+    let config: Config = serde_json::from_str(
+        r#"{ "synonyms": { "firewall": "networking.firewall", "Docker": "virtualisation.docker" } }"#,
+    )
+    .unwrap();
+    assert_eq!(config.synonym("firewall"), Some("networking.firewall"));
+    assert_eq!(config.synonym("docker "), Some("virtualisation.docker"));
+    assert_eq!(config.synonym("podman"), None);
+    assert!(serde_json::from_str::<Config>(r#"{ "synonym": {} }"#).is_err());
+}
+
+#[test]
+fn test_options_json() {
+    let config: Config =
+        serde_json::from_str(r#"{ "options_json": { "nixos": "/nix/store/x-options.json" } }"#)
+            .unwrap();
+    assert_eq!(
+        config.options_json.nixos,
+        Some(PathBuf::from("/nix/store/x-options.json"))
+    );
+    assert_eq!(config.options_json.home_manager, None);
+    assert!(serde_json::from_str::<Config>(r#"{ "options_json": { "hm": "" } }"#).is_err());
+}
+
+#[test]
+fn test_prebuilt_caches() {
+    let config: Config = serde_json::from_str(
+        r#"{ "prebuilt_caches": { "url": "https://example.org/manix/", "public_key": "RWQ" } }"#,
+    )
+    .unwrap();
+    let prebuilt = config.prebuilt_caches.unwrap();
+    let revision = "0c19708cf035f50d28eb4b2b8e7a79d4dc52f6bb";
+    assert_eq!(
+        prebuilt.base_url(revision),
+        format!(
+            "https://example.org/manix/{}/{}",
+            env!("CARGO_PKG_VERSION"),
+            revision
+        )
+    );
+}
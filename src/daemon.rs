@@ -0,0 +1,168 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    io::{
+        self,
+        BufRead,
+        BufReader,
+        Write,
+    },
+    os::unix::net::{
+        UnixListener,
+        UnixStream,
+    },
+    path::Path,
+    time::Duration,
+};
+
+/// How long the daemon waits on a connection, for its request or to take the response, before
+/// going on to the next one
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(2);
+/// How long a search waits on the daemon before running on its own, long enough for the daemon
+/// to be done with a connection that stalled and the search itself
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A search handed over to `manix daemon`, one JSON line over its socket
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Request {
+    /// The command line of the search, the name of the program first
+    pub args: Vec<String>,
+    /// Whether the output can be colored, which the daemon can't tell on its own
+    pub color: bool,
+    /// The terminal the output goes to, if it goes to one
+    pub terminal: Option<Terminal>,
+    /// What chooses the sources of the search, the daemon declining the ones it didn't load
+    pub loading: Loading,
+}
+
+/// What the sources loaded depend on besides the caches, from the search and from the daemon
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Loading {
+    /// The arguments choosing the sources, with the paths made absolute
+    pub sources: Vec<String>,
+    /// The environment variables that change where the sources come from, None for unset ones
+    pub env: Vec<(String, Option<String>)>,
+}
+
+/// The terminal of a search, which the output is laid out for
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Terminal {
+    /// Rows and columns, when they can be told
+    pub size: Option<(usize, usize)>,
+    /// Whether stdin is a terminal too, the search asking which result to show when several
+    /// match, which only the search itself can do
+    pub interactive: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Response {
+    /// The search is one manix has to run on its own, with other sources or interactively
+    Declined,
+    /// What the search would have written and exited with
+    Done {
+        status: u8,
+        stdout: String,
+        error: Option<String>,
+    },
+}
+
+/// Hands the request over to the daemon listening on the socket, failing when none is or when it
+/// doesn't answer in time
+pub fn send(socket: &Path, request: &Request) -> io::Result<Response> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.set_read_timeout(Some(RESPONSE_TIMEOUT))?;
+    stream.set_write_timeout(Some(RESPONSE_TIMEOUT))?;
+    let mut line = serde_json::to_vec(request)?;
+    line.push(b'\n');
+    stream.write_all(&line)?;
+    read_line(&mut BufReader::new(stream))
+}
+
+/// Answers the requests of every connection one after the other, until accepting one fails. A
+/// connection that stalls is given up on after a while, so that it doesn't hold up the others
+pub fn serve(
+    listener: &UnixListener,
+    mut handle: impl FnMut(Request) -> Response,
+) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        stream.set_read_timeout(Some(CONNECTION_TIMEOUT))?;
+        stream.set_write_timeout(Some(CONNECTION_TIMEOUT))?;
+        let request = match read_line(&mut BufReader::new(&stream)) {
+            Ok(request) => request,
+            // a connection only checking whether the daemon runs
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => continue,
+            Err(e) => {
+                eprintln!("Failed to read a request: {}", e);
+                continue;
+            }
+        };
+        let mut line = serde_json::to_vec(&handle(request))?;
+        line.push(b'\n');
+        if let Err(e) = (&stream).write_all(&line) {
+            eprintln!("Failed to answer a request: {}", e);
+        }
+    }
+    Ok(())
+}
+
+fn read_line<T: for<'de> Deserialize<'de>>(reader: &mut impl BufRead) -> io::Result<T> {
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    Ok(serde_json::from_str(&line)?)
+}
+
+#[test]
+fn test_daemon() {
+    let socket = std::env::temp_dir().join(format!("manix-daemon-{}.sock", std::process::id()));
+    let _ = std::fs::remove_file(&socket);
+    let listener = UnixListener::bind(&socket).unwrap();
+    let loading = Loading {
+        sources: vec!["--source".to_string(), "nixpkgs-doc".to_string()],
+        env: vec![("NIX_PATH".to_string(), None)],
+    };
+    let loaded = loading.clone();
+    let server = std::thread::spawn(move || {
+        let _ = serve(&listener, |request| {
+            if request.color || request.loading != loaded {
+                return Response::Declined;
+            }
+            Response::Done {
+                status: 0,
+                stdout: request.args.join(" "),
+                error: None,
+            }
+        });
+    });
+
+    let request = |args: &[&str], color| Request {
+        args: args.iter().map(|arg| arg.to_string()).collect(),
+        color,
+        terminal: None,
+        loading: loading.clone(),
+    };
+    assert_eq!(
+        send(&socket, &request(&["manix", "mkIf"], false)).unwrap(),
+        Response::Done {
+            status: 0,
+            stdout: "manix mkIf".to_string(),
+            error: None,
+        }
+    );
+    assert_eq!(
+        send(&socket, &request(&["manix", "mkIf"], true)).unwrap(),
+        Response::Declined
+    );
+    let mut other_nix_path = request(&["manix", "mkIf"], false);
+    other_nix_path.loading.env[0].1 = Some("nixpkgs=/other".to_string());
+    assert_eq!(send(&socket, &other_nix_path).unwrap(), Response::Declined);
+    // a connection that never sends its request doesn't hold up the next one
+    let stalled = UnixStream::connect(&socket).unwrap();
+    assert!(send(&socket, &request(&["manix", "mkIf"], false)).is_ok());
+    drop(stalled);
+    std::fs::remove_file(&socket).unwrap();
+    drop(server);
+    assert!(send(&socket, &request(&["manix"], false)).is_err());
+}
@@ -0,0 +1,290 @@
+use crate::{
+    comments_docsource::find_nix_path_entry,
+    nixpkgs,
+    options_docsource::{
+        check_expression,
+        run_nix,
+    },
+};
+use rayon::prelude::*;
+use std::{
+    path::Path,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    /// Only matters to some, like home-manager missing
+    Warning,
+    Error,
+}
+
+/// Something `manix doctor` looked at, with how to fix it when it isn't right
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub status: Status,
+    pub what: String,
+    pub detail: String,
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub fn ok(what: &str, detail: impl Into<String>) -> Self {
+        Self {
+            status: Status::Ok,
+            what: what.to_string(),
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn warning(what: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Warning,
+            fix: Some(fix.into()),
+            ..Self::ok(what, detail)
+        }
+    }
+
+    pub fn error(what: &str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            status: Status::Error,
+            ..Self::warning(what, detail, fix)
+        }
+    }
+}
+
+/// The bundled expressions, whether a derivation is what they give, what they're for and whether
+/// a failure stops manix from working at all
+const EXPRESSIONS: &[(&str, &str, bool, Status)] = &[
+    (
+        "nixos-options.nix",
+        include_str!("nix/nixos-options.nix"),
+        true,
+        Status::Error,
+    ),
+    (
+        "hm-options.nix",
+        include_str!("nix/hm-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "darwin-options.nix",
+        include_str!("nix/darwin-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "nur.nix",
+        include_str!("nix/nur.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "devenv-options.nix",
+        include_str!("nix/devenv-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "flake-parts-options.nix",
+        include_str!("nix/flake-parts-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "nixvim-options.nix",
+        include_str!("nix/nixvim-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "treefmt-nix-options.nix",
+        include_str!("nix/treefmt-nix-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "pre-commit-hooks-options.nix",
+        include_str!("nix/pre-commit-hooks-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "colmena-options.nix",
+        include_str!("nix/colmena-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "deploy-rs-options.nix",
+        include_str!("nix/deploy-rs-options.nix"),
+        true,
+        Status::Warning,
+    ),
+    (
+        "licenses.nix",
+        include_str!("nix/licenses.nix"),
+        false,
+        Status::Error,
+    ),
+    (
+        "maintainers.nix",
+        include_str!("nix/maintainers.nix"),
+        false,
+        Status::Error,
+    ),
+    (
+        "rfcs.nix",
+        include_str!("nix/rfcs.nix"),
+        false,
+        Status::Warning,
+    ),
+];
+
+/// Whether the nix commands manix runs are there
+pub fn check_nix() -> Vec<Check> {
+    ["nix", "nix-instantiate", "nix-build"]
+        .iter()
+        .map(
+            |command| match run_nix(Command::new(command).arg("--version")) {
+                Ok(output) if output.status.success() => Check::ok(
+                    command,
+                    String::from_utf8_lossy(&output.stdout).trim().to_string(),
+                ),
+                _ => Check::error(
+                    command,
+                    "isn't on the PATH",
+                    "Install nix, see https://nixos.org/download",
+                ),
+            },
+        )
+        .collect()
+}
+
+/// Whether `<nixpkgs>` and `<home-manager>` resolve, from the NIX_PATH or the flake registry
+pub fn check_nix_path() -> Vec<Check> {
+    let registry = run_nix(
+        Command::new("nix")
+            .arg("--extra-experimental-features")
+            .arg("nix-command flakes")
+            .arg("registry")
+            .arg("list"),
+    )
+    .map(|output| String::from_utf8_lossy(&output.stdout).to_string())
+    .unwrap_or_default();
+    let in_registry = |name: &str| {
+        registry
+            .lines()
+            .any(|line| line.split_whitespace().nth(1) == Some(&format!("flake:{}", name)))
+    };
+
+    let mut checks = vec![Check::ok(
+        "NIX_PATH",
+        nixpkgs::nix_path().unwrap_or_else(|| "unset".to_string()),
+    )];
+    for (name, status, fix) in [
+        (
+            "nixpkgs",
+            Status::Error,
+            "Add a channel with `nix-channel --add https://nixos.org/channels/nixos-unstable \
+             nixpkgs && nix-channel --update`, or set `nixpkgs` in the config",
+        ),
+        (
+            "home-manager",
+            Status::Warning,
+            "Needed for the Home Manager options, add the home-manager channel or set \
+             `manual.json.enable` in your home configuration",
+        ),
+    ] {
+        let what = format!("<{}>", name);
+        checks.push(match find_nix_path_entry(name) {
+            Some(path) => Check::ok(&what, path.display().to_string()),
+            None if in_registry(name) => Check::ok(&what, "from the flake registry"),
+            None => Check {
+                status,
+                what,
+                detail: "is neither in the NIX_PATH nor in the flake registry".to_string(),
+                fix: Some(fix.to_string()),
+            },
+        });
+    }
+    checks
+}
+
+/// Whether the caches can be written where they go
+pub fn check_cache_dir(dir: &Path) -> Check {
+    let probe = dir.join(format!(".doctor.{}", std::process::id()));
+    let written = std::fs::create_dir_all(dir).and_then(|_| std::fs::write(&probe, b""));
+    let _ = std::fs::remove_file(&probe);
+    match written {
+        Ok(()) => Check::ok("cache directory", dir.display().to_string()),
+        Err(e) => Check::error(
+            "cache directory",
+            format!("{} can't be written: {}", dir.display(), e),
+            "Fix the permissions of the directory, or point XDG_CACHE_HOME somewhere writable",
+        ),
+    }
+}
+
+/// Whether the bundled expressions evaluate, without building anything. Those taking arguments,
+/// the flake ones and the home configuration, are left out
+pub fn check_expressions() -> Vec<Check> {
+    EXPRESSIONS
+        .par_iter()
+        .map(
+            |(name, expr, derivation, status)| match check_expression(expr, *derivation) {
+                Ok(()) => Check::ok(name, "evaluates"),
+                Err(e) => Check {
+                    status: *status,
+                    what: name.to_string(),
+                    detail: first_error(&e.to_string()),
+                    fix: Some(
+                        "Check that what it looks up in the NIX_PATH is there, the checks above \
+                         tell about <nixpkgs> and <home-manager>"
+                            .to_string(),
+                    ),
+                },
+            },
+        )
+        .collect()
+}
+
+/// The line of the output of nix that tells what went wrong, the rest being the trace
+fn first_error(output: &str) -> String {
+    let mut lines = output
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty());
+    lines
+        .clone()
+        .find(|line| line.starts_with("error:"))
+        .or_else(|| lines.next_back())
+        .unwrap_or("failed")
+        .to_string()
+}
+
+#[test]
+fn test_check_cache_dir() {
+    let dir = std::env::temp_dir().join(format!("manix-doctor-{}", std::process::id()));
+    assert_eq!(check_cache_dir(&dir).status, Status::Ok);
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let file = std::env::temp_dir().join(format!("manix-doctor-{}.txt", std::process::id()));
+    std::fs::write(&file, b"").unwrap();
+    assert_eq!(check_cache_dir(&file.join("manix")).status, Status::Error);
+    std::fs::remove_file(&file).unwrap();
+}
+
+#[test]
+fn test_first_error() {
+    assert_eq!(
+        first_error("warning: foo\nerror: file 'nixpkgs' was not found\n\n  at «string»:2:9\n"),
+        "error: file 'nixpkgs' was not found"
+    );
+    assert_eq!(first_error("killed\n"), "killed");
+    assert_eq!(first_error(""), "failed");
+}
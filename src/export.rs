@@ -0,0 +1,524 @@
+use crate::{
+    markup,
+    DocEntry,
+    Errors,
+};
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    path::Path,
+    process::{
+        Command,
+        Stdio,
+    },
+};
+
+/// Escapes text for roff, where a line starting with a dot or a quote would be a request
+fn roff_escape(line: &str) -> String {
+    let line = line.replace('\\', "\\e").replace('-', "\\-");
+    if line.starts_with(['.', '\'']) {
+        format!("\\&{}", line)
+    } else {
+        line
+    }
+}
+
+fn roff_verbatim(text: &str, output: &mut String) {
+    output.push_str(".RS 4\n.nf\n");
+    for line in text.lines() {
+        output.push_str(&format!("{}\n", roff_escape(line)));
+    }
+    output.push_str(".fi\n.RE\n");
+}
+
+/// The options among the entries as a man page laid out like configuration.nix(5), sorted by
+/// name. Descriptions go through [markup::render], so colors should be turned off beforehand
+pub fn man_page(entries: &[DocEntry]) -> String {
+    let mut options = entries
+        .iter()
+        .filter_map(|entry| match entry {
+            DocEntry::OptionDoc(typ, option) => Some((typ.source_name(), option)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    options.sort_by_cached_key(|(_, option)| option.name());
+    let mut sources = options
+        .iter()
+        .map(|(source, _)| *source)
+        .collect::<Vec<_>>();
+    sources.sort_unstable();
+    sources.dedup();
+
+    let mut output = format!(
+        ".TH \"MANIX\" \"5\" \"\" \"manix\" \"Options\"\n.SH \"NAME\"\nmanix \\- {}\n.SH \"OPTIONS\"\n",
+        roff_escape(&sources.join(", "))
+    );
+    for (_, option) in options {
+        output.push_str(&format!(
+            ".PP\n\\fB{}\\fR\n.RS 4\n",
+            roff_escape(&option.name())
+        ));
+        for paragraph in markup::render(&option.description).split("\n\n") {
+            // code blocks come out of the markup indented
+            if paragraph.lines().all(|line| line.starts_with("  ")) {
+                roff_verbatim(paragraph, &mut output);
+            } else {
+                for line in paragraph.lines() {
+                    output.push_str(&format!("{}\n", roff_escape(line.trim())));
+                }
+            }
+            output.push_str(".sp\n");
+        }
+        output.push_str(&format!(
+            "\\fIType:\\fR\n{}\n",
+            roff_escape(&option.option_type)
+        ));
+        if !option.enum_values.is_empty() {
+            output.push_str(".sp\n\\fIAllowed values:\\fR\n");
+            roff_verbatim(&option.enum_values.join("\n"), &mut output);
+        }
+        for (label, value) in [("Default", &option.default), ("Example", &option.example)] {
+            if let Some(value) = value {
+                output.push_str(&format!(".sp\n\\fI{}:\\fR\n", label));
+                roff_verbatim(value, &mut output);
+            }
+        }
+        if !option.declarations.is_empty() {
+            output.push_str(".sp\n\\fIDeclared by:\\fR\n");
+            roff_verbatim(&option.declarations.join("\n"), &mut output);
+        }
+        output.push_str(".RE\n");
+    }
+    output
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Id of the entry within its page, with the characters that aren't safe in URLs replaced
+fn anchor(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// A page of the entries of a source, `nixos-options.html` for NixOS Options
+struct Page<'a> {
+    title: &'a str,
+    file: String,
+    entries: Vec<&'a DocEntry>,
+}
+
+/// Groups the entries by their source, the key-only ones having nothing to put on a page
+fn pages(entries: &[DocEntry]) -> Vec<Page<'_>> {
+    let mut by_source = BTreeMap::<&str, Vec<&DocEntry>>::new();
+    for entry in entries {
+        if !matches!(entry, DocEntry::NixpkgsTreeDoc(_)) {
+            by_source.entry(entry.source()).or_default().push(entry);
+        }
+    }
+    by_source
+        .into_iter()
+        .map(|(title, mut entries)| {
+            entries.sort_by_cached_key(|entry| entry.name());
+            let file = title
+                .to_ascii_lowercase()
+                .split(|c: char| !c.is_ascii_alphanumeric())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join("-");
+            Page {
+                title,
+                file: format!("{}.html", file),
+                entries,
+            }
+        })
+        .collect()
+}
+
+fn entry_html(entry: &DocEntry) -> String {
+    let name = entry.name();
+    let mut output = format!(
+        "<section id=\"{}\">\n<h2><code>{}</code></h2>\n",
+        anchor(&name),
+        html_escape(&name)
+    );
+    let text = entry.text();
+    for paragraph in text.trim().split("\n\n").filter(|p| !p.trim().is_empty()) {
+        output.push_str(&format!("<p>{}</p>\n", html_escape(paragraph.trim())));
+    }
+    if let DocEntry::OptionDoc(_, option) = entry {
+        output.push_str(&format!(
+            "<dl>\n<dt>Type</dt><dd><code>{}</code></dd>\n",
+            html_escape(&option.option_type)
+        ));
+        for (label, value) in [("Default", &option.default), ("Example", &option.example)] {
+            if let Some(value) = value {
+                output.push_str(&format!(
+                    "<dt>{}</dt><dd><pre>{}</pre></dd>\n",
+                    label,
+                    html_escape(value)
+                ));
+            }
+        }
+        for declaration in &option.declarations {
+            output.push_str(&format!(
+                "<dt>Declared in</dt><dd><code>{}</code></dd>\n",
+                html_escape(declaration)
+            ));
+        }
+        output.push_str("</dl>\n");
+    }
+    if let Some(url) = entry.url() {
+        output.push_str(&format!(
+            "<p><a href=\"{0}\">{0}</a></p>\n",
+            html_escape(&url)
+        ));
+    }
+    output.push_str("</section>\n");
+    output
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>
+<html>
+<head>
+<meta charset=\"utf-8\">
+<title>{}</title>
+<style>p {{ white-space: pre-wrap; }} dt {{ font-weight: bold; }}</style>
+</head>
+<body>
+{}</body>
+</html>
+",
+        html_escape(title),
+        body
+    )
+}
+
+fn page_html(page: &Page) -> String {
+    let mut body = format!("<h1>{}</h1>\n", html_escape(page.title));
+    for entry in &page.entries {
+        body.push_str(&entry_html(entry));
+    }
+    html_document(page.title, &body)
+}
+
+/// Entry types of Dash, which give entries their icon and section in the sidebar
+fn dash_type(entry: &DocEntry) -> &'static str {
+    match entry {
+        DocEntry::OptionDoc(_, _) | DocEntry::OptionRenameDoc(_) => "Option",
+        DocEntry::BuiltinDoc(_) => "Builtin",
+        DocEntry::CommentDoc(_) | DocEntry::XmlFuncDoc(_) | DocEntry::LibFuncDoc(_) => "Function",
+        DocEntry::PackageDoc(_) | DocEntry::NurPackageDoc(_) => "Package",
+        DocEntry::CliDoc(_) => "Command",
+        DocEntry::NixConfDoc(_) => "Setting",
+        DocEntry::WikiDoc(_)
+        | DocEntry::RfcDoc(_)
+        | DocEntry::ChangelogDoc(_)
+        | DocEntry::ManualDoc(_, _)
+        | DocEntry::LanguageDoc(_) => "Guide",
+        DocEntry::MaintainerDoc(_) | DocEntry::LicenseDoc(_) | DocEntry::NixpkgsTreeDoc(_) => {
+            "Entry"
+        }
+    }
+}
+
+fn sql_string(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Statements filling the `searchIndex` table Dash and Zeal look entries up in
+fn docset_index(pages: &[Page]) -> String {
+    let mut sql = String::from(
+        "BEGIN;
+CREATE TABLE searchIndex(id INTEGER PRIMARY KEY, name TEXT, type TEXT, path TEXT);
+CREATE UNIQUE INDEX anchor ON searchIndex (name, type, path);
+",
+    );
+    for page in pages {
+        for entry in &page.entries {
+            let name = entry.name();
+            sql.push_str(&format!(
+                "INSERT OR IGNORE INTO searchIndex(name, type, path) VALUES ({}, {}, {});\n",
+                sql_string(&name),
+                sql_string(dash_type(entry)),
+                sql_string(&format!("{}#{}", page.file, anchor(&name)))
+            ));
+        }
+    }
+    sql.push_str("COMMIT;\n");
+    sql
+}
+
+fn write_file(path: &Path, content: &str) -> Result<(), Errors> {
+    std::fs::write(path, content).map_err(|err| Errors::FileIo {
+        filename: path.display().to_string(),
+        err,
+    })
+}
+
+fn create_dir(path: &Path) -> Result<(), Errors> {
+    std::fs::create_dir_all(path).map_err(|err| Errors::FileIo {
+        filename: path.display().to_string(),
+        err,
+    })
+}
+
+/// Writes the page of each source to `dir`
+fn write_pages<'a>(entries: &'a [DocEntry], dir: &Path) -> Result<Vec<Page<'a>>, Errors> {
+    let pages = pages(entries);
+    for page in &pages {
+        write_file(&dir.join(&page.file), &page_html(page))?;
+    }
+    Ok(pages)
+}
+
+fn sources_list(pages: &[Page]) -> String {
+    let mut list = String::from("<ul>\n");
+    for page in pages {
+        list.push_str(&format!(
+            "<li><a href=\"{}\">{}</a> ({})</li>\n",
+            page.file,
+            html_escape(page.title),
+            page.entries.len()
+        ));
+    }
+    list.push_str("</ul>\n");
+    list
+}
+
+/// Writes a Dash/Zeal docset of the entries to `dir`, with a page per source. Its index is an
+/// SQLite database, which is built by handing the statements to `sqlite3`
+pub fn write_docset(entries: &[DocEntry], dir: &Path) -> Result<(), Errors> {
+    let contents = dir.join("Contents");
+    let documents = contents.join("Resources").join("Documents");
+    create_dir(&documents)?;
+    write_file(
+        &contents.join("Info.plist"),
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>CFBundleIdentifier</key>
+	<string>manix</string>
+	<key>CFBundleName</key>
+	<string>Nix</string>
+	<key>DocSetPlatformFamily</key>
+	<string>nix</string>
+	<key>isDashDocset</key>
+	<true/>
+	<key>dashIndexFilePath</key>
+	<string>index.html</string>
+</dict>
+</plist>
+"#,
+    )?;
+
+    let pages = write_pages(entries, &documents)?;
+    let index = format!("<h1>Nix</h1>\n{}", sources_list(&pages));
+    write_file(&documents.join("index.html"), &html_document("Nix", &index))?;
+
+    let database = contents.join("Resources").join("docSet.dsidx");
+    let sqlite_error = |err| Errors::FileIo {
+        filename: database.display().to_string(),
+        err,
+    };
+    if database.exists() {
+        std::fs::remove_file(&database).map_err(sqlite_error)?;
+    }
+    let mut sqlite = Command::new("sqlite3")
+        .arg(&database)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(sqlite_error)?;
+    if let Some(mut stdin) = sqlite.stdin.take() {
+        stdin
+            .write_all(docset_index(&pages).as_bytes())
+            .map_err(sqlite_error)?;
+    }
+    let status = sqlite.wait().map_err(sqlite_error)?;
+    if !status.success() {
+        return Err(sqlite_error(std::io::Error::other(format!(
+            "sqlite3 exited with {}",
+            status
+        ))));
+    }
+    Ok(())
+}
+
+/// Looks the keys typed into the search box of the index up in `ENTRIES`, which `search-index.js`
+/// defines rather than being fetched as JSON so that the site also works from `file://` URLs
+const SEARCH_SCRIPT: &str = r#"const input = document.getElementById("search");
+const results = document.getElementById("results");
+input.addEventListener("input", () => {
+  const words = input.value.toLowerCase().split(/\s+/).filter((word) => word);
+  results.replaceChildren();
+  if (words.length === 0) {
+    return;
+  }
+  const found = ENTRIES.filter(([name]) => words.every((word) => name.toLowerCase().includes(word)));
+  for (const [name, source, path] of found.slice(0, 100)) {
+    const item = document.createElement("li");
+    const link = document.createElement("a");
+    link.href = path;
+    link.textContent = name;
+    item.append(link, " (" + source + ")");
+    results.append(item);
+  }
+});
+"#;
+
+/// Writes a static site of the entries to `dir`: an index with a search box over every key and
+/// the page of each source
+pub fn write_html_site(entries: &[DocEntry], dir: &Path) -> Result<(), Errors> {
+    create_dir(dir)?;
+    let pages = write_pages(entries, dir)?;
+    let search_index = pages
+        .iter()
+        .flat_map(|page| {
+            page.entries.iter().map(|entry| {
+                let name = entry.name();
+                let path = format!("{}#{}", page.file, anchor(&name));
+                (name, page.title, path)
+            })
+        })
+        .collect::<Vec<_>>();
+    write_file(
+        &dir.join("search-index.js"),
+        &format!(
+            "const ENTRIES = {};\n",
+            serde_json::to_string(&search_index)?
+        ),
+    )?;
+    write_file(&dir.join("search.js"), SEARCH_SCRIPT)?;
+    let index = format!(
+        "<h1>Nix</h1>
+<input id=\"search\" type=\"search\" placeholder=\"Search\" autofocus>
+<ul id=\"results\"></ul>
+<h2>Sources</h2>
+{}<script src=\"search-index.js\"></script>
+<script src=\"search.js\"></script>
+",
+        sources_list(&pages)
+    );
+    write_file(&dir.join("index.html"), &html_document("Nix", &index))
+}
+
+#[test]
+fn test_man_page() {
+    use crate::options_docsource::{
+        OptionDocumentation,
+        OptionsDatabaseType,
+    };
+
+    colored::control::set_override(false);
+    let option = OptionDocumentation {
+        description: "Whether to enable `nginx`.\n\n.dots are escaped".to_string(),
+        location: vec![
+            "services".to_string(),
+            "nginx".to_string(),
+            "enable".to_string(),
+        ],
+        option_type: "boolean".to_string(),
+        default: Some("false".to_string()),
+        declarations: vec!["nixos/modules/services/web-servers/nginx/default.nix".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(
+        man_page(&[DocEntry::OptionDoc(OptionsDatabaseType::NixOS, option)]),
+        r#".TH "MANIX" "5" "" "manix" "Options"
+.SH "NAME"
+manix \- NixOS Options
+.SH "OPTIONS"
+.PP
+\fBservices.nginx.enable\fR
+.RS 4
+Whether to enable nginx.
+.sp
+\&.dots are escaped
+.sp
+\fIType:\fR
+boolean
+.sp
+\fIDefault:\fR
+.RS 4
+.nf
+false
+.fi
+.RE
+.sp
+\fIDeclared by:\fR
+.RS 4
+.nf
+nixos/modules/services/web\-servers/nginx/default.nix
+.fi
+.RE
+.RE
+"#
+    );
+}
+
+#[test]
+fn test_docset_index() {
+    use crate::options_docsource::{
+        OptionDocumentation,
+        OptionsDatabaseType,
+    };
+
+    let entries = [DocEntry::NixpkgsTreeDoc("pkgs.hello".to_string())];
+    assert!(pages(&entries).is_empty());
+    let option = OptionDocumentation {
+        location: vec![
+            "users".to_string(),
+            "users".to_string(),
+            "<name>".to_string(),
+        ],
+        ..Default::default()
+    };
+    let entries = [DocEntry::OptionDoc(OptionsDatabaseType::NixOS, option)];
+    let pages = pages(&entries);
+    assert_eq!(pages[0].file, "nixos-options.html");
+    assert!(docset_index(&pages).contains(
+        "VALUES ('users.users.<name>', 'Option', 'nixos-options.html#users.users._name_');"
+    ));
+}
+
+#[test]
+fn test_write_html_site() {
+    use crate::options_docsource::{
+        OptionDocumentation,
+        OptionsDatabaseType,
+    };
+
+    let option = OptionDocumentation {
+        location: vec!["services".to_string(), "nginx".to_string()],
+        description: "Run <nginx>.".to_string(),
+        ..Default::default()
+    };
+    let dir = std::env::temp_dir().join(format!("manix-html-{}", std::process::id()));
+    write_html_site(
+        &[DocEntry::OptionDoc(OptionsDatabaseType::NixOS, option)],
+        &dir,
+    )
+    .unwrap();
+    let read = |file| std::fs::read_to_string(dir.join(file)).unwrap();
+    assert_eq!(
+        read("search-index.js"),
+        "const ENTRIES = [[\"services.nginx\",\"NixOS Options\",\"nixos-options.html#services.nginx\"]];\n"
+    );
+    assert!(read("nixos-options.html").contains("<p>Run &lt;nginx&gt;.</p>"));
+    assert!(read("index.html").contains("<a href=\"nixos-options.html\">NixOS Options</a> (1)"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
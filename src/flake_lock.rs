@@ -0,0 +1,190 @@
+use crate::{
+    nixpkgs::nix_path_location,
+    Errors,
+};
+use serde::Deserialize;
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+#[derive(Deserialize)]
+struct FlakeLock {
+    nodes: HashMap<String, Node>,
+    root: String,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(default)]
+    inputs: HashMap<String, Input>,
+    locked: Option<Locked>,
+}
+
+/// Either the name of a node or the path of inputs from the root it follows
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Input {
+    Node(String),
+    Follows(Vec<String>),
+}
+
+#[derive(Deserialize)]
+struct Locked {
+    #[serde(rename = "type")]
+    typ: String,
+    owner: Option<String>,
+    repo: Option<String>,
+    rev: Option<String>,
+    url: Option<String>,
+    path: Option<String>,
+}
+
+/// The nixpkgs and home-manager a flake.lock pins, as the NIX_PATH takes them
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LockedInputs {
+    pub nixpkgs: Option<String>,
+    pub home_manager: Option<String>,
+}
+
+impl LockedInputs {
+    pub fn load(path: &Path) -> Result<Self, Errors> {
+        let content = std::fs::read(path).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })?;
+        let lock: FlakeLock = serde_json::from_slice(&content)?;
+        let input = |name: &str| {
+            let root = lock.nodes.get(&lock.root)?;
+            let node = resolve(&lock, root.inputs.get(name)?, 0)?;
+            location(node.locked.as_ref()?)
+        };
+        Ok(Self {
+            nixpkgs: input("nixpkgs"),
+            home_manager: input("home-manager"),
+        })
+    }
+
+    /// Names the caches built from these inputs, the same for a project as long as its lock
+    /// doesn't change them
+    pub fn cache_key(&self) -> String {
+        let inputs = format!("{:?} {:?}", self.nixpkgs, self.home_manager);
+        format!("{:08x}", crc32fast::hash(inputs.as_bytes()))
+    }
+}
+
+/// The flake.lock of the directory, or of the closest one above it that has one
+pub fn find(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|dir| dir.join("flake.lock"))
+        .find(|path| path.is_file())
+}
+
+/// The node an input stands for. Follows paths may go through other follows, which nix doesn't
+/// let loop but a lock written by hand could
+fn resolve<'a>(lock: &'a FlakeLock, input: &Input, depth: usize) -> Option<&'a Node> {
+    match input {
+        Input::Node(name) => lock.nodes.get(name),
+        Input::Follows(_) if depth > 16 => None,
+        Input::Follows(path) => {
+            let mut node = lock.nodes.get(&lock.root)?;
+            for name in path {
+                node = resolve(lock, node.inputs.get(name)?, depth + 1)?;
+            }
+            Some(node)
+        }
+    }
+}
+
+/// What the NIX_PATH takes for a locked input, flake refs being left to nix versions that have
+/// them for all but GitHub and plain tarballs and paths
+fn location(locked: &Locked) -> Option<String> {
+    match locked.typ.as_str() {
+        "github" | "gitlab" | "sourcehut" => {
+            let reference = format!(
+                "{}:{}/{}/{}",
+                locked.typ,
+                locked.owner.as_ref()?,
+                locked.repo.as_ref()?,
+                locked.rev.as_ref()?
+            );
+            Some(nix_path_location(&reference))
+        }
+        "tarball" | "file" => locked.url.clone(),
+        "path" => locked.path.clone(),
+        "git" => Some(format!(
+            "flake:git+{}?rev={}",
+            locked.url.as_ref()?,
+            locked.rev.as_ref()?
+        )),
+        _ => None,
+    }
+}
+
+#[test]
+fn test_locked_inputs() {
+    let dir = std::env::temp_dir().join(format!("manix-flake-lock-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("nested")).unwrap();
+    let rev = "0c19708cf035f50d28eb4b2b8e7a79d4dc52f6bb";
+    let lock = format!(
+        r#"{{
+            "nodes": {{
+                "home-manager": {{
+                    "inputs": {{ "nixpkgs": ["nixpkgs"] }},
+                    "locked": {{ "type": "git", "url": "https://example.org/hm", "rev": "abc" }}
+                }},
+                "nixpkgs_2": {{
+                    "locked": {{ "type": "github", "owner": "NixOS", "repo": "nixpkgs", "rev": "{}" }}
+                }},
+                "root": {{ "inputs": {{ "home-manager": "home-manager", "nixpkgs": "nixpkgs_2" }} }}
+            }},
+            "root": "root",
+            "version": 7
+        }}"#,
+        rev
+    );
+    std::fs::write(dir.join("flake.lock"), lock).unwrap();
+
+    let path = find(&dir.join("nested")).unwrap();
+    assert_eq!(path, dir.join("flake.lock"));
+    let inputs = LockedInputs::load(&path).unwrap();
+    assert_eq!(
+        inputs,
+        LockedInputs {
+            nixpkgs: Some(format!(
+                "https://github.com/NixOS/nixpkgs/archive/{}.tar.gz",
+                rev
+            )),
+            home_manager: Some("flake:git+https://example.org/hm?rev=abc".to_string()),
+        }
+    );
+    assert_ne!(inputs.cache_key(), LockedInputs::default().cache_key());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_follows() {
+    let lock: FlakeLock = serde_json::from_str(
+        r#"{
+            "nodes": {
+                "nixpkgs": { "locked": { "type": "path", "path": "/src/nixpkgs" } },
+                "other": { "inputs": { "nixpkgs": "nixpkgs" } },
+                "root": {
+                    "inputs": { "other": "other", "nixpkgs": ["other", "nixpkgs"], "loop": ["loop"] }
+                }
+            },
+            "root": "root"
+        }"#,
+    )
+    .unwrap();
+    let root = &lock.nodes["root"];
+    let nixpkgs = resolve(&lock, &root.inputs["nixpkgs"], 0).unwrap();
+    assert_eq!(
+        location(nixpkgs.locked.as_ref().unwrap()).as_deref(),
+        Some("/src/nixpkgs")
+    );
+    assert!(resolve(&lock, &root.inputs["loop"], 0).is_none());
+}
@@ -0,0 +1,157 @@
+use crate::Lowercase;
+use rayon::prelude::*;
+
+const MAX_SUGGESTIONS: usize = 3;
+const MATCH: i64 = 16;
+const CONSECUTIVE_BONUS: i64 = 8;
+const WORD_START_BONUS: i64 = 10;
+const TYPO_PENALTY: i64 = 20;
+
+/// Scores how well the candidate matches the query, higher is better. Every word of the query is
+/// matched on its own, so their order doesn't matter, either as a subsequence of the candidate or
+/// as a part of it with a typo or two
+pub fn score(candidate: &str, query: &Lowercase) -> Option<i64> {
+    let candidate = candidate.to_ascii_lowercase();
+    let words = query
+        .0
+        .split(|b| b.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return None;
+    }
+
+    let mut total = 0;
+    for word in words {
+        total += subsequence_score(candidate.as_bytes(), word)
+            .or_else(|| typo_score(&candidate, word))?;
+    }
+    // shorter candidates are closer to what was asked for
+    Some(total - candidate.len() as i64 / 4)
+}
+
+fn is_word_start(candidate: &[u8], i: usize) -> bool {
+    i == 0 || !candidate[i - 1].is_ascii_alphanumeric()
+}
+
+/// Best score of the word as a subsequence among the ones starting at each occurrence of its first
+/// letter
+fn subsequence_score(candidate: &[u8], word: &[u8]) -> Option<i64> {
+    let first = *word.first()?;
+    candidate
+        .iter()
+        .enumerate()
+        .filter(|(_, &b)| b == first)
+        .filter_map(|(start, _)| {
+            let mut score = MATCH;
+            if is_word_start(candidate, start) {
+                score += WORD_START_BONUS;
+            }
+            let mut position = start;
+            for &b in &word[1..] {
+                let next = position + 1 + candidate[position + 1..].iter().position(|&c| c == b)?;
+                score += MATCH;
+                if next == position + 1 {
+                    score += CONSECUTIVE_BONUS;
+                } else {
+                    score -= (next - position - 1) as i64;
+                    if is_word_start(candidate, next) {
+                        score += WORD_START_BONUS;
+                    }
+                }
+                position = next;
+            }
+            Some(score)
+        })
+        .max()
+}
+
+/// Matches the word against the parts of the candidate, allowing one typo per four letters
+fn typo_score(candidate: &str, word: &[u8]) -> Option<i64> {
+    let word = std::str::from_utf8(word).ok()?;
+    let allowed = (word.len() / 4).max(1);
+    candidate
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            // typos in a prefix of the part count as well, `mkderivaton` in `mkderivationwith`
+            let prefix = part.get(..word.len()).unwrap_or(part);
+            edit_distance(prefix, word).min(edit_distance(part, word))
+        })
+        .filter(|&distance| distance <= allowed)
+        .min()
+        .map(|distance| MATCH * word.len() as i64 - TYPO_PENALTY * distance as i64)
+}
+
+/// Keys close to a query that found nothing, either as a whole or by their last segment, so
+/// `mkderivaton` suggests `mkDerivation` and `stdenv.mkDerivation`
+pub fn suggestions<'a>(keys: &[&'a str], query: &str) -> Vec<&'a str> {
+    let query = query.to_ascii_lowercase();
+    let allowed = (query.len() / 3).max(1);
+    let distance = |candidate: &str| {
+        (candidate.len().abs_diff(query.len()) <= allowed)
+            .then(|| edit_distance(&candidate.to_ascii_lowercase(), &query))
+            .filter(|&distance| distance <= allowed)
+    };
+    let mut close = keys
+        .par_iter()
+        .filter_map(|&key| {
+            let last_segment = key.rsplit('.').next().unwrap_or(key);
+            let distance = match (distance(key), distance(last_segment)) {
+                (Some(a), Some(b)) => a.min(b),
+                (a, b) => a.or(b)?,
+            };
+            Some((distance, key.len(), key))
+        })
+        .collect::<Vec<_>>();
+    close.sort_unstable();
+    close.dedup_by_key(|(_, _, key)| *key);
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, _, key)| key)
+        .collect()
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[test]
+fn test_score() {
+    let score = |candidate: &str, query: &str| score(candidate, &Lowercase(query.as_bytes()));
+    assert!(score("lib.mergeAttrs", "mrgattr").is_some());
+    assert!(score("mkDerivation", "mkderivaton").is_some());
+    assert!(score("services.openssh.enable", "enable openssh").is_some());
+    assert_eq!(score("services.openssh.enable", "xyz"), None);
+    // word starts and consecutive letters beat scattered ones
+    assert!(score("mapAttrs", "map") > score("mkAppImage", "map"));
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+}
+
+#[test]
+fn test_suggestions() {
+    let keys = [
+        "mkDerivation",
+        "stdenv.mkDerivation",
+        "mkShell",
+        "lib.mapAttrs",
+    ];
+    assert_eq!(
+        suggestions(&keys, "mkderivaton"),
+        vec!["mkDerivation", "stdenv.mkDerivation"]
+    );
+    assert_eq!(suggestions(&keys, "mapattr"), vec!["lib.mapAttrs"]);
+    assert!(suggestions(&keys, "xyz").is_empty());
+}
@@ -0,0 +1,93 @@
+use crate::MatchOptions;
+
+/// `services.nginx.virtualHosts.*.locations` style patterns over attribute paths. They're matched
+/// segment by segment, `*` and `?` within a segment as in shell globs and a whole `**` segment
+/// standing for any number of segments. Like strict searches, the options below a match match too
+#[derive(Debug, PartialEq, Eq)]
+pub struct GlobQuery {
+    segments: Vec<String>,
+}
+
+impl GlobQuery {
+    pub fn is_glob(query: &str) -> bool {
+        query.contains(['*', '?'])
+    }
+
+    pub fn parse(query: &str) -> Self {
+        Self {
+            segments: query.split('.').map(str::to_string).collect(),
+        }
+    }
+
+    /// Segments are always matched whole, so only the case sensitivity of the options matters
+    pub fn matches(&self, name: &str, options: MatchOptions) -> bool {
+        let fold = |s: &str| {
+            if options.case_sensitive {
+                s.to_string()
+            } else {
+                s.to_ascii_lowercase()
+            }
+        };
+        let segments = self.segments.iter().map(|s| fold(s)).collect::<Vec<_>>();
+        let name = fold(name);
+        matches_prefix(&segments, &name.split('.').collect::<Vec<_>>())
+    }
+}
+
+fn matches_prefix(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => true,
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skipped| matches_prefix(rest, &path[skipped..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((segment, path)) => {
+                matches_segment(first.as_bytes(), segment.as_bytes()) && matches_prefix(rest, path)
+            }
+            None => false,
+        },
+    }
+}
+
+fn matches_segment(pattern: &[u8], segment: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => segment.is_empty(),
+        Some((b'*', rest)) => (0..=segment.len()).any(|i| matches_segment(rest, &segment[i..])),
+        Some((b'?', rest)) => !segment.is_empty() && matches_segment(rest, &segment[1..]),
+        Some((&b, rest)) => segment.first() == Some(&b) && matches_segment(rest, &segment[1..]),
+    }
+}
+
+#[test]
+fn test_glob_query() {
+    assert!(GlobQuery::is_glob("services.*.enable"));
+    assert!(!GlobQuery::is_glob("services.nginx.enable"));
+
+    let matches =
+        |query: &str, name: &str| GlobQuery::parse(query).matches(name, MatchOptions::default());
+    let query = "services.nginx.virtualHosts.*.locations";
+    assert!(matches(
+        query,
+        "services.nginx.virtualHosts.<name>.locations"
+    ));
+    assert!(matches(
+        query,
+        "services.nginx.virtualHosts.<name>.locations.<name>.proxyPass"
+    ));
+    assert!(!matches(
+        query,
+        "services.nginx.virtualHosts.<name>.locationsExtra"
+    ));
+    assert!(!matches(query, "services.nginx.virtualHosts"));
+
+    assert!(matches("services.*ssh*.enable", "services.openssh.enable"));
+    assert!(matches("boot.**.enable", "boot.loader.grub.enable"));
+    assert!(matches("boot.**.enable", "boot.enable"));
+    assert!(!matches("boot.?.enable", "boot.ab.enable"));
+
+    let sensitive = MatchOptions {
+        case_sensitive: true,
+        whole_word: false,
+    };
+    assert!(!GlobQuery::parse("services.*.Enable").matches("services.openssh.enable", sensitive));
+}
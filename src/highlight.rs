@@ -0,0 +1,89 @@
+use regex::Regex;
+use std::{
+    collections::BTreeSet,
+    sync::OnceLock,
+};
+
+const START: &str = "\x1b[7m";
+const END: &str = "\x1b[27m";
+
+fn escape_sequence() -> &'static Regex {
+    static ESCAPE_SEQUENCE: OnceLock<Regex> = OnceLock::new();
+    ESCAPE_SEQUENCE.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*[A-Za-z]").unwrap())
+}
+
+/// Shows what the pattern matches in reverse video. The text may be colored already, the pattern
+/// is matched against what's visible of it and the colors are kept
+pub fn highlight(text: &str, pattern: &Regex) -> String {
+    let mut visible = String::new();
+    // the escape sequences along with where in the visible text they go
+    let mut escapes = Vec::new();
+    let mut last = 0;
+    for escape in escape_sequence().find_iter(text) {
+        visible.push_str(&text[last..escape.start()]);
+        escapes.push((visible.len(), escape.as_str()));
+        last = escape.end();
+    }
+    visible.push_str(&text[last..]);
+
+    let matches = pattern
+        .find_iter(&visible)
+        .filter(|m| !m.is_empty())
+        .map(|m| m.range())
+        .collect::<Vec<_>>();
+    if matches.is_empty() {
+        return text.to_string();
+    }
+    let boundaries = escapes
+        .iter()
+        .map(|(position, _)| *position)
+        .chain(matches.iter().flat_map(|m| [m.start, m.end]))
+        .chain([visible.len()])
+        .collect::<BTreeSet<_>>();
+
+    let mut output = String::new();
+    let mut escapes = escapes.into_iter().peekable();
+    let mut matches = matches.into_iter().peekable();
+    let mut position = 0;
+    let mut in_match = false;
+    for boundary in boundaries {
+        output.push_str(&visible[position..boundary]);
+        position = boundary;
+        if in_match && matches.peek().is_some_and(|m| m.end == boundary) {
+            output.push_str(END);
+            matches.next();
+            in_match = false;
+        }
+        while let Some((_, escape)) = escapes.next_if(|(position, _)| *position == boundary) {
+            output.push_str(escape);
+            // a reset in the middle of a match would end the highlighting too early
+            if in_match {
+                output.push_str(START);
+            }
+        }
+        if !in_match && matches.peek().is_some_and(|m| m.start == boundary) {
+            output.push_str(START);
+            in_match = true;
+        }
+    }
+    output
+}
+
+#[test]
+fn test_highlight() {
+    let pattern = Regex::new("(?i)nginx").unwrap();
+    assert_eq!(
+        highlight("services.Nginx.enable and nginx", &pattern),
+        "services.\x1b[7mNginx\x1b[27m.enable and \x1b[7mnginx\x1b[27m"
+    );
+    assert_eq!(
+        highlight("\x1b[1;34mservices.nginx\x1b[0m", &pattern),
+        "\x1b[1;34mservices.\x1b[7mnginx\x1b[27m\x1b[0m"
+    );
+    // a match across colors
+    assert_eq!(
+        highlight("ng\x1b[0minx", &pattern),
+        "\x1b[7mng\x1b[0m\x1b[7minx\x1b[27m"
+    );
+    assert_eq!(highlight("openssh", &pattern), "openssh");
+}
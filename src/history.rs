@@ -0,0 +1,109 @@
+use crate::Errors;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{
+        SystemTime,
+        UNIX_EPOCH,
+    },
+};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+/// Keys beyond these many are forgotten, the least frecent first
+const MAX_KEYS: usize = 1000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+struct Visit {
+    count: u32,
+    /// Seconds since the epoch
+    last: u64,
+}
+
+/// The keys that were looked at, how often and when last, so that those used a lot and recently
+/// rank higher like directories do in zoxide
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct History {
+    visits: HashMap<String, Visit>,
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+impl History {
+    /// Reads the history, which is empty until something gets recorded
+    pub fn load(path: &Path) -> Result<Self, Errors> {
+        match std::fs::read(path) {
+            Ok(content) => Ok(serde_json::from_slice(&content)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(Errors::FileIo {
+                filename: path.display().to_string(),
+                err,
+            }),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Errors> {
+        let content = serde_json::to_vec(self)?;
+        std::fs::write(path, content).map_err(|err| Errors::FileIo {
+            filename: path.display().to_string(),
+            err,
+        })
+    }
+
+    pub fn record(&mut self, key: &str, now: u64) {
+        let visit = self.visits.entry(key.to_string()).or_insert(Visit {
+            count: 0,
+            last: now,
+        });
+        visit.count += 1;
+        visit.last = now;
+        if self.visits.len() > MAX_KEYS {
+            let least = self
+                .visits
+                .iter()
+                .min_by_key(|(key, _)| self.frecency(key, now))
+                .map(|(key, _)| key.clone());
+            if let Some(least) = least {
+                self.visits.remove(&least);
+            }
+        }
+    }
+
+    /// How often the key was looked at, weighed by how long ago that was last, 0 for keys never
+    /// looked at
+    pub fn frecency(&self, key: &str, now: u64) -> u64 {
+        let Some(visit) = self.visits.get(key) else {
+            return 0;
+        };
+        let age = now.saturating_sub(visit.last);
+        let weight = match age {
+            _ if age < HOUR => 16,
+            _ if age < DAY => 8,
+            _ if age < WEEK => 2,
+            _ => 1,
+        };
+        u64::from(visit.count) * weight
+    }
+}
+
+#[test]
+fn test_history() {
+    let mut history = History::default();
+    history.record("services.nginx.enable", 0);
+    history.record("services.nginx.enable", 0);
+    history.record("lib.mkIf", WEEK);
+    assert_eq!(history.frecency("services.nginx.enable", WEEK), 2);
+    assert_eq!(history.frecency("lib.mkIf", WEEK), 16);
+    assert_eq!(history.frecency("lib.mkMerge", WEEK), 0);
+    assert_eq!(history.frecency("services.nginx.enable", HOUR), 16);
+}
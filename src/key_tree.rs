@@ -0,0 +1,91 @@
+use std::collections::BTreeMap;
+
+/// The keys below a prefix arranged by their segments, `services.nginx.enable` being the `enable`
+/// child of `services.nginx`
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct KeyTree {
+    children: BTreeMap<String, KeyTree>,
+}
+
+impl KeyTree {
+    pub fn from_keys<'a>(keys: impl IntoIterator<Item = &'a str>, prefix: &str) -> Self {
+        let prefix = prefix.trim_end_matches('.');
+        let mut tree = Self::default();
+        for key in keys {
+            let rest = if prefix.is_empty() {
+                Some(key)
+            } else {
+                key.strip_prefix(prefix)
+                    .and_then(|rest| rest.strip_prefix('.'))
+            };
+            let Some(rest) = rest else {
+                continue;
+            };
+            let mut node = &mut tree;
+            for segment in rest.split('.').filter(|segment| !segment.is_empty()) {
+                node = node.children.entry(segment.to_string()).or_default();
+            }
+        }
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Draws the tree below the root, down to `depth` levels when given
+    pub fn render(&self, root: &str, depth: Option<usize>) -> String {
+        let mut output = String::new();
+        if !root.is_empty() {
+            output.push_str(&format!("{}\n", root));
+        }
+        self.render_children("", depth, &mut output);
+        output
+    }
+
+    fn render_children(&self, indent: &str, depth: Option<usize>, output: &mut String) {
+        if depth == Some(0) {
+            return;
+        }
+        let count = self.children.len();
+        for (i, (segment, child)) in self.children.iter().enumerate() {
+            let last = i + 1 == count;
+            output.push_str(&format!(
+                "{}{}{}\n",
+                indent,
+                if last { "└── " } else { "├── " },
+                segment
+            ));
+            let indent = format!("{}{}", indent, if last { "    " } else { "│   " });
+            child.render_children(&indent, depth.map(|depth| depth - 1), output);
+        }
+    }
+}
+
+#[test]
+fn test_key_tree() {
+    let keys = [
+        "services.nginx.enable",
+        "services.nginx.virtualHosts.<name>.root",
+        "services.nginx.virtualHosts.<name>.locations",
+        "services.nginx",
+        "services.nginxExtra.enable",
+        "services.caddy.enable",
+    ];
+    let tree = KeyTree::from_keys(keys, "services.nginx");
+    assert_eq!(
+        tree.render("services.nginx", None),
+        "services.nginx
+├── enable
+└── virtualHosts
+    └── <name>
+        ├── locations
+        └── root
+"
+    );
+    assert_eq!(
+        tree.render("services.nginx", Some(1)),
+        "services.nginx\n├── enable\n└── virtualHosts\n"
+    );
+    assert!(KeyTree::from_keys(keys, "programs").is_empty());
+}
@@ -0,0 +1,247 @@
+use crate::{
+    contains_insensitive_ascii,
+    starts_with_insensitive_ascii,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Keyword or operator, an example of its syntax and what it does
+const LANGUAGE_REFERENCE: &[(&str, &str, &str)] = &[
+    (
+        "let",
+        "let x = 1; y = x + 1; in y",
+        "Binds names for use in the expression after `in`. Bindings can refer to each other.",
+    ),
+    (
+        "with",
+        "with pkgs; [ git curl ]",
+        "Brings all attributes of a set into scope for the following expression. Names bound by `let` or function arguments take precedence.",
+    ),
+    (
+        "inherit",
+        "{ inherit x y; }",
+        "Copies names from the enclosing scope into an attribute set or `let` block, `inherit x;` is `x = x;`.",
+    ),
+    (
+        "inherit (set)",
+        "{ inherit (pkgs) git curl; }",
+        "Copies attributes of a set, `inherit (pkgs) git;` is `git = pkgs.git;`.",
+    ),
+    (
+        "rec",
+        "rec { x = 1; y = x + 1; }",
+        "Recursive attribute set, its attributes can refer to each other.",
+    ),
+    (
+        "if",
+        "if cond then a else b",
+        "Conditional expression, the `else` branch is mandatory.",
+    ),
+    (
+        "assert",
+        "assert x > 0; x",
+        "Aborts evaluation when the condition is false, otherwise evaluates to the expression after `;`.",
+    ),
+    (
+        "or",
+        "set.a.b or default",
+        "Default value of an attribute selection, used when an attribute along the path is missing.",
+    ),
+    (
+        "import",
+        "import ./file.nix",
+        "Evaluates a Nix file, a directory imports its `default.nix`.",
+    ),
+    (
+        "//",
+        "a // b",
+        "Updates a set with the attributes of another, shallowly. Attributes of the right-hand side win.",
+    ),
+    (
+        "?",
+        "set ? a.b",
+        "Tests whether a set has an attribute, also used for default values of function arguments: `{ x ? 1 }: x`.",
+    ),
+    (
+        "++",
+        "[ 1 ] ++ [ 2 ]",
+        "Concatenates lists.",
+    ),
+    (
+        "+",
+        "\"a\" + \"b\"",
+        "Adds numbers, concatenates strings and paths.",
+    ),
+    (
+        "->",
+        "a -> b",
+        "Logical implication, `!a || b`.",
+    ),
+    (
+        "==",
+        "a == b",
+        "Deep equality, `!=` is its negation. Functions are never equal.",
+    ),
+    (
+        "&&",
+        "a && b",
+        "Logical and, `||` is logical or and `!` negation.",
+    ),
+    (
+        ".",
+        "set.a.b",
+        "Selects an attribute, the path can contain interpolations: `set.${name}`.",
+    ),
+    (
+        ":",
+        "x: x + 1",
+        "Defines a function of one argument, functions of several arguments are curried: `x: y: x + y`.",
+    ),
+    (
+        "{ }:",
+        "{ a, b ? 2, ... }: a + b",
+        "Function taking an attribute set, `b ? 2` gives a default value and `...` allows extra attributes.",
+    ),
+    (
+        "@",
+        "args@{ a, ... }: args.b",
+        "Binds the whole argument set of a function taking a set to a name.",
+    ),
+    (
+        "${}",
+        "\"hello ${name}\"",
+        "String interpolation, also works in paths and attribute names. Interpolating a path copies it to the store.",
+    ),
+    (
+        "''",
+        "''\n  indented\n  string\n''",
+        "Indented string, the common indentation is stripped. `''$` escapes `$`, `'''` escapes `''` and `''\\n` is a newline.",
+    ),
+    (
+        "\\",
+        "\"a\\nb \\${not interpolated}\"",
+        "Escapes in double quoted strings: `\\n`, `\\r`, `\\t`, `\\\"`, `\\\\` and `\\${`.",
+    ),
+    (
+        "<>",
+        "<nixpkgs>",
+        "Looks a path up in the NIX_PATH, `<nixpkgs/lib>` looks up a file inside of it.",
+    ),
+    (
+        "./",
+        "./file.nix",
+        "Paths are resolved relative to the file they're written in and must contain a `/`.",
+    ),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LanguageDocumentation {
+    name: String,
+    syntax: String,
+    description: String,
+}
+
+impl LanguageDocumentation {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.description
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!(
+            "# {}\n{}\n\nSyntax:\n",
+            self.name.blue().bold(),
+            self.description
+        );
+        for line in self.syntax.lines() {
+            output.push_str(&format!("  {}\n", line.white()));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// Keywords and operators of the Nix language. Compiled in, so it doesn't need a cache
+pub struct LanguageDocSource {
+    entries: Vec<LanguageDocumentation>,
+}
+
+impl Default for LanguageDocSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LanguageDocSource {
+    pub fn new() -> Self {
+        Self {
+            entries: LANGUAGE_REFERENCE
+                .iter()
+                .map(|(name, syntax, description)| LanguageDocumentation {
+                    name: name.to_string(),
+                    syntax: syntax.to_string(),
+                    description: description.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl DocSource for LanguageDocSource {
+    fn all_keys(&self) -> Vec<&str> {
+        self.entries.iter().map(|x| x.name.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| starts_with_insensitive_ascii(entry.name.as_bytes(), query))
+            .map(|entry| DocEntry::LanguageDoc(entry.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        // operators are short, so only keywords are matched anywhere
+        self.entries
+            .iter()
+            .filter(|entry| {
+                starts_with_insensitive_ascii(entry.name.as_bytes(), query)
+                    || (entry.name.chars().all(|c| c.is_ascii_alphabetic())
+                        && contains_insensitive_ascii(entry.name.as_bytes(), query))
+            })
+            .map(|entry| DocEntry::LanguageDoc(entry.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.entries
+            .iter()
+            .map(|entry| DocEntry::LanguageDoc(entry.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        Ok(false)
+    }
+}
+
+#[test]
+fn test_language_search() {
+    let source = LanguageDocSource::new();
+    let names = |entries: Vec<DocEntry>| entries.iter().map(DocEntry::name).collect::<Vec<_>>();
+    assert_eq!(names(source.search_liberal(&Lowercase(b"//"))), vec!["//"]);
+    assert_eq!(
+        names(source.search_liberal(&Lowercase(b"with"))),
+        vec!["with"]
+    );
+    assert_eq!(
+        names(source.search(&Lowercase(b"inherit"))),
+        vec!["inherit", "inherit (set)"]
+    );
+}
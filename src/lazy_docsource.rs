@@ -0,0 +1,128 @@
+use crate::{
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use regex::Regex;
+use std::sync::{
+    Mutex,
+    OnceLock,
+    PoisonError,
+};
+
+type LoadedSource = Box<dyn DocSource + Sync + Send>;
+type Load = Box<dyn FnOnce() -> Option<LoadedSource> + Send>;
+
+/// A source that's only loaded once it's first searched, so that the caches of the sources a
+/// command never looks at aren't read, and the ones it does are read at the same time. A source
+/// that fails to load finds nothing
+pub struct LazyDocSource {
+    load: Mutex<Option<Load>>,
+    source: OnceLock<Option<LoadedSource>>,
+}
+
+impl LazyDocSource {
+    pub fn new(load: impl FnOnce() -> Option<LoadedSource> + Send + 'static) -> Self {
+        Self {
+            load: Mutex::new(Some(Box::new(load))),
+            source: OnceLock::new(),
+        }
+    }
+
+    fn source(&self) -> Option<&(dyn DocSource + Sync + Send)> {
+        self.source
+            .get_or_init(|| {
+                let load = self
+                    .load
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .take();
+                load.and_then(|load| load())
+            })
+            .as_deref()
+    }
+}
+
+impl DocSource for LazyDocSource {
+    fn all_keys(&self) -> Vec<&str> {
+        self.source()
+            .map(|source| source.all_keys())
+            .unwrap_or_default()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.search(query))
+            .unwrap_or_default()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.search_liberal(query))
+            .unwrap_or_default()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.all_entries())
+            .unwrap_or_default()
+    }
+    fn search_matching(&self, matches: &(dyn Fn(&DocEntry) -> bool + Sync)) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.search_matching(matches))
+            .unwrap_or_default()
+    }
+    fn search_fuzzy(&self, query: &Lowercase) -> Vec<(i64, DocEntry)> {
+        self.source()
+            .map(|source| source.search_fuzzy(query))
+            .unwrap_or_default()
+    }
+    fn search_tokens(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.search_tokens(query))
+            .unwrap_or_default()
+    }
+    fn search_regex(&self, regex: &Regex, text: bool) -> Vec<DocEntry> {
+        self.source()
+            .map(|source| source.search_regex(regex, text))
+            .unwrap_or_default()
+    }
+    /// Updates the loaded source, one that failed to load having nothing to update
+    fn update(&mut self) -> Result<bool, Errors> {
+        self.source();
+        match self.source.get_mut() {
+            Some(Some(source)) => source.update(),
+            _ => Ok(false),
+        }
+    }
+}
+
+#[test]
+fn test_lazy_docsource() {
+    use crate::language_docsource::LanguageDocSource;
+    use std::sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+    };
+
+    let loads = Arc::new(AtomicUsize::new(0));
+    let source = LazyDocSource::new({
+        let loads = loads.clone();
+        move || {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Some(Box::new(LanguageDocSource::new()) as LoadedSource)
+        }
+    });
+    assert_eq!(loads.load(Ordering::SeqCst), 0);
+    assert_eq!(
+        source.search(&Lowercase(b"with")).len(),
+        LanguageDocSource::new().search(&Lowercase(b"with")).len()
+    );
+    assert!(!source.all_keys().is_empty());
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    let mut failed = LazyDocSource::new(|| None);
+    assert!(failed.search(&Lowercase(b"with")).is_empty());
+    assert!(!failed.update().unwrap());
+}
@@ -1,39 +1,292 @@
+use bincode::Options;
+use builtins_docsource::BuiltinDocumentation;
+use changelog_docsource::ChangelogDocumentation;
+use cli_docsource::CliCommandDocumentation;
 use comments_docsource::CommentDocumentation;
+use history::History;
+use language_docsource::LanguageDocumentation;
+use lib_docsource::LibFunctionDocumentation;
+use licenses_docsource::LicenseDocumentation;
+use maintainers_docsource::MaintainerDocumentation;
+use manual_docsource::{
+    ManualDocumentation,
+    ManualSectionType,
+};
+use nixconf_docsource::NixConfSettingDocumentation;
 use options_docsource::{
+    OptionDisplay,
     OptionDocumentation,
     OptionsDatabaseType,
 };
+use packages_docsource::PackageDocumentation;
 use rayon::iter::{
     IntoParallelRefIterator,
     ParallelIterator,
 };
+use regex::Regex;
+use renames_docsource::OptionRenameDocumentation;
+use rfc_docsource::RfcDocumentation;
 use serde::ser::SerializeStruct;
-use std::path::PathBuf;
+use std::{
+    borrow::Cow,
+    io::Read,
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+    sync::Mutex,
+};
 use thiserror::Error;
+use wiki_docsource::WikiPageDocumentation;
 use xml_docsource::XmlFuncDocumentation;
 
+pub mod bookmarks;
+pub mod boolean_query;
+pub mod builtins_docsource;
+pub mod cache_bundle;
+pub mod cache_status;
+pub mod changelog_docsource;
+pub mod cli_docsource;
 pub mod comments_docsource;
+pub mod compression;
+pub mod config;
+pub mod daemon;
+pub mod doctor;
+pub mod export;
+pub mod flake_lock;
+pub mod fuzzy;
+pub mod glob_query;
+pub mod highlight;
+pub mod history;
+pub mod key_tree;
+pub mod language_docsource;
+pub mod lazy_docsource;
+pub mod lib_docsource;
+pub mod licenses_docsource;
+pub mod maintainers_docsource;
+pub mod manual_docsource;
+pub mod markup;
+pub mod nixconf_docsource;
+pub mod nixpkgs;
 pub mod nixpkgs_tree_docsource;
+pub mod nur_docsource;
+pub mod online_docsource;
 pub mod options_docsource;
+pub mod packages_docsource;
+pub mod prebuilt;
+pub mod renames_docsource;
+pub mod rfc_docsource;
+pub mod table;
+pub mod tokenize;
+pub mod wiki_docsource;
 pub mod xml_docsource;
 
+/// Bumped whenever what the caches hold changes, caches of another format being rebuilt
+pub const CACHE_FORMAT_VERSION: u32 = 8;
+/// Far more than a header takes, so that reading one from a cache of before headers fails early
+const CACHE_HEADER_LIMIT: u64 = 4096;
+
+/// Written ahead of every cache, telling what wrote it and what it holds
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct CacheHeader {
+    pub format_version: u32,
+    pub manix_version: String,
+    /// Which source the cache is of
+    pub fingerprint: String,
+    /// The revision of nixpkgs the cache was built from, for the sources that are built from it
+    pub nixpkgs: Option<String>,
+    /// The version of nix the cache was dumped from, for the sources that come from the nix
+    /// binary
+    pub nix: Option<String>,
+    /// Whether what follows the header is compressed with zstd
+    pub compressed: bool,
+    /// When the cache was built, in seconds since the epoch
+    pub built_at: u64,
+    /// crc32 of what follows the header, so that a cache that got corrupted is told apart
+    /// without deserializing it
+    pub checksum: u32,
+}
+
+impl CacheHeader {
+    pub fn of<T: Cache>() -> Self {
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            manix_version: env!("CARGO_PKG_VERSION").to_string(),
+            fingerprint: std::any::type_name::<T>().to_string(),
+            nixpkgs: T::BUILT_FROM_NIXPKGS.then(nixpkgs_revision),
+            nix: T::BUILT_FROM_NIX.then(nix_version),
+            compressed: T::COMPRESSED,
+            built_at: history::now(),
+            checksum: 0,
+        }
+    }
+
+    /// Reads the header off the start of a cache
+    pub fn read(reader: impl Read) -> Result<Self, Errors> {
+        bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .allow_trailing_bytes()
+            .with_limit(CACHE_HEADER_LIMIT)
+            .deserialize_from(reader)
+            .map_err(|_| Errors::OutdatedCache)
+    }
+
+    /// Reads the header off the start of a cache, failing with `Errors::OutdatedCache` unless
+    /// it's the one `expected`, whether the cache is compressed and when it was built aside.
+    /// Gives the header read
+    fn check(reader: impl Read, expected: &Self) -> Result<Self, Errors> {
+        let header = Self::read(reader)?;
+        let is_expected = header.format_version == expected.format_version
+            && header.manix_version == expected.manix_version
+            && header.fingerprint == expected.fingerprint
+            && header.nixpkgs == expected.nixpkgs
+            && header.nix == expected.nix;
+        if is_expected {
+            Ok(header)
+        } else {
+            Err(Errors::OutdatedCache)
+        }
+    }
+
+    /// Checks the header of a cache as `check` does, and that what follows it matches its
+    /// checksum. Gives the header along with what follows it
+    pub(crate) fn check_content<'a>(
+        content: &'a [u8],
+        expected: &Self,
+    ) -> Result<(Self, &'a [u8]), Errors> {
+        let mut body = content;
+        let header = Self::check(&mut body, expected)?;
+        if crc32fast::hash(body) != header.checksum {
+            let mismatch = "the cache doesn't match its checksum".to_string();
+            return Err(Box::new(bincode::ErrorKind::Custom(mismatch)).into());
+        }
+        Ok((header, body))
+    }
+}
+
+static NIXPKGS_REVISION: Mutex<Option<String>> = Mutex::new(None);
+static NIX_VERSION: Mutex<Option<String>> = Mutex::new(None);
+
+/// The git revision of `<nixpkgs>` when it tells it, channels do, its store path otherwise. Both
+/// change with `nix-channel --update` or a flake update, looked up again after
+/// [forget_nix_versions]
+pub fn nixpkgs_revision() -> String {
+    let mut revision = NIXPKGS_REVISION.lock().unwrap();
+    revision
+        .get_or_insert_with(|| {
+            let root = comments_docsource::get_nixpkgs_root();
+            match std::fs::read_to_string(root.join(".git-revision")) {
+                Ok(revision) if !revision.trim().is_empty() => revision.trim().to_string(),
+                _ => root.display().to_string(),
+            }
+        })
+        .clone()
+}
+
+/// The version of the nix binary, `2.18.1` out of `nix (Nix) 2.18.1`, looked up again after
+/// [forget_nix_versions]. Empty when there's no nix
+pub fn nix_version() -> String {
+    let mut version = NIX_VERSION.lock().unwrap();
+    version
+        .get_or_insert_with(|| {
+            options_docsource::run_nix(Command::new("nix").arg("--version"))
+                .ok()
+                .filter(|output| output.status.success())
+                .and_then(|output| {
+                    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+                    Some(stdout.split_whitespace().last()?.to_string())
+                })
+                .unwrap_or_default()
+        })
+        .clone()
+}
+
+/// Has [nixpkgs_revision] and [nix_version] looked up again, the sources being loaded after
+/// `<nixpkgs>` was changed or updated
+pub fn forget_nix_versions() {
+    *NIXPKGS_REVISION.lock().unwrap() = None;
+    *NIX_VERSION.lock().unwrap() = None;
+}
+
+/// Writes the content next to the file first and then renames it over the file
+pub(crate) fn write_atomically(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temporary = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+    std::fs::write(&temporary, content)?;
+    std::fs::rename(&temporary, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&temporary);
+    })
+}
+
+/// Moves a cache that can't be read aside, to `NAME.corrupt`, so that it gets rebuilt and can
+/// still be looked at. Gives where it went
+pub fn quarantine_cache(path: &Path) -> Result<PathBuf, Errors> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let quarantined = path.with_file_name(format!("{}.corrupt", file_name));
+    std::fs::rename(path, &quarantined).map_err(|err| Errors::FileIo {
+        filename: path.display().to_string(),
+        err,
+    })?;
+    Ok(quarantined)
+}
+
 pub trait Cache
 where
     Self: Sized + DocSource + serde::Serialize,
 {
-    /// Deserializes content to Self
-    fn load<'a>(content: &'a [u8]) -> Result<Self, Errors>
+    /// Whether the source is built from nixpkgs, its cache being rebuilt when nixpkgs changes
+    const BUILT_FROM_NIXPKGS: bool = true;
+    /// Whether the source is dumped from the nix binary, its cache being rebuilt when nix changes
+    const BUILT_FROM_NIX: bool = false;
+    /// Whether the cache is compressed, left out for the ones read in place from the disk
+    const COMPRESSED: bool = true;
+
+    /// Deserializes content to Self, failing with `Errors::OutdatedCache` when it was written by
+    /// another version of manix, from another nixpkgs or is of another source
+    fn load(content: &[u8]) -> Result<Self, Errors>
     where
-        Self: serde::Deserialize<'a>,
+        Self: serde::de::DeserializeOwned,
     {
-        Ok(bincode::deserialize(content)?)
+        let (header, content) = CacheHeader::check_content(content, &CacheHeader::of::<Self>())?;
+        if !header.compressed {
+            return Ok(bincode::deserialize(content)?);
+        }
+        let content = compression::decompress(content)
+            .map_err(|err| Box::new(bincode::ErrorKind::Io(err)))?;
+        Ok(bincode::deserialize(&content)?)
     }
-    /// Saves self to a file, serialized with bincode
-    fn save(&self, filename: &PathBuf) -> Result<(), Errors> {
-        let x = bincode::serialize(self)?;
-        std::fs::write(filename, x)?;
+    /// Saves self to a file, serialized with bincode after a header and compressed with zstd
+    /// unless `COMPRESSED` is unset. The file is replaced at once, so that an interrupted build
+    /// leaves the cache as it was
+    fn save(&self, filename: &Path) -> Result<(), Errors> {
+        let mut header = CacheHeader::of::<Self>();
+        let content = bincode::serialize(self)?;
+        let content = if header.compressed {
+            compression::compress(&content)?
+        } else {
+            content
+        };
+        header.checksum = crc32fast::hash(&content);
+        let mut x = bincode::serialize(&header)?;
+        x.extend(content);
+        write_atomically(filename, &x)?;
         Ok(())
     }
+    /// Fails as `load` would on a cache that's outdated or corrupt, without deserializing it
+    fn verify(content: &[u8]) -> Result<(), Errors> {
+        CacheHeader::check_content(content, &CacheHeader::of::<Self>()).map(|_| ())
+    }
+    /// Whether there's a cache at the path that `load` wouldn't take, only its header being read
+    fn is_outdated(path: &Path) -> bool {
+        match std::fs::File::open(path) {
+            Ok(file) => {
+                let reader = std::io::BufReader::new(file);
+                CacheHeader::check(reader, &CacheHeader::of::<Self>()).is_err()
+            }
+            Err(_) => false,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
@@ -45,6 +298,8 @@ pub enum Errors {
     },
     #[error("Failed to perform IO on a cache file")]
     CacheFileIo(#[from] std::io::Error),
+    #[error("The cache was written by another version of manix or from another nixpkgs")]
+    OutdatedCache,
     #[error("Failed to serialize/deserialize cache(bincode)")]
     Bincode(#[from] bincode::Error),
     #[error("Failed to serialize/deserialize cache(serde_json)")]
@@ -73,6 +328,31 @@ impl SearchResults {
             key_only_entries,
         }
     }
+
+    /// The entries grouped by their source, the sources in the order their first entry comes in
+    pub fn by_source(&self) -> Vec<(&str, Vec<&DocEntry>)> {
+        let mut groups: Vec<(&str, Vec<&DocEntry>)> = Vec::new();
+        for entry in &self.entries {
+            match groups
+                .iter_mut()
+                .find(|(source, _)| *source == entry.source())
+            {
+                Some((_, entries)) => entries.push(entry),
+                None => groups.push((entry.source(), vec![entry])),
+            }
+        }
+        groups
+    }
+
+    /// Skips `offset` entries and keeps at most `limit` of the rest, returning how many were left
+    /// out after them
+    pub fn paginate(&mut self, offset: usize, limit: Option<usize>) -> usize {
+        self.entries.drain(..offset.min(self.entries.len()));
+        match limit {
+            Some(limit) if self.entries.len() > limit => self.entries.drain(limit..).count(),
+            _ => 0,
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -80,6 +360,20 @@ pub enum DocEntry {
     OptionDoc(OptionsDatabaseType, OptionDocumentation),
     CommentDoc(CommentDocumentation),
     XmlFuncDoc(XmlFuncDocumentation),
+    BuiltinDoc(BuiltinDocumentation),
+    PackageDoc(PackageDocumentation),
+    NurPackageDoc(PackageDocumentation),
+    CliDoc(CliCommandDocumentation),
+    NixConfDoc(NixConfSettingDocumentation),
+    LibFuncDoc(LibFunctionDocumentation),
+    WikiDoc(WikiPageDocumentation),
+    RfcDoc(RfcDocumentation),
+    ChangelogDoc(ChangelogDocumentation),
+    ManualDoc(ManualSectionType, ManualDocumentation),
+    LanguageDoc(LanguageDocumentation),
+    MaintainerDoc(MaintainerDocumentation),
+    LicenseDoc(LicenseDocumentation),
+    OptionRenameDoc(OptionRenameDocumentation),
     NixpkgsTreeDoc(String),
 }
 
@@ -89,6 +383,20 @@ impl DocEntry {
             DocEntry::OptionDoc(_, _) => "option",
             DocEntry::CommentDoc(_) => "comment",
             DocEntry::XmlFuncDoc(_) => "xml_function",
+            DocEntry::BuiltinDoc(_) => "builtin",
+            DocEntry::PackageDoc(_) => "package",
+            DocEntry::NurPackageDoc(_) => "package",
+            DocEntry::CliDoc(_) => "nix_command",
+            DocEntry::NixConfDoc(_) => "nix_setting",
+            DocEntry::LibFuncDoc(_) => "lib_function",
+            DocEntry::WikiDoc(_) => "wiki_page",
+            DocEntry::RfcDoc(_) => "rfc",
+            DocEntry::ChangelogDoc(_) => "changelog",
+            DocEntry::ManualDoc(_, _) => "manual_section",
+            DocEntry::LanguageDoc(_) => "language",
+            DocEntry::MaintainerDoc(_) => "maintainer",
+            DocEntry::LicenseDoc(_) => "license",
+            DocEntry::OptionRenameDoc(_) => "option_rename",
             DocEntry::NixpkgsTreeDoc(_) => "nixpkgs_tree",
         }
     }
@@ -98,6 +406,20 @@ impl DocEntry {
             DocEntry::OptionDoc(_, x) => x.name(),
             DocEntry::CommentDoc(x) => x.name(),
             DocEntry::XmlFuncDoc(x) => x.name(),
+            DocEntry::BuiltinDoc(x) => x.name(),
+            DocEntry::PackageDoc(x) => x.name(),
+            DocEntry::NurPackageDoc(x) => x.name(),
+            DocEntry::CliDoc(x) => x.name(),
+            DocEntry::NixConfDoc(x) => x.name(),
+            DocEntry::LibFuncDoc(x) => x.name(),
+            DocEntry::WikiDoc(x) => x.name(),
+            DocEntry::RfcDoc(x) => x.name(),
+            DocEntry::ChangelogDoc(x) => x.name(),
+            DocEntry::ManualDoc(_, x) => x.name(),
+            DocEntry::LanguageDoc(x) => x.name(),
+            DocEntry::MaintainerDoc(x) => x.name(),
+            DocEntry::LicenseDoc(x) => x.name(),
+            DocEntry::OptionRenameDoc(x) => x.name(),
             DocEntry::NixpkgsTreeDoc(x) => x.clone(),
         }
     }
@@ -106,21 +428,113 @@ impl DocEntry {
             DocEntry::OptionDoc(_, x) => x.pretty_printed(),
             DocEntry::CommentDoc(x) => x.pretty_printed(),
             DocEntry::XmlFuncDoc(x) => x.pretty_printed(),
+            DocEntry::BuiltinDoc(x) => x.pretty_printed(),
+            DocEntry::PackageDoc(x) => x.pretty_printed(),
+            DocEntry::NurPackageDoc(x) => x.pretty_printed(),
+            DocEntry::CliDoc(x) => x.pretty_printed(),
+            DocEntry::NixConfDoc(x) => x.pretty_printed(),
+            DocEntry::LibFuncDoc(x) => x.pretty_printed(),
+            DocEntry::WikiDoc(x) => x.pretty_printed(),
+            DocEntry::RfcDoc(x) => x.pretty_printed(),
+            DocEntry::ChangelogDoc(x) => x.pretty_printed(),
+            DocEntry::ManualDoc(_, x) => x.pretty_printed(),
+            DocEntry::LanguageDoc(x) => x.pretty_printed(),
+            DocEntry::MaintainerDoc(x) => x.pretty_printed(),
+            DocEntry::LicenseDoc(x) => x.pretty_printed(),
+            DocEntry::OptionRenameDoc(x) => x.pretty_printed(),
             DocEntry::NixpkgsTreeDoc(x) => x.clone(),
         }
     }
     pub fn source(&self) -> &str {
         match self {
-            DocEntry::OptionDoc(typ, _) => match typ {
-                OptionsDatabaseType::NixOS => "NixOS Options",
-                OptionsDatabaseType::NixDarwin => "NixDarwin Options",
-                OptionsDatabaseType::HomeManager => "HomeManager Options",
-            },
+            DocEntry::OptionDoc(typ, _) => typ.source_name(),
             DocEntry::CommentDoc(_) => "Nixpkgs Comments",
             DocEntry::XmlFuncDoc(_) => "Nixpkgs Documentation",
+            DocEntry::BuiltinDoc(_) => "Nix Builtins",
+            DocEntry::PackageDoc(_) => "Nixpkgs Packages",
+            DocEntry::NurPackageDoc(_) => "NUR Packages",
+            DocEntry::CliDoc(_) => "Nix CLI",
+            DocEntry::NixConfDoc(_) => "nix.conf Settings",
+            DocEntry::LibFuncDoc(_) => "Nixpkgs Lib",
+            DocEntry::WikiDoc(_) => "NixOS Wiki",
+            DocEntry::RfcDoc(_) => "Nix RFCs",
+            DocEntry::ChangelogDoc(_) => "Release Notes",
+            DocEntry::ManualDoc(typ, _) => typ.source_name(),
+            DocEntry::LanguageDoc(_) => "Nix Language",
+            DocEntry::MaintainerDoc(_) => "Nixpkgs Maintainers",
+            DocEntry::LicenseDoc(_) => "Nixpkgs Licenses",
+            DocEntry::OptionRenameDoc(_) => "Renamed Options",
             DocEntry::NixpkgsTreeDoc(_) => "Nixpkgs Tree",
         }
     }
+    /// Description, comment or body of the entry, whatever prose it comes with, which full text
+    /// searches look in. Borrowed from the entry unless it's put together from several parts
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            DocEntry::OptionDoc(_, x) => x.text().into(),
+            DocEntry::CommentDoc(x) => x.text().into(),
+            DocEntry::XmlFuncDoc(x) => x.text().into(),
+            DocEntry::BuiltinDoc(x) => x.text().into(),
+            DocEntry::PackageDoc(x) => x.text().into(),
+            DocEntry::NurPackageDoc(x) => x.text().into(),
+            DocEntry::CliDoc(x) => x.text().into(),
+            DocEntry::NixConfDoc(x) => x.text().into(),
+            DocEntry::LibFuncDoc(x) => x.text().into(),
+            DocEntry::WikiDoc(x) => x.text().into(),
+            DocEntry::RfcDoc(x) => x.text().into(),
+            DocEntry::ChangelogDoc(x) => x.text().into(),
+            DocEntry::ManualDoc(_, x) => x.text().into(),
+            DocEntry::LanguageDoc(x) => x.text().into(),
+            DocEntry::MaintainerDoc(x) => x.text().into(),
+            DocEntry::LicenseDoc(x) => x.text().into(),
+            DocEntry::OptionRenameDoc(x) => x.text().into(),
+            DocEntry::NixpkgsTreeDoc(_) => Cow::Borrowed(""),
+        }
+    }
+    /// The entry as a Markdown section
+    pub fn markdown(&self, display: OptionDisplay) -> String {
+        let body = match self {
+            DocEntry::OptionDoc(typ, x) => x.markdown(*typ, display),
+            _ => {
+                let mut body = String::new();
+                let text = self.text();
+                if !text.trim().is_empty() {
+                    body.push_str(&format!("{}\n\n", text.trim()));
+                }
+                if let Some(url) = self.url() {
+                    body.push_str(&format!("<{}>\n\n", url));
+                }
+                body
+            }
+        };
+        format!("## `{}`\n\n*{}*\n\n{}", self.name(), self.source(), body)
+    }
+    /// Where the entry is documented online, when known
+    pub fn url(&self) -> Option<String> {
+        match self {
+            DocEntry::OptionDoc(typ, x) => typ.option_url(&x.name()),
+            DocEntry::PackageDoc(x) | DocEntry::NurPackageDoc(x) => x.homepage.clone(),
+            DocEntry::WikiDoc(x) => Some(x.url.clone()),
+            _ => None,
+        }
+    }
+    /// File defining the entry on disk, along with the line when it's found
+    pub fn location(&self) -> Option<(PathBuf, Option<usize>)> {
+        match self {
+            DocEntry::OptionDoc(_, x) => x.declaration_path(),
+            DocEntry::CommentDoc(x) => x.location(),
+            _ => None,
+        }
+    }
+    /// Whether every word of the query shows up in the name or the text of the entry,
+    /// `wayland compositor` finds `programs.sway.enable`
+    pub fn matches_full_text(&self, query: &str, options: MatchOptions) -> bool {
+        let name = self.name();
+        let text = self.text();
+        query
+            .split_whitespace()
+            .all(|word| options.finds(&name, word) || options.finds(&text, word))
+    }
 }
 
 impl serde::Serialize for DocEntry {
@@ -128,9 +542,19 @@ impl serde::Serialize for DocEntry {
     where
         S: serde::Serializer,
     {
-        let mut state = serializer.serialize_struct("DocEntry", 4)?;
+        let (option_type, default) = match self {
+            DocEntry::OptionDoc(_, x) => (Some(x.option_type()), x.default.as_deref()),
+            _ => (None, None),
+        };
+        let mut state = serializer.serialize_struct("DocEntry", 9)?;
+        state.serialize_field("key", &self.name())?;
         state.serialize_field("kind", self.kind())?;
         state.serialize_field("source", self.source())?;
+        state.serialize_field("description", &self.text())?;
+        state.serialize_field("type", &option_type)?;
+        state.serialize_field("default", &default)?;
+        state.serialize_field("url", &self.url())?;
+        // deprecated alias of `key`, kept for the tools reading the JSON from before it
         state.serialize_field("name", &self.name())?;
         match self {
             DocEntry::OptionDoc(_, documentation) => {
@@ -142,6 +566,45 @@ impl serde::Serialize for DocEntry {
             DocEntry::XmlFuncDoc(documentation) => {
                 state.serialize_field("documentation", documentation)?;
             }
+            DocEntry::BuiltinDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::PackageDoc(documentation) | DocEntry::NurPackageDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::CliDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::NixConfDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::LibFuncDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::WikiDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::RfcDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::ChangelogDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::ManualDoc(_, documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::LanguageDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::MaintainerDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::LicenseDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
+            DocEntry::OptionRenameDoc(documentation) => {
+                state.serialize_field("documentation", documentation)?;
+            }
             DocEntry::NixpkgsTreeDoc(_) => {
                 state.serialize_field("documentation", &Option::<()>::None)?;
             }
@@ -154,6 +617,33 @@ pub trait DocSource {
     fn all_keys(&self) -> Vec<&str>;
     fn search(&self, query: &Lowercase) -> Vec<DocEntry>;
     fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry>;
+    /// Every entry of the source, for searches looking at more than the keys
+    fn all_entries(&self) -> Vec<DocEntry>;
+    fn search_matching(&self, matches: &(dyn Fn(&DocEntry) -> bool + Sync)) -> Vec<DocEntry> {
+        self.all_entries()
+            .into_iter()
+            .filter(|entry| matches(entry))
+            .collect()
+    }
+    /// Entries fuzzily matching the query along with their score, higher is better
+    fn search_fuzzy(&self, query: &Lowercase) -> Vec<(i64, DocEntry)> {
+        self.all_entries()
+            .into_iter()
+            .filter_map(|entry| fuzzy::score(&entry.name(), query).map(|score| (score, entry)))
+            .collect()
+    }
+    /// Entries whose key has the words of the query, for queries of several words that substrings
+    /// of keys never match
+    fn search_tokens(&self, query: &Lowercase) -> Vec<DocEntry> {
+        let query = String::from_utf8_lossy(query.0);
+        self.search_matching(&|entry| tokenize::matches_tokens(&entry.name(), &query))
+    }
+    /// Entries whose key, or text when `text` is set, the regular expression matches
+    fn search_regex(&self, regex: &Regex, text: bool) -> Vec<DocEntry> {
+        self.search_matching(&|entry| {
+            regex.is_match(&entry.name()) || (text && regex.is_match(&entry.text()))
+        })
+    }
 
     /// Updates the cache, returns true if anything changed
     fn update(&mut self) -> Result<bool, Errors>;
@@ -168,6 +658,33 @@ impl AggregateDocSource {
     pub fn add_source(&mut self, source: Box<dyn DocSource + Sync>) {
         self.sources.push(source)
     }
+
+    /// Runs the search on every source in parallel, handing over the results of each source as
+    /// soon as it's done
+    pub fn search_streaming(
+        &self,
+        search: &(dyn Fn(&dyn DocSource) -> Vec<DocEntry> + Sync),
+        found: &(dyn Fn(Vec<DocEntry>) + Sync),
+    ) {
+        self.sources
+            .par_iter()
+            .for_each(|source| found(search(source.as_ref())));
+    }
+}
+
+/// Liberal search that also matches queries of several words against the words of the keys,
+/// which substrings never do
+pub fn search_liberal_with_tokens(source: &dyn DocSource, query: &Lowercase) -> Vec<DocEntry> {
+    let mut found = source.search_liberal(query);
+    if query.0.iter().any(u8::is_ascii_whitespace) {
+        found.extend(
+            source
+                .search_tokens(query)
+                .into_iter()
+                .filter(|entry| !contains_insensitive_ascii(entry.name().as_bytes(), query)),
+        );
+    }
+    found
 }
 
 impl DocSource for AggregateDocSource {
@@ -189,6 +706,24 @@ impl DocSource for AggregateDocSource {
             .flat_map(|source| source.search_liberal(query))
             .collect()
     }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.sources
+            .par_iter()
+            .flat_map(|source| source.all_entries())
+            .collect()
+    }
+    fn search_matching(&self, matches: &(dyn Fn(&DocEntry) -> bool + Sync)) -> Vec<DocEntry> {
+        self.sources
+            .par_iter()
+            .flat_map(|source| source.search_matching(matches))
+            .collect()
+    }
+    fn search_fuzzy(&self, query: &Lowercase) -> Vec<(i64, DocEntry)> {
+        self.sources
+            .par_iter()
+            .flat_map(|source| source.search_fuzzy(query))
+            .collect()
+    }
     fn update(&mut self) -> Result<bool, Errors> {
         unimplemented!();
     }
@@ -233,6 +768,185 @@ pub(crate) fn contains_insensitive_ascii(s: &[u8], inner: &Lowercase) -> bool {
     false
 }
 
+/// How query terms are looked for in names and texts, by default as case insensitive substrings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MatchOptions {
+    pub case_sensitive: bool,
+    /// Only match terms not surrounded by letters or digits, `map` isn't a word of `mapAttrs`
+    pub whole_word: bool,
+}
+
+impl MatchOptions {
+    pub fn finds(&self, haystack: &str, needle: &str) -> bool {
+        let (haystack, needle) = if self.case_sensitive {
+            (Cow::Borrowed(haystack), Cow::Borrowed(needle))
+        } else {
+            (
+                Cow::Owned(haystack.to_ascii_lowercase()),
+                Cow::Owned(needle.to_ascii_lowercase()),
+            )
+        };
+        if !self.whole_word {
+            return haystack.contains(&*needle);
+        }
+        let bytes = haystack.as_bytes();
+        haystack.match_indices(&*needle).any(|(start, found)| {
+            let end = start + found.len();
+            (start == 0 || !bytes[start - 1].is_ascii_alphanumeric())
+                && !bytes.get(end).is_some_and(u8::is_ascii_alphanumeric)
+        })
+    }
+}
+
+/// How well a name matches the query, lower is better: exact matches, where `mergeAttrs` is an
+/// exact match for `lib.mergeAttrs` as well, come before prefix and then substring matches, with
+/// shorter paths first within each of them
+fn relevance(name: &str, query: &Lowercase) -> (u8, usize, usize) {
+    let lower = name.to_ascii_lowercase();
+    let bytes = lower.as_bytes();
+    let last_segment = bytes.rsplit(|&b| b == b'.').next().unwrap_or(bytes);
+    let tier = if bytes == query.0 || last_segment == query.0 {
+        0
+    } else if bytes.starts_with(query.0) {
+        1
+    } else if contains_insensitive_ascii(bytes, query) {
+        2
+    } else {
+        3
+    };
+    (tier, bytes.split(|&b| b == b'.').count(), bytes.len())
+}
+
+/// Whether the name is the query itself, or ends with it as its last segment
+pub fn is_exact_match(name: &str, query: &Lowercase) -> bool {
+    relevance(name, query).0 == 0
+}
+
+/// Sorts the entries of every source together by their relevance to the query
+pub fn sort_by_relevance(entries: &mut [DocEntry], query: &Lowercase) {
+    sort_by_relevance_with_history(entries, query, &History::default())
+}
+
+/// Like [sort_by_relevance], the entries looked at often and recently going first among those as
+/// relevant
+pub fn sort_by_relevance_with_history(
+    entries: &mut [DocEntry],
+    query: &Lowercase,
+    history: &History,
+) {
+    let now = history::now();
+    entries.sort_by_cached_key(|entry| {
+        let name = entry.name();
+        let (tier, depth, length) = relevance(&name, query);
+        let frecency = std::cmp::Reverse(history.frecency(&name, now));
+        (tier, frecency, depth, length, name)
+    });
+}
+
+#[test]
+fn test_cache_header() {
+    let path = std::env::temp_dir().join(format!("manix-cache-{}.bin", std::process::id()));
+    cli_docsource::CliDatabase::new().save(&path).unwrap();
+    assert!(!cli_docsource::CliDatabase::is_outdated(&path));
+    assert!(lib_docsource::LibDatabase::is_outdated(&path));
+    assert_eq!(
+        CacheHeader::of::<cli_docsource::CliDatabase>().nixpkgs,
+        None
+    );
+    assert_eq!(
+        CacheHeader::of::<cli_docsource::CliDatabase>().nix,
+        Some(nix_version())
+    );
+    assert_eq!(
+        CacheHeader::of::<lib_docsource::LibDatabase>().nixpkgs,
+        Some(nixpkgs_revision())
+    );
+    assert_eq!(CacheHeader::of::<lib_docsource::LibDatabase>().nix, None);
+    assert_eq!(
+        CacheHeader::of::<rfc_docsource::RfcDatabase>().nixpkgs,
+        None
+    );
+    let content = std::fs::read(&path).unwrap();
+    assert!(cli_docsource::CliDatabase::load(&content).is_ok());
+    assert!(matches!(
+        lib_docsource::LibDatabase::load(&content),
+        Err(Errors::OutdatedCache)
+    ));
+    // caches of before headers
+    std::fs::write(&path, bincode::serialize(&vec![u64::MAX; 4]).unwrap()).unwrap();
+    assert!(cli_docsource::CliDatabase::is_outdated(&path));
+    std::fs::remove_file(&path).unwrap();
+    assert!(!cli_docsource::CliDatabase::is_outdated(&path));
+}
+
+#[test]
+fn test_corrupt_cache() {
+    let dir = std::env::temp_dir().join(format!("manix-corrupt-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("cli_database.bin");
+    cli_docsource::CliDatabase::new().save(&path).unwrap();
+    let mut content = std::fs::read(&path).unwrap();
+    // cut short like an interrupted write
+    content.truncate(content.len() - 1);
+    std::fs::write(&path, &content).unwrap();
+    assert!(matches!(
+        cli_docsource::CliDatabase::load(&std::fs::read(&path).unwrap()),
+        Err(Errors::Bincode(_))
+    ));
+    assert!(matches!(
+        cli_docsource::CliDatabase::verify(&content),
+        Err(Errors::Bincode(_))
+    ));
+    // a byte changed on the disk
+    cli_docsource::CliDatabase::new().save(&path).unwrap();
+    let mut content = std::fs::read(&path).unwrap();
+    *content.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        cli_docsource::CliDatabase::verify(&content),
+        Err(Errors::Bincode(_))
+    ));
+    assert_eq!(
+        quarantine_cache(&path).unwrap(),
+        dir.join("cli_database.bin.corrupt")
+    );
+    assert!(!path.exists());
+    // nothing but the quarantined cache is left
+    assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_by_source() {
+    let option = |typ, name: &str| {
+        let documentation = OptionDocumentation {
+            location: vec![name.to_string()],
+            ..Default::default()
+        };
+        DocEntry::OptionDoc(typ, documentation)
+    };
+    let results = SearchResults::from_entries(vec![
+        option(OptionsDatabaseType::HomeManager, "a"),
+        option(OptionsDatabaseType::NixOS, "b"),
+        option(OptionsDatabaseType::HomeManager, "c"),
+        DocEntry::NixpkgsTreeDoc("d".to_string()),
+    ]);
+    let groups = results
+        .by_source()
+        .into_iter()
+        .map(|(source, entries)| (source, entries.iter().map(|e| e.name()).collect::<Vec<_>>()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        groups,
+        vec![
+            (
+                "HomeManager Options",
+                vec!["a".to_string(), "c".to_string()]
+            ),
+            ("NixOS Options", vec!["b".to_string()]),
+        ]
+    );
+}
+
 #[test]
 fn test_starts_with_insensitive_ascii() {
     assert!(starts_with_insensitive_ascii(
@@ -276,3 +990,121 @@ fn test_contains_insensitive_ascii() {
         &Lowercase(b"abcd")
     ),);
 }
+
+#[test]
+fn test_matches_full_text() {
+    let source = language_docsource::LanguageDocSource::new();
+    let found = source.search_matching(&|entry| {
+        entry.matches_full_text("concatenates lists", MatchOptions::default())
+    });
+    assert_eq!(
+        found.iter().map(DocEntry::name).collect::<Vec<_>>(),
+        vec!["++"]
+    );
+}
+
+#[test]
+fn test_search_regex() {
+    let source = language_docsource::LanguageDocSource::new();
+    let mut found = source
+        .search_regex(&Regex::new("^inherit").unwrap(), false)
+        .iter()
+        .map(DocEntry::name)
+        .collect::<Vec<_>>();
+    found.sort();
+    assert_eq!(found, vec!["inherit", "inherit (set)"]);
+    assert_eq!(
+        source
+            .search_regex(&Regex::new("curried").unwrap(), true)
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn test_sort_by_relevance() {
+    let mut entries = [
+        "services.xserver.windowManager.i3.enable",
+        "lib.mergeAttrsList",
+        "services.mergeAttrs.enable",
+        "mergeAttrsList",
+        "lib.mergeAttrs",
+    ]
+    .map(|name| DocEntry::NixpkgsTreeDoc(name.to_string()));
+    sort_by_relevance(&mut entries, &Lowercase(b"mergeattrs"));
+    assert_eq!(
+        entries.iter().map(DocEntry::name).collect::<Vec<_>>(),
+        vec![
+            "lib.mergeAttrs",
+            "mergeAttrsList",
+            "lib.mergeAttrsList",
+            "services.mergeAttrs.enable",
+            "services.xserver.windowManager.i3.enable",
+        ]
+    );
+}
+
+#[test]
+fn test_paginate() {
+    let entries = language_docsource::LanguageDocSource::new().all_entries();
+    let names = entries.iter().map(DocEntry::name).collect::<Vec<_>>();
+    let total = entries.len();
+
+    let mut results = SearchResults::from_entries(entries);
+    assert_eq!(results.paginate(1, Some(2)), total - 3);
+    assert_eq!(
+        results
+            .entries
+            .iter()
+            .map(DocEntry::name)
+            .collect::<Vec<_>>(),
+        names[1..3]
+    );
+    assert_eq!(results.paginate(10_000, None), 0);
+    assert!(results.entries.is_empty());
+}
+
+#[test]
+fn test_match_options() {
+    let sensitive = MatchOptions {
+        case_sensitive: true,
+        whole_word: false,
+    };
+    let word = MatchOptions {
+        case_sensitive: false,
+        whole_word: true,
+    };
+    assert!(MatchOptions::default().finds("lib.mapAttrs", "map"));
+    assert!(!sensitive.finds("lib.mapAttrs", "Map"));
+    assert!(sensitive.finds("lib.concatMap", "Map"));
+    assert!(!word.finds("lib.mapAttrs", "map"));
+    assert!(word.finds("lib.map", "map"));
+    assert!(word.finds("the map function", "MAP"));
+}
+
+#[test]
+fn test_serialize_entry() {
+    let entry = language_docsource::LanguageDocSource::new()
+        .search(&Lowercase(b"//"))
+        .pop()
+        .unwrap();
+    let value = serde_json::to_value(&entry).unwrap();
+    assert_eq!(value["key"], "//");
+    assert_eq!(value["name"], value["key"]);
+    assert_eq!(value["kind"], "language");
+    assert_eq!(value["source"], "Nix Language");
+    assert!(value["description"].as_str().is_some_and(|d| !d.is_empty()));
+    assert!(value["type"].is_null());
+    assert!(value["url"].is_null());
+}
+
+#[test]
+fn test_markdown() {
+    let entry = language_docsource::LanguageDocSource::new()
+        .search(&Lowercase(b"//"))
+        .pop()
+        .unwrap();
+    let markdown = entry.markdown(OptionDisplay::default());
+    assert!(markdown.starts_with("## `//`\n\n*Nix Language*\n\n"));
+    assert!(!markdown.contains('\x1b'));
+}
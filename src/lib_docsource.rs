@@ -0,0 +1,330 @@
+use crate::{
+    comments_docsource::{
+        find_comments,
+        get_nixpkgs_root,
+    },
+    contains_insensitive_ascii,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use rayon::prelude::*;
+use rnix::{
+    ast::{
+        AttrpathValue,
+        Expr,
+    },
+    Root,
+};
+use rowan::ast::AstNode;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LibFunctionDocumentation {
+    name: String,
+    signature: Option<String>,
+    description: String,
+    example: Option<String>,
+    path: PathBuf,
+}
+
+impl LibFunctionDocumentation {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.description
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = match &self.signature {
+            Some(signature) => format!("# {} :: {}\n", self.name.blue().bold(), signature.cyan()),
+            None => format!("# {}\n", self.name.blue().bold()),
+        };
+        output.push_str(&format!("{}\n", self.description));
+        if let Some(example) = &self.example {
+            output.push_str("\nExample:\n");
+            for line in example.lines() {
+                output.push_str(&format!("  {}\n", line.white()));
+            }
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// Typed documentation of the nixpkgs lib functions, extracted from their doc comments the same
+/// way nixdoc does
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LibDatabase {
+    pub functions: HashMap<String, LibFunctionDocumentation>,
+}
+
+impl Default for LibDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LibDatabase {
+    pub fn new() -> Self {
+        Self {
+            functions: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for LibDatabase {}
+
+impl DocSource for LibDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.functions.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.functions
+            .iter()
+            .filter(|(key, _)| {
+                // match `lib.attrsets.mapAttrs`, `lib.mapAttrs` and `mapAttrs`
+                let short = key.rsplit('.').next().unwrap_or(key);
+                starts_with_insensitive_ascii(key.as_bytes(), query)
+                    || starts_with_insensitive_ascii(short.as_bytes(), query)
+                    || starts_with_insensitive_ascii(format!("lib.{}", short).as_bytes(), query)
+            })
+            .map(|(_, value)| DocEntry::LibFuncDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.functions
+            .iter()
+            .filter(|(key, _)| contains_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::LibFuncDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.functions
+            .values()
+            .map(|value| DocEntry::LibFuncDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let lib_root = get_nixpkgs_root().join("lib");
+        let new = walkdir::WalkDir::new(&lib_root)
+            .into_iter()
+            .filter_map(Result::ok)
+            .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("nix"))
+            .filter(|e| !e.path().to_string_lossy().contains("test"))
+            .collect::<Vec<_>>()
+            .par_iter()
+            .flat_map(|file| {
+                let namespace = lib_namespace(&lib_root, file.path());
+                match std::fs::read_to_string(file.path()) {
+                    Ok(content) => parse_lib_file(&namespace, file.path(), &content),
+                    Err(_) => Vec::new(),
+                }
+            })
+            .map(|doc| (doc.name(), doc))
+            .collect::<HashMap<_, _>>();
+        let old = std::mem::replace(&mut self.functions, new);
+
+        Ok(!self.functions.keys().eq(old.keys()))
+    }
+}
+
+/// `lib/attrsets.nix` is `lib.attrsets`, `lib/path/default.nix` is `lib.path`
+fn lib_namespace(lib_root: &Path, file: &Path) -> String {
+    let relative = file
+        .strip_prefix(lib_root)
+        .unwrap_or(file)
+        .with_extension("");
+    std::iter::once("lib".to_string())
+        .chain(
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().to_string())
+                .filter(|c| c != "default"),
+        )
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+fn parse_lib_file(namespace: &str, path: &Path, content: &str) -> Vec<LibFunctionDocumentation> {
+    let ast = match Root::parse(content).ok() {
+        Ok(ast) => ast,
+        Err(_) => return Vec::new(),
+    };
+
+    ast.syntax()
+        .descendants()
+        .filter_map(AttrpathValue::cast)
+        .filter_map(|entry| {
+            let comment = find_comments(entry.syntax().clone())?.pop()?;
+            // `/** */` marks a doc comment, plain `/* */` ones only count above functions
+            let is_lambda = matches!(entry.value(), Some(Expr::Lambda(_)));
+            if !comment.starts_with("/**") && !(is_lambda && comment.starts_with("/*")) {
+                return None;
+            }
+            let attrpath = entry
+                .attrpath()?
+                .attrs()
+                .map(|attr| attr.to_string())
+                .collect::<Vec<_>>()
+                .join(".");
+            let (description, signature, example) = parse_doc_comment(&comment);
+
+            Some(LibFunctionDocumentation {
+                name: format!("{}.{}", namespace, attrpath),
+                signature,
+                description,
+                example,
+                path: path.to_path_buf(),
+            })
+        })
+        .collect()
+}
+
+/// Strips fences and directive markers off a doc comment section
+fn section_text(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .filter(|line| !line.trim_start().starts_with("```"))
+        .filter(|line| !line.trim_start().starts_with(":::"))
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string()
+}
+
+/// Splits an RFC 145 (or legacy `Type:`) doc comment into description, type signature and example
+fn parse_doc_comment(comment: &str) -> (String, Option<String>, Option<String>) {
+    let body = comment
+        .trim_start_matches("/**")
+        .trim_start_matches("/*")
+        .trim_end_matches("*/");
+    let lines = body.lines().collect::<Vec<_>>();
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+    let lines = lines
+        .iter()
+        .map(|line| line.get(indent..).unwrap_or_else(|| line.trim_start()))
+        .collect::<Vec<_>>();
+
+    let mut sections: Vec<(&str, Vec<&str>)> = vec![("", Vec::new())];
+    for line in lines {
+        match line.strip_prefix("# ") {
+            Some(heading) => sections.push((heading.trim(), Vec::new())),
+            None => sections.last_mut().unwrap().1.push(line),
+        }
+    }
+
+    let section = |name: &str| {
+        sections
+            .iter()
+            .find(|(heading, _)| heading.eq_ignore_ascii_case(name))
+            .map(|(_, lines)| section_text(lines))
+            .filter(|text| !text.is_empty())
+    };
+
+    let mut description = section("").unwrap_or_default();
+    let mut signature = section("Type");
+    let example = section("Examples").or_else(|| section("Example"));
+
+    // legacy comments put the signature on a `Type: f :: a -> b` line
+    if signature.is_none() {
+        if let Some(line) = description
+            .lines()
+            .find(|line| line.trim_start().starts_with("Type:"))
+        {
+            signature = Some(
+                line.trim_start()
+                    .trim_start_matches("Type:")
+                    .trim()
+                    .to_string(),
+            );
+            description = description
+                .lines()
+                .filter(|l| *l != line)
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+    }
+
+    // drop the function name from `mapAttrs :: ...` so it isn't printed twice
+    let signature = signature.map(|s| match s.split_once("::") {
+        Some((_, ty)) => ty.trim().to_string(),
+        None => s,
+    });
+
+    (description.trim().to_string(), signature, example)
+}
+
+#[test]
+fn test_parse_doc_comment() {
+    let comment = r#"/**
+      Apply a function to each element in an attribute set.
+
+      # Type
+
+      ```
+      mapAttrs :: (String -> Any -> Any) -> AttrSet -> AttrSet
+      ```
+
+      # Examples
+      :::{.example}
+      ```nix
+      mapAttrs (name: value: name + "-" + value) { x = "foo"; }
+      ```
+      :::
+    */"#;
+    let (description, signature, example) = parse_doc_comment(comment);
+    assert_eq!(
+        description,
+        "Apply a function to each element in an attribute set."
+    );
+    assert_eq!(
+        signature.as_deref(),
+        Some("(String -> Any -> Any) -> AttrSet -> AttrSet")
+    );
+    assert_eq!(
+        example.as_deref(),
+        Some(r#"mapAttrs (name: value: name + "-" + value) { x = "foo"; }"#)
+    );
+
+    let legacy = "/* Return the head of a list.\n\n     Type: head :: [a] -> a\n  */";
+    let (description, signature, example) = parse_doc_comment(legacy);
+    assert_eq!(description, "Return the head of a list.");
+    assert_eq!(signature.as_deref(), Some("[a] -> a"));
+    assert!(example.is_none());
+}
+
+#[test]
+fn test_lib_namespace() {
+    let root = Path::new("/nixpkgs/lib");
+    assert_eq!(
+        lib_namespace(root, Path::new("/nixpkgs/lib/attrsets.nix")),
+        "lib.attrsets"
+    );
+    assert_eq!(
+        lib_namespace(root, Path::new("/nixpkgs/lib/path/default.nix")),
+        "lib.path"
+    );
+}
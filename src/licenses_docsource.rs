@@ -0,0 +1,172 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LicenseDocumentation {
+    attr: String,
+    full_name: String,
+    spdx_id: Option<String>,
+    url: Option<String>,
+    free: bool,
+    redistributable: bool,
+}
+
+impl LicenseDocumentation {
+    pub fn name(&self) -> String {
+        format!("licenses.{}", self.attr)
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.full_name
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}\n{}\n", self.name().blue().bold(), self.full_name);
+        if let Some(spdx_id) = &self.spdx_id {
+            output.push_str(&format!("SPDX: {}\n", spdx_id.cyan()));
+        }
+        if let Some(url) = &self.url {
+            output.push_str(&format!("{}\n", url.underline()));
+        }
+        output.push_str(&format!(
+            "free: {}, redistributable: {}\n\n",
+            self.free, self.redistributable
+        ));
+        output
+    }
+}
+
+/// Shape of the licenses dumped by `nix/licenses.nix`, keyed by attribute name
+#[derive(Deserialize)]
+struct DumpedLicense {
+    #[serde(rename = "fullName")]
+    full_name: String,
+    #[serde(rename = "spdxId")]
+    spdx_id: Option<String>,
+    url: Option<String>,
+    free: bool,
+    redistributable: bool,
+}
+
+/// `lib.licenses` of nixpkgs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LicensesDatabase {
+    pub licenses: HashMap<String, LicenseDocumentation>,
+}
+
+impl Default for LicensesDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LicensesDatabase {
+    pub fn new() -> Self {
+        Self {
+            licenses: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for LicensesDatabase {}
+
+impl DocSource for LicensesDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.licenses.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.licenses
+            .iter()
+            .filter(|(key, value)| {
+                starts_with_insensitive_ascii(key.as_bytes(), query)
+                    || starts_with_insensitive_ascii(value.name().as_bytes(), query)
+                    || value
+                        .spdx_id
+                        .as_ref()
+                        .is_some_and(|id| starts_with_insensitive_ascii(id.as_bytes(), query))
+            })
+            .map(|(_, value)| DocEntry::LicenseDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.licenses
+            .iter()
+            .filter(|(key, value)| {
+                contains_insensitive_ascii(key.as_bytes(), query)
+                    || contains_insensitive_ascii(value.full_name.as_bytes(), query)
+                    || value
+                        .spdx_id
+                        .as_ref()
+                        .is_some_and(|id| contains_insensitive_ascii(id.as_bytes(), query))
+            })
+            .map(|(_, value)| DocEntry::LicenseDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.licenses
+            .values()
+            .map(|value| DocEntry::LicenseDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let new = parse_dumped_licenses(&dump_licenses()?)?;
+        let old = std::mem::replace(&mut self.licenses, new);
+
+        Ok(!self.licenses.keys().eq(old.keys()))
+    }
+}
+
+fn parse_dumped_licenses(content: &[u8]) -> Result<HashMap<String, LicenseDocumentation>, Errors> {
+    let dumped: HashMap<String, DumpedLicense> = serde_json::from_slice(content)?;
+
+    Ok(dumped
+        .into_iter()
+        .map(|(attr, license)| {
+            let doc = LicenseDocumentation {
+                attr: attr.clone(),
+                full_name: license.full_name,
+                spdx_id: license.spdx_id,
+                url: license.url,
+                free: license.free,
+                redistributable: license.redistributable,
+            };
+            (attr, doc)
+        })
+        .collect())
+}
+
+fn dump_licenses() -> Result<Vec<u8>, std::io::Error> {
+    let output = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--strict")
+            .arg("--json")
+            .arg("-E")
+            .arg(include_str!("nix/licenses.nix")),
+    )?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    Ok(output.stdout)
+}
@@ -0,0 +1,205 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+/// Fields printed first, the rest follows in alphabetical order
+const FIELD_ORDER: &[&str] = &["name", "shortName", "email", "github", "githubId", "matrix"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MaintainerDocumentation {
+    attr: String,
+    fields: Vec<(String, String)>,
+}
+
+impl MaintainerDocumentation {
+    pub fn name(&self) -> String {
+        self.attr.clone()
+    }
+
+    pub(crate) fn text(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(_, value)| value.as_str())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}\n", self.attr.blue().bold());
+        for (name, value) in &self.fields {
+            output.push_str(&format!("{}: {}\n", name.green(), value));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// `lib.maintainers` and `lib.teams` of nixpkgs
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MaintainersDatabase {
+    pub maintainers: HashMap<String, MaintainerDocumentation>,
+}
+
+impl Default for MaintainersDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MaintainersDatabase {
+    pub fn new() -> Self {
+        Self {
+            maintainers: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for MaintainersDatabase {}
+
+impl DocSource for MaintainersDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.maintainers.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.maintainers
+            .iter()
+            .filter(|(key, _)| {
+                // `jtojnar` finds `maintainers.jtojnar`
+                let short = key.split_once('.').map_or(key.as_str(), |(_, s)| s);
+                starts_with_insensitive_ascii(key.as_bytes(), query)
+                    || starts_with_insensitive_ascii(short.as_bytes(), query)
+            })
+            .map(|(_, value)| DocEntry::MaintainerDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.maintainers
+            .iter()
+            .filter(|(key, value)| {
+                contains_insensitive_ascii(key.as_bytes(), query)
+                    || value
+                        .fields
+                        .iter()
+                        .any(|(_, field)| contains_insensitive_ascii(field.as_bytes(), query))
+            })
+            .map(|(_, value)| DocEntry::MaintainerDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.maintainers
+            .values()
+            .map(|value| DocEntry::MaintainerDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let dumped: Value = serde_json::from_slice(&dump_maintainers()?)?;
+        let mut new = HashMap::new();
+        for set in ["maintainers", "teams"] {
+            if let Some(entries) = dumped.get(set).and_then(Value::as_object) {
+                for (name, entry) in entries {
+                    let attr = format!("{}.{}", set, name);
+                    let doc = MaintainerDocumentation {
+                        attr: attr.clone(),
+                        fields: fields_of(entry),
+                    };
+                    new.insert(attr, doc);
+                }
+            }
+        }
+        let old = std::mem::replace(&mut self.maintainers, new);
+
+        Ok(!self.maintainers.keys().eq(old.keys()))
+    }
+}
+
+fn format_field(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(format_field)
+            .collect::<Vec<_>>()
+            .join(", "),
+        // gpg keys are `{ fingerprint = "..."; }`
+        Value::Object(object) => object
+            .values()
+            .map(format_field)
+            .collect::<Vec<_>>()
+            .join(" "),
+        other => other.to_string(),
+    }
+}
+
+fn fields_of(entry: &Value) -> Vec<(String, String)> {
+    let mut fields = entry
+        .as_object()
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (name.clone(), format_field(value)))
+        .filter(|(_, value)| !value.is_empty())
+        .collect::<Vec<_>>();
+    fields.sort_by_key(|(name, _)| {
+        let position = FIELD_ORDER.iter().position(|field| field == name);
+        (position.unwrap_or(FIELD_ORDER.len()), name.clone())
+    });
+    fields
+}
+
+fn dump_maintainers() -> Result<Vec<u8>, std::io::Error> {
+    let output = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--strict")
+            .arg("--json")
+            .arg("-E")
+            .arg(include_str!("nix/maintainers.nix")),
+    )?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_fields_of() {
+    let entry: Value = serde_json::from_str(
+        r#"{
+            "keys": [{ "fingerprint": "1234 5678" }],
+            "github": "jtojnar",
+            "githubId": 705123,
+            "email": "jtojnar@gmail.com",
+            "name": "Jan Tojnar",
+            "matrix": ""
+        }"#,
+    )
+    .unwrap();
+    let fields = fields_of(&entry);
+    let names = fields
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["name", "email", "github", "githubId", "keys"]);
+    assert_eq!(fields[3].1, "705123");
+    assert_eq!(fields[4].1, "1234 5678");
+}
@@ -0,0 +1,698 @@
+use crate::{
+    comments_docsource::{
+        find_nix_path_entry,
+        get_nixpkgs_root,
+    },
+    contains_insensitive_ascii,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+/// Chapters of the nixpkgs manual indexed as their own source
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum ManualSectionType {
+    Stdenv,
+    BuildHelpers,
+    LanguageFrameworks,
+    Images,
+    NixosTests,
+}
+
+impl ManualSectionType {
+    pub fn source_name(&self) -> &'static str {
+        match self {
+            ManualSectionType::Stdenv => "Nixpkgs stdenv",
+            ManualSectionType::BuildHelpers => "Nixpkgs Fetchers and Builders",
+            ManualSectionType::LanguageFrameworks => "Nixpkgs Language Frameworks",
+            ManualSectionType::Images => "Image Builders",
+            ManualSectionType::NixosTests => "NixOS Tests",
+        }
+    }
+
+    /// Files or directories below `<nixpkgs>`
+    fn paths(&self) -> &'static [&'static str] {
+        match self {
+            ManualSectionType::Stdenv => &["doc/stdenv", "doc/hooks"],
+            ManualSectionType::BuildHelpers => &[
+                "doc/build-helpers/fetchers.chapter.md",
+                "doc/build-helpers/trivial-build-helpers.chapter.md",
+            ],
+            ManualSectionType::LanguageFrameworks => &["doc/languages-frameworks"],
+            ManualSectionType::Images => &["doc/build-helpers/images"],
+            ManualSectionType::NixosTests => &[
+                "nixos/doc/manual/development/writing-nixos-tests.section.md",
+                "nixos/doc/manual/development/running-nixos-tests.section.md",
+                "nixos/doc/manual/development/running-nixos-tests-interactively.section.md",
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ManualDocumentation {
+    title: String,
+    parents: Vec<String>,
+    aliases: Vec<String>,
+    body: String,
+    arguments: Vec<(String, String)>,
+    related: Vec<String>,
+}
+
+impl ManualDocumentation {
+    pub fn name(&self) -> String {
+        self.aliases.first().unwrap_or(&self.title).clone()
+    }
+
+    pub(crate) fn text(&self) -> String {
+        std::iter::once(self.body.as_str())
+            .chain(
+                self.arguments
+                    .iter()
+                    .map(|(_, description)| description.as_str()),
+            )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}\n", self.title.blue().bold());
+        if !self.parents.is_empty() {
+            output.push_str(&format!("{}\n", self.parents.join(" > ").white()));
+        }
+        if !self.body.is_empty() {
+            output.push_str(&format!("{}\n", self.body));
+        }
+        if !self.arguments.is_empty() {
+            output.push_str("\nArguments:\n");
+            for (name, description) in &self.arguments {
+                output.push_str(&format!("  {}: {}\n", name.green(), description));
+            }
+        }
+        if !self.related.is_empty() {
+            output.push_str(&format!(
+                "\nSee also: {}\n",
+                self.related.join(", ").green()
+            ));
+        }
+        output.push('\n');
+        output
+    }
+
+    fn keys(&self) -> impl Iterator<Item = &String> {
+        std::iter::once(&self.title).chain(self.aliases.iter())
+    }
+
+    /// Headings like `Usage` repeat across chapters, so they're stored under their full path
+    fn path(&self) -> String {
+        self.parents
+            .iter()
+            .chain(std::iter::once(&self.title))
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" > ")
+    }
+}
+
+/// Sections of nixpkgs manual chapters, keyed by their heading path
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ManualDatabase {
+    pub typ: ManualSectionType,
+    pub sections: HashMap<String, ManualDocumentation>,
+}
+
+impl ManualDatabase {
+    pub fn new(typ: ManualSectionType) -> Self {
+        Self {
+            typ,
+            sections: HashMap::new(),
+        }
+    }
+
+    fn search_with(&self, matches: impl Fn(&[u8]) -> bool) -> Vec<DocEntry> {
+        self.sections
+            .values()
+            .filter(|section| section.keys().any(|key| matches(key.as_bytes())))
+            .map(|section| DocEntry::ManualDoc(self.typ, section.clone()))
+            .collect()
+    }
+}
+
+impl Cache for ManualDatabase {}
+
+impl DocSource for ManualDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.sections.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.search_with(|key| starts_with_insensitive_ascii(key, query))
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.search_with(|key| contains_insensitive_ascii(key, query))
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.sections
+            .values()
+            .map(|value| DocEntry::ManualDoc(self.typ, value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let nixpkgs_root = get_nixpkgs_root();
+        let mut new = HashMap::new();
+        for path in self.typ.paths() {
+            for file in walkdir::WalkDir::new(nixpkgs_root.join(path))
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("md"))
+            {
+                let content = std::fs::read_to_string(file.path()).map_err(|e| Errors::FileIo {
+                    err: e,
+                    filename: file.path().display().to_string(),
+                })?;
+                for section in parse_markdown_sections(&content) {
+                    new.insert(section.path(), section);
+                }
+            }
+        }
+        if self.typ == ManualSectionType::Images {
+            // nixos-generators is optional, its formats are only listed when it's in the NIX_PATH
+            if let Some(root) = find_nix_path_entry("nixos-generators") {
+                for format in nixos_generators_formats(&root.join("formats")) {
+                    new.insert(format.path(), format);
+                }
+            }
+        }
+        if self.typ == ManualSectionType::NixosTests {
+            // the methods of machine objects are documented in the test driver's docstrings
+            for file in walkdir::WalkDir::new(nixpkgs_root.join("nixos/lib/test-driver"))
+                .into_iter()
+                .filter_map(Result::ok)
+                .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("py"))
+            {
+                if let Ok(content) = std::fs::read_to_string(file.path()) {
+                    for method in machine_methods(&content) {
+                        new.insert(method.path(), method);
+                    }
+                }
+            }
+        }
+        let old = std::mem::replace(&mut self.sections, new);
+
+        Ok(!self.sections.keys().eq(old.keys()))
+    }
+}
+
+/// `The install phase {#ssec-install-phase}` is titled `The install phase`
+fn heading_title(heading: &str) -> String {
+    heading
+        .split(" {#")
+        .next()
+        .unwrap_or(heading)
+        .replace('`', "")
+        .trim()
+        .to_string()
+}
+
+/// Phases are referred to by their variable, `The install phase` is `installPhase`, and
+/// ``Building Go modules with `buildGoModule` `` is found as `buildGoModule`
+fn heading_aliases(heading: &str, title: &str) -> Vec<String> {
+    let phase = title
+        .strip_prefix("The ")
+        .and_then(|t| t.strip_suffix(" phase"))
+        .filter(|t| !t.contains(' '));
+    let code = heading.split('`').skip(1).step_by(2).collect::<Vec<_>>();
+    match (phase, code.as_slice()) {
+        (Some("distribution"), _) => vec!["distPhase".to_string()],
+        (Some(phase), _) => vec![format!("{}Phase", phase)],
+        (None, [code]) if *code != title => vec![code.to_string()],
+        _ => Vec::new(),
+    }
+}
+
+/// Headings of the sections listing the arguments of a builder
+fn is_arguments_heading(title: &str) -> bool {
+    let title = title.to_ascii_lowercase();
+    title.starts_with("attributes")
+        || title.contains("parameters")
+        || title.contains("arguments")
+        || title == "inputs"
+}
+
+/// `` `name` (String) `` starts the definition of an argument
+fn argument_name(line: &str) -> Option<String> {
+    let rest = line.strip_prefix('`')?;
+    let (name, rest) = rest.split_once('`')?;
+    if !rest.is_empty() && !rest.starts_with(" (") {
+        return None;
+    }
+    Some(format!("{}{}", name, rest))
+}
+
+/// Pulls the definition lists documenting arguments out of a section body, along with
+/// `` - `name`: description `` bullet points if `bullets` is set
+fn split_arguments<'a>(body: &[&'a str], bullets: bool) -> (Vec<&'a str>, Vec<(String, String)>) {
+    let mut text = Vec::new();
+    let mut arguments = Vec::new();
+    let mut i = 0;
+    while i < body.len() {
+        let bullet = ["- `", "* `"]
+            .iter()
+            .find_map(|prefix| body[i].strip_prefix(prefix))
+            .and_then(|rest| rest.split_once('`'))
+            .filter(|_| bullets);
+        if let Some((name, rest)) = bullet {
+            let mut end = i + 1;
+            while end < body.len() && body[end].starts_with("  ") {
+                end += 1;
+            }
+            let description = std::iter::once(rest.trim_start_matches(':'))
+                .chain(body[i + 1..end].iter().copied())
+                .map(|line| line.trim())
+                .filter(|line| !line.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ");
+            arguments.push((name.to_string(), description));
+            i = end;
+            continue;
+        }
+
+        let definition = body[i + 1..]
+            .iter()
+            .position(|line| !line.trim().is_empty())
+            .map(|offset| i + 1 + offset)
+            .filter(|next| body[*next].starts_with(": "));
+        match (argument_name(body[i]), definition) {
+            (Some(name), Some(start)) => {
+                let mut end = start + 1;
+                // the description continues as long as its lines are indented
+                while end < body.len()
+                    && (body[end].starts_with("  ")
+                        || (body[end].trim().is_empty()
+                            && body.get(end + 1).is_some_and(|l| l.starts_with("  "))))
+                {
+                    end += 1;
+                }
+                let description = std::iter::once(&body[start][2..])
+                    .chain(body[start + 1..end].iter().copied())
+                    .map(|line| line.trim())
+                    .filter(|line| !line.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                arguments.push((name, description));
+                i = end;
+            }
+            _ => {
+                text.push(body[i]);
+                i += 1;
+            }
+        }
+    }
+    (text, arguments)
+}
+
+/// Every file of nixos-generators' `formats` directory is a format of `nixos-generate -f`
+fn nixos_generators_formats(formats_dir: &Path) -> Vec<ManualDocumentation> {
+    let Ok(entries) = std::fs::read_dir(formats_dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let path = entry.path();
+            let format = path.file_stem()?.to_str()?.to_string();
+            let content = std::fs::read_to_string(&path).ok()?;
+            // `fileExtension = ".qcow2";`
+            let extension = content
+                .split("fileExtension")
+                .nth(1)
+                .and_then(|rest| rest.split('"').nth(1));
+            let mut body = format!("Build with `nixos-generate -f {}`", format);
+            if let Some(extension) = extension {
+                body.push_str(&format!(", produces a `{}` file", extension));
+            }
+            body.push('.');
+            Some(ManualDocumentation {
+                title: format.clone(),
+                parents: vec!["nixos-generators".to_string()],
+                aliases: vec![format!("nixos-generators.{}", format)],
+                body,
+                arguments: Vec::new(),
+                related: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Public methods of the test driver's `Machine` class, with their signature and docstring
+fn machine_methods(content: &str) -> Vec<ManualDocumentation> {
+    let lines = content.lines().collect::<Vec<_>>();
+    let mut methods = Vec::new();
+    let mut in_machine = false;
+    let mut i = 0;
+    while i < lines.len() {
+        let line = lines[i];
+        i += 1;
+        if let Some(class) = line.strip_prefix("class ") {
+            in_machine = class.starts_with("Machine") && !class.starts_with("MachineConfig");
+            continue;
+        }
+        let Some(definition) = line.strip_prefix("    def ").filter(|_| in_machine) else {
+            continue;
+        };
+        if definition.starts_with('_') {
+            continue;
+        }
+
+        // signatures can span several lines
+        let mut signature = vec![definition.trim()];
+        while !signature.last().is_some_and(|l| l.ends_with(':')) && i < lines.len() {
+            signature.push(lines[i].trim());
+            i += 1;
+        }
+        let signature = signature
+            .join(" ")
+            .replace("( ", "(")
+            .replace(", )", ")")
+            .replace(" )", ")")
+            .replace("(self, ", "(")
+            .replace("(self)", "()")
+            .trim_end_matches(':')
+            .to_string();
+        let name = signature.split('(').next().unwrap_or_default().to_string();
+
+        let mut docstring = Vec::new();
+        if let Some(first) = lines.get(i).and_then(|l| l.trim().strip_prefix("\"\"\"")) {
+            i += 1;
+            match first.strip_suffix("\"\"\"") {
+                Some(single) => docstring.push(single),
+                None => {
+                    docstring.push(first);
+                    while i < lines.len() && !lines[i].contains("\"\"\"") {
+                        docstring.push(lines[i].get(8..).unwrap_or_else(|| lines[i].trim()));
+                        i += 1;
+                    }
+                    if let Some(last) = lines.get(i) {
+                        docstring.push(last.trim().trim_end_matches("\"\"\""));
+                    }
+                    i += 1;
+                }
+            }
+        }
+
+        methods.push(ManualDocumentation {
+            title: format!("machine.{}", name),
+            parents: vec!["Machine objects".to_string()],
+            aliases: vec![name],
+            body: format!("machine.{}\n\n{}", signature, docstring.join("\n").trim()),
+            arguments: Vec::new(),
+            related: Vec::new(),
+        });
+    }
+    methods
+}
+
+/// A markdown heading along with the lines up to the next one
+struct Heading<'a> {
+    level: usize,
+    raw: &'a str,
+    title: String,
+    parents: Vec<String>,
+    body: Vec<&'a str>,
+}
+
+/// Splits a markdown chapter into its headings, each with the text up to the next heading and the
+/// identifier-like titles of its subsections
+fn parse_markdown_sections(content: &str) -> Vec<ManualDocumentation> {
+    let mut headings: Vec<Heading> = Vec::new();
+    let mut parents: Vec<(usize, String)> = Vec::new();
+    let mut in_code = false;
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code = !in_code;
+        }
+        let level = line.chars().take_while(|c| *c == '#').count();
+        if !in_code && level > 0 && line[level..].starts_with(' ') {
+            let raw = &line[level..];
+            let title = heading_title(raw);
+            while parents.last().is_some_and(|(parent, _)| *parent >= level) {
+                parents.pop();
+            }
+            headings.push(Heading {
+                level,
+                raw,
+                title: title.clone(),
+                parents: parents.iter().map(|(_, t)| t.clone()).collect(),
+                body: Vec::new(),
+            });
+            parents.push((level, title));
+        } else if let Some(heading) = headings.last_mut() {
+            // `:::` fences admonitions and examples
+            if !line.starts_with(":::") {
+                heading.body.push(line);
+            }
+        }
+    }
+
+    let mut sections = headings
+        .iter()
+        .enumerate()
+        .map(|(i, heading)| {
+            let subsections = headings[i + 1..]
+                .iter()
+                .take_while(|sub| sub.level > heading.level)
+                .filter(|sub| !sub.title.contains(' '));
+            let related = subsections.clone().map(|sub| sub.title.clone()).collect();
+            let listing_arguments = is_arguments_heading(&heading.title);
+            let (body, mut arguments) = split_arguments(&heading.body, listing_arguments);
+            if listing_arguments {
+                // some chapters give every argument its own heading instead
+                arguments.extend(subsections.map(|sub| {
+                    let paragraph = sub
+                        .body
+                        .iter()
+                        .skip_while(|line| line.trim().is_empty())
+                        .take_while(|line| !line.trim().is_empty())
+                        .map(|line| line.trim())
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    (sub.title.clone(), paragraph)
+                }));
+            }
+            let mut aliases = heading_aliases(heading.raw, &heading.title);
+            // `## buildImage` of the `# pkgs.dockerTools` chapter is `dockerTools.buildImage`
+            if let Some(chapter) = heading
+                .parents
+                .first()
+                .and_then(|p| p.strip_prefix("pkgs."))
+            {
+                if !heading.title.contains(' ') {
+                    aliases.push(format!("{}.{}", chapter, heading.title));
+                }
+            }
+            ManualDocumentation {
+                title: heading.title.clone(),
+                parents: heading.parents.clone(),
+                aliases,
+                body: body.join("\n").trim().to_string(),
+                arguments,
+                related,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // builders document their arguments in an `Inputs` subsection
+    for i in 0..sections.len() {
+        if !matches!(sections[i].title.as_str(), "Inputs" | "Arguments") {
+            continue;
+        }
+        let parent_path = sections[i].parents.join(" > ");
+        if let Some(parent) = (0..i).rev().find(|j| sections[*j].path() == parent_path) {
+            let arguments = std::mem::take(&mut sections[i].arguments);
+            sections[parent].arguments.extend(arguments);
+        }
+    }
+
+    sections
+}
+
+#[test]
+fn test_parse_markdown_sections() {
+    let content = "
+### The install phase {#ssec-install-phase}
+
+The install phase is responsible for installing the package.
+
+#### Variables controlling the install phase {#variables-controlling-the-install-phase}
+
+##### `dontInstall` {#var-stdenv-dontInstall}
+
+Set to true to skip the install phase.
+
+```bash
+# not a heading
+```
+
+### The distribution phase {#ssec-distribution-phase}
+";
+    let sections = parse_markdown_sections(content);
+    assert_eq!(sections.len(), 4);
+    assert_eq!(sections[0].name(), "installPhase");
+    assert_eq!(
+        sections[0].body,
+        "The install phase is responsible for installing the package."
+    );
+    assert_eq!(sections[0].related, vec!["dontInstall"]);
+    assert_eq!(sections[2].name(), "dontInstall");
+    assert_eq!(
+        sections[2].path(),
+        "The install phase > Variables controlling the install phase > dontInstall"
+    );
+    assert!(sections[2].body.contains("# not a heading"));
+    assert_eq!(sections[3].name(), "distPhase");
+}
+
+#[test]
+fn test_split_arguments() {
+    let content = "
+## `writeShellApplication` {#trivial-builder-writeShellApplication}
+
+Writes an executable shell script.
+
+### Inputs
+
+`name` (String)
+
+: The name used in the Nix store path
+  and as the name of the script.
+
+`text` (String)
+
+: The shell script's text.
+
+Other text.
+";
+    let sections = parse_markdown_sections(content);
+    assert_eq!(sections[0].name(), "writeShellApplication");
+    assert_eq!(
+        sections[0].arguments,
+        vec![
+            (
+                "name (String)".to_string(),
+                "The name used in the Nix store path and as the name of the script.".to_string()
+            ),
+            (
+                "text (String)".to_string(),
+                "The shell script's text.".to_string()
+            ),
+        ]
+    );
+    assert!(sections[1].arguments.is_empty());
+    assert_eq!(sections[1].body, "Other text.");
+}
+
+#[test]
+fn test_builder_arguments() {
+    let content = "
+## Building Go modules with `buildGoModule` {#ssec-language-go}
+
+### Attributes of `buildGoModule` {#buildgomodule-parameters}
+
+#### `vendorHash` {#var-go-vendorHash}
+
+Hash of the output of the intermediate fetcher derivation.
+
+More details.
+
+##### `buildPythonPackage` parameters {#buildpythonpackage-parameters}
+
+* `catchConflicts` If `true`, abort package build if a package name
+  appears more than once in dependency tree.
+";
+    let sections = parse_markdown_sections(content);
+    assert_eq!(sections[0].name(), "buildGoModule");
+    assert_eq!(sections[1].name(), "buildGoModule");
+    assert_eq!(
+        sections[1].arguments[0],
+        (
+            "vendorHash".to_string(),
+            "Hash of the output of the intermediate fetcher derivation.".to_string()
+        )
+    );
+    assert_eq!(
+        sections[3].arguments,
+        vec![(
+            "catchConflicts".to_string(),
+            "If `true`, abort package build if a package name appears more than once in dependency tree."
+                .to_string()
+        )]
+    );
+}
+
+#[test]
+fn test_chapter_aliases() {
+    let content = "
+# pkgs.dockerTools {#sec-pkgs-dockerTools}
+
+## buildImage {#ssec-pkgs-dockerTools-buildImage}
+
+### Inputs {#ssec-pkgs-dockerTools-buildImage-inputs}
+
+`name` (String)
+
+: The name of the generated image.
+";
+    let sections = parse_markdown_sections(content);
+    assert_eq!(sections[1].name(), "dockerTools.buildImage");
+    assert_eq!(sections[1].arguments.len(), 1);
+}
+
+#[test]
+fn test_machine_methods() {
+    let content = r#"
+class MachineConfig:
+    def ignored(self) -> None:
+        pass
+
+
+class Machine:
+    def _private(self) -> None:
+        pass
+
+    def wait_for_unit(
+        self, unit: str, user: str | None = None, timeout: int = 900
+    ) -> None:
+        """
+        Wait for a systemd unit to get into "active" state.
+        Throws exceptions on "failed" and "inactive" states.
+        """
+        pass
+
+    def start(self) -> None:
+        """Start the machine."""
+"#;
+    let methods = machine_methods(content);
+    assert_eq!(methods.len(), 2);
+    assert_eq!(methods[0].title, "machine.wait_for_unit");
+    assert_eq!(
+        methods[0].body,
+        "machine.wait_for_unit(unit: str, user: str | None = None, timeout: int = 900) -> None\n\n\
+        Wait for a systemd unit to get into \"active\" state.\n\
+        Throws exceptions on \"failed\" and \"inactive\" states."
+    );
+    assert_eq!(
+        methods[1].body,
+        "machine.start() -> None\n\nStart the machine."
+    );
+}
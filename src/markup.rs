@@ -0,0 +1,152 @@
+use colored::*;
+use regex::{
+    Captures,
+    Regex,
+};
+use std::sync::OnceLock;
+
+/// Patterns of the inline markup, compiled once
+struct Inline {
+    code: Regex,
+    link: Regex,
+    bold: Regex,
+    emphasis: Regex,
+    docbook_link: Regex,
+    docbook_code: Regex,
+    docbook_para: Regex,
+}
+
+fn inline() -> &'static Inline {
+    static INLINE: OnceLock<Inline> = OnceLock::new();
+    INLINE.get_or_init(|| {
+        let regex = |pattern| Regex::new(pattern).unwrap();
+        Inline {
+            code: regex(r"(?:\{(option|command|file|var|env|manpage)\})?`([^`]+)`"),
+            link: regex(r"\[([^\]]*)\]\(([^)\s]+)\)"),
+            bold: regex(r"\*\*([^*]+)\*\*"),
+            emphasis: regex(r"\*([^*\s][^*]*)\*"),
+            docbook_link: regex(
+                r#"<link xlink:href="([^"]+)"\s*/>|<link xlink:href="([^"]+)">([^<]*)</link>"#,
+            ),
+            docbook_code: regex(
+                r"<(?:literal|option|command|filename|varname|envar)>([^<]*)</[a-z]+>",
+            ),
+            docbook_para: regex(r"</?para>"),
+        }
+    })
+}
+
+/// Turns the Markdown, with the roles of the NixOS manual, and DocBook of descriptions into styled
+/// terminal text: `` {option}`services.foo.enable` `` is printed as the bare option name
+pub fn render(text: &str) -> String {
+    let mut output = String::new();
+    let mut in_code_block = false;
+    for line in text.trim().lines() {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            output.push_str(&format!("  {}\n", line.white()));
+        } else if let Some(admonition) = trimmed.strip_prefix(":::") {
+            // `::: {.note}` opens a block and a bare `:::` closes it
+            let kind = admonition
+                .trim()
+                .trim_start_matches("{.")
+                .trim_end_matches('}');
+            if !kind.is_empty() {
+                let mut chars = kind.chars();
+                let title = chars
+                    .next()
+                    .map(|c| c.to_uppercase().chain(chars).collect::<String>())
+                    .unwrap_or_default();
+                output.push_str(&format!("{}\n", format!("{}:", title).bold()));
+            }
+        } else {
+            output.push_str(&render_inline(line));
+            output.push('\n');
+        }
+    }
+    output.trim_end().to_string()
+}
+
+/// Code spans, roles included, are styled as they are and the text around them for its markup
+fn render_inline(line: &str) -> String {
+    let inline = inline();
+    let mut output = String::new();
+    let mut last = 0;
+    for caps in inline.code.captures_iter(line) {
+        let span = caps.get(0).unwrap();
+        output.push_str(&render_text(&line[last..span.start()]));
+        let code = &caps[2];
+        output.push_str(&match caps.get(1).map(|role| role.as_str()) {
+            Some("file") => code.underline().to_string(),
+            Some("manpage") => code.bold().to_string(),
+            _ => code.white().to_string(),
+        });
+        last = span.end();
+    }
+    output.push_str(&render_text(&line[last..]));
+    output
+}
+
+fn render_text(text: &str) -> String {
+    let inline = inline();
+    let text = inline.docbook_para.replace_all(text, "");
+    let text = inline.docbook_link.replace_all(&text, |caps: &Captures| {
+        match (caps.get(1), caps.get(2), caps.get(3)) {
+            (_, Some(url), Some(label)) if !label.as_str().is_empty() => {
+                format!("{} ({})", label.as_str(), url.as_str().underline())
+            }
+            (Some(url), _, _) | (_, Some(url), _) => url.as_str().underline().to_string(),
+            _ => String::new(),
+        }
+    });
+    let text = inline
+        .docbook_code
+        .replace_all(&text, |caps: &Captures| caps[1].white().to_string());
+    let text = inline.link.replace_all(&text, |caps: &Captures| {
+        let (label, url) = (&caps[1], &caps[2]);
+        match url.strip_prefix("#opt-") {
+            // links to other options of the manual only need their name
+            Some(option) if label.is_empty() => option.white().to_string(),
+            Some(_) => label.to_string(),
+            None if label.is_empty() || label == url => url.underline().to_string(),
+            None => format!("{} ({})", label, url.underline()),
+        }
+    });
+    let text = inline
+        .bold
+        .replace_all(&text, |caps: &Captures| caps[1].bold().to_string());
+    let text = inline
+        .emphasis
+        .replace_all(&text, |caps: &Captures| caps[1].italic().to_string());
+    text.into_owned()
+}
+
+#[test]
+fn test_render() {
+    colored::control::set_override(false);
+    assert_eq!(
+        render("Whether to enable {option}`services.foo`, see [the wiki](https://wiki.nixos.org)."),
+        "Whether to enable services.foo, see the wiki (https://wiki.nixos.org)."
+    );
+    assert_eq!(
+        render("Same as [](#opt-services.bar.enable)."),
+        "Same as services.bar.enable."
+    );
+    assert_eq!(
+        render("Use <literal>pkgs.foo</literal>.\n\n::: {.note}\nIt's **slow**.\n:::"),
+        "Use pkgs.foo.\n\nNote:\nIt's slow."
+    );
+    assert_eq!(
+        render("Example:\n```nix\n{ a = 1; }\n```"),
+        "Example:\n  { a = 1; }"
+    );
+    assert_eq!(render("users.users.<name>.home"), "users.users.<name>.home");
+    assert_eq!(
+        render("Matches `*.nix` or `*.sh`."),
+        "Matches *.nix or *.sh."
+    );
+}
@@ -0,0 +1,36 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <colmena>;
+
+  colmena =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/zhaofengli/colmena/archive/main.tar.gz";
+
+  hiveOptions = let
+    options = import (colmena + "/src/nix/hive/options.nix");
+  in
+    if builtins.isFunction options
+    then options {inherit lib;}
+    else options;
+
+  eval = import (pkgs.path + "/nixos/lib/eval-config.nix") {
+    modules = [
+      hiveOptions.deploymentOptions
+      {_module.args.name = "node";}
+    ];
+  };
+
+  # the module system pulls in every NixOS option, only keep the ones colmena declares
+  declaredInColmena = opt: lib.any (decl: lib.hasPrefix (toString colmena) (toString decl)) opt.declarations;
+
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+    documented = declaredInColmena;
+  };
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
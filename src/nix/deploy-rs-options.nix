@@ -0,0 +1,49 @@
+let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <deploy-rs>;
+
+  deploy-rs =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/serokell/deploy-rs/archive/master.tar.gz";
+
+  # deploy-rs settings aren't module options, they're described by a JSON schema
+  schema = builtins.fromJSON (builtins.readFile (deploy-rs + "/interface.json"));
+  inherit (schema) definitions;
+
+  propertiesOf = def:
+    lib.foldl' (acc: d: acc // (d.properties or {})) (def.properties or {}) (def.allOf or []);
+
+  typeOf = prop: let
+    type = prop.type or "any";
+  in
+    if lib.isList type
+    then lib.concatStringsSep " or " type
+    else type;
+
+  settingsOf = prefix: def:
+    lib.mapAttrsToList (name: prop: rec {
+      loc = lib.splitString "." prefix ++ [name];
+      name = lib.showOption loc;
+      value =
+        {
+          inherit loc;
+          description = prop.description or "";
+          type = typeOf prop;
+        }
+        // lib.optionalAttrs (prop ? default) {
+          inherit (prop) default;
+        };
+    })
+    (propertiesOf def);
+
+  settings =
+    settingsOf "deploy" definitions.genericSettings
+    ++ settingsOf "deploy.nodes.<name>" definitions.genericSettings
+    ++ settingsOf "deploy.nodes.<name>" definitions.node
+    ++ settingsOf "deploy.nodes.<name>.profiles.<name>" definitions.genericSettings
+    ++ settingsOf "deploy.nodes.<name>.profiles.<name>" definitions.profile;
+in
+  pkgs.writeText "options.json" (builtins.toJSON (lib.listToAttrs settings))
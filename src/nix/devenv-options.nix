@@ -0,0 +1,28 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <devenv>;
+
+  devenv =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/cachix/devenv/archive/main.tar.gz";
+
+  eval = lib.evalModules {
+    modules = [
+      (devenv + "/src/modules/top-level.nix")
+      {_module.check = false;}
+    ];
+    specialArgs = {
+      inherit pkgs;
+      inputs = {};
+    };
+  };
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+  };
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
@@ -0,0 +1,59 @@
+{
+  flake,
+  optionsDoc,
+}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  source = builtins.getFlake flake;
+
+  # `default` usually aliases another module, importing both would declare its options twice
+  modulesOf = modules:
+    if modules ? default
+    then [modules.default]
+    else lib.attrValues modules;
+
+  nixosModules =
+    modulesOf (source.nixosModules or {})
+    ++ lib.optional (source ? nixosModule) source.nixosModule;
+  hmModules =
+    modulesOf (source.homeManagerModules or source.homeModules or {})
+    ++ lib.optional (source ? homeManagerModule) source.homeManagerModule;
+
+  # the module systems pull in every upstream option, only keep the ones the flake declares
+  declaredInFlake = opt: lib.any (decl: lib.hasPrefix (toString source.outPath) (toString decl)) opt.declarations;
+
+  flakeOptionsDoc = options:
+    optionsDoc {
+      inherit pkgs options;
+      documented = declaredInFlake;
+    };
+
+  nixos = (import (pkgs.path + "/nixos/lib/eval-config.nix") {modules = nixosModules;}).options;
+
+  # home-manager modules can only be evaluated with home-manager itself
+  hmAttempt = builtins.tryEval <home-manager>;
+
+  hm =
+    (import (hmAttempt.value + "/modules") {
+      inherit pkgs;
+      check = false;
+      configuration = {
+        imports = hmModules;
+        home.stateVersion = lib.trivial.release;
+        home.username = "manix";
+        home.homeDirectory = "/homeless-shelter";
+      };
+    })
+    .options;
+
+  docs =
+    lib.optional (nixosModules != []) (flakeOptionsDoc nixos)
+    ++ lib.optional (hmModules != [] && hmAttempt.success) (flakeOptionsDoc hm);
+in
+  pkgs.runCommandLocal "options.json" {
+    nativeBuildInputs = [pkgs.jq];
+    files = map (doc: "${doc}/share/doc/nixos/options.json") docs;
+  } ''
+    jq -n '[inputs] | add // {}' $files < /dev/null > $out
+  ''
@@ -0,0 +1,38 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <flake-parts>;
+
+  flake-parts =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/hercules-ci/flake-parts/archive/main.tar.gz";
+
+  flake-parts-lib = import (flake-parts + "/lib.nix") {inherit lib;};
+
+  eval = flake-parts-lib.evalFlakeModule {
+    inputs.self = {
+      outPath = "/manix";
+      inputs = {};
+    };
+  } {systems = [builtins.currentSystem];};
+
+  # perSystem is a deferred module, its options only show up under allSystems.<name>
+  perSystemLoc = loc: lib.take 2 loc == ["allSystems" "<name>"];
+
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+    transform = opt:
+      opt
+      // lib.optionalAttrs (perSystemLoc opt.loc) rec {
+        loc = ["perSystem"] ++ lib.drop 2 opt.loc;
+        name = lib.showOption loc;
+        isVisible = true;
+        isInternal = false;
+      };
+  };
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
@@ -0,0 +1,20 @@
+{
+  username,
+  hostname,
+  configDir,
+}: let
+  configurations = (builtins.getFlake configDir).homeConfigurations;
+  configFile = let
+    env = builtins.getEnv "HOME_MANAGER_CONFIG";
+  in
+    if env != ""
+    then env
+    else configDir + "/home.nix";
+in
+  if builtins.pathExists (configDir + "/flake.nix")
+  then configurations."${username}@${hostname}" or configurations.${username}
+  else
+    import <home-manager/modules> {
+      configuration = /. + configFile;
+      pkgs = import <nixpkgs> {};
+    }
@@ -0,0 +1,11 @@
+let
+  lib = import <nixpkgs/lib>;
+in
+  lib.mapAttrs (_: license: {
+    fullName = license.fullName or "";
+    spdxId = license.spdxId or null;
+    url = license.url or null;
+    free = license.free or true;
+    redistributable = license.redistributable or false;
+  })
+  (lib.filterAttrs (_: lib.isAttrs) lib.licenses)
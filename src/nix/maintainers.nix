@@ -0,0 +1,14 @@
+let
+  lib = import <nixpkgs/lib>;
+  handleOf = maintainer: maintainer.github or maintainer.name or "";
+in {
+  inherit (lib) maintainers;
+  teams =
+    lib.mapAttrs (_: team: {
+      shortName = team.shortName or "";
+      scope = team.scope or "";
+      members = map handleOf (team.members or []);
+      githubTeams = team.githubTeams or [];
+    })
+    lib.teams;
+}
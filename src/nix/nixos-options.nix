@@ -1,6 +1,10 @@
+{optionsDoc}:
 with import <nixpkgs> {}; let
   eval = import (pkgs.path + "/nixos/lib/eval-config.nix") {modules = [];};
-  opts = (nixosOptionsDoc {options = eval.options;}).optionsJSON;
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+  };
 in
   runCommandLocal "options.json" {inherit opts;}
   "cp $opts/share/doc/nixos/options.json $out"
@@ -0,0 +1,21 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (builtins) currentSystem;
+
+  # nixvim's flake inputs need to be locked, so it can't be fetched as a plain tarball
+  nixvim = builtins.getFlake "github:nix-community/nixvim";
+
+  options =
+    if nixvim.lib ? evalNixvim
+    then
+      (nixvim.lib.evalNixvim {
+        system = currentSystem;
+        modules = [{_module.check = false;}];
+      })
+      .options
+    else (nixvim.legacyPackages.${currentSystem}.makeNixvimWithModule {module = {};}).options;
+
+  opts = optionsDoc {inherit pkgs options;};
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
@@ -0,0 +1,71 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <nur>;
+
+  nurSrc =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/nix-community/NUR/archive/master.tar.gz";
+
+  nur = import nurSrc {
+    nurpkgs = pkgs;
+    inherit pkgs;
+  };
+
+  tryOr = default: value: let
+    result = builtins.tryEval value;
+  in
+    if result.success
+    then result.value
+    else default;
+
+  tryDeepOr = default: value: tryOr default (builtins.deepSeq value value);
+
+  # same shape as `nix-env -qa --json --meta`, limited to the fields manix reads
+  packageInfo = drv: let
+    meta = tryOr {} (drv.meta or {});
+  in {
+    name = tryOr "" (drv.name or "");
+    meta = lib.genAttrs ["description" "homepage" "license" "maintainers" "platforms"] (field: tryDeepOr null (meta.${field} or null));
+  };
+
+  packagesOf = repoName: repo:
+    lib.concatMapAttrs (attr: drv:
+      if tryOr false (lib.isDerivation drv)
+      then {"nur.repos.${repoName}.${attr}" = packageInfo drv;}
+      else {})
+    (tryOr {} (removeAttrs repo ["modules" "overlays" "lib" "hmModules"]));
+
+  modulesOf = repo: tryOr {} (repo.modules or {});
+
+  # the module system pulls in every NixOS option, only keep the ones declared outside nixpkgs
+  optionsOf = repo: let
+    eval = import (pkgs.path + "/nixos/lib/eval-config.nix") {modules = lib.attrValues (modulesOf repo);};
+    declaredInRepo = opt: lib.any (decl: !lib.hasPrefix (toString pkgs.path) (toString decl)) opt.declarations;
+  in
+    optionsDoc {
+      inherit pkgs;
+      inherit (eval) options;
+      documented = declaredInRepo;
+    };
+
+  repos = tryOr {} nur.repos;
+
+  packages = lib.concatMapAttrs (name: repo: tryOr {} (packagesOf name repo)) repos;
+
+  optionDocs =
+    lib.filter (doc: (builtins.tryEval doc.drvPath).success)
+    (map optionsOf (lib.filter (repo: modulesOf repo != {}) (lib.attrValues repos)));
+in
+  pkgs.runCommandLocal "nur-docs" {
+    nativeBuildInputs = [pkgs.jq];
+    packages = builtins.toJSON packages;
+    passAsFile = ["packages"];
+    options = map (doc: "${doc}/share/doc/nixos/options.json") optionDocs;
+  } ''
+    mkdir $out
+    cp $packagesPath $out/packages.json
+    jq -n '[inputs] | add // {}' $options < /dev/null > $out/options.json
+  ''
@@ -0,0 +1,23 @@
+# The options.json nixosOptionsDoc renders for `options`, given to the other expressions as
+# `optionsDoc`. Hidden and internal options are kept with what they are, manix filters them out
+# unless asked for. `documented` picks the options to document, since module systems pull in
+# every upstream option, and `transform` changes them further
+{
+  pkgs,
+  options,
+  documented ? _: true,
+  transform ? opt: opt,
+}:
+(pkgs.nixosOptionsDoc {
+  inherit options;
+  warningsAreErrors = false;
+  transformOptions = opt:
+    transform (opt
+      // {
+        visible = documented opt;
+        internal = false;
+        isVisible = opt.visible != false;
+        isInternal = opt.internal;
+      });
+})
+.optionsJSON
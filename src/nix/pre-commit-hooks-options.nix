@@ -0,0 +1,28 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+  inherit (pkgs) lib;
+
+  attempt = builtins.tryEval <pre-commit-hooks>;
+
+  pre-commit-hooks =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/cachix/git-hooks.nix/archive/master.tar.gz";
+
+  eval = lib.evalModules {
+    modules = [
+      (pre-commit-hooks + "/modules/all-modules.nix")
+      {
+        _module.check = false;
+        _module.args.pkgs = pkgs;
+        tools = import (pre-commit-hooks + "/nix/call-tools.nix") pkgs;
+      }
+    ];
+  };
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+  };
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
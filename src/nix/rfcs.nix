@@ -0,0 +1,8 @@
+let
+  attempt = builtins.tryEval <rfcs>;
+in
+  toString (
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/NixOS/rfcs/archive/master.tar.gz"
+  )
@@ -0,0 +1,18 @@
+{optionsDoc}: let
+  pkgs = import <nixpkgs> {};
+
+  attempt = builtins.tryEval <treefmt-nix>;
+
+  treefmt-nix =
+    if attempt.success
+    then attempt.value
+    else fetchTarball "https://github.com/numtide/treefmt-nix/archive/main.tar.gz";
+
+  eval = (import treefmt-nix).evalModule pkgs {projectRootFile = "flake.nix";};
+  opts = optionsDoc {
+    inherit pkgs;
+    inherit (eval) options;
+  };
+in
+  pkgs.runCommandLocal "options.json" {inherit opts;}
+  "cp $opts/share/doc/nixos/options.json $out"
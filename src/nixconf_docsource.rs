@@ -0,0 +1,193 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NixConfSettingDocumentation {
+    name: String,
+    description: String,
+    setting_type: String,
+    default: String,
+    value: String,
+    aliases: Vec<String>,
+}
+
+impl NixConfSettingDocumentation {
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.description
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!(
+            "# {} ({})\n{}\n",
+            self.name.blue().bold(),
+            "nix.conf".white(),
+            self.description.trim()
+        );
+        output.push_str(&format!("type: {}\n", self.setting_type));
+        output.push_str(&format!("default: {}\n", self.default));
+        if self.value != self.default {
+            output.push_str(&format!("current value: {}\n", self.value));
+        }
+        if !self.aliases.is_empty() {
+            output.push_str(&format!("aliases: {}\n", self.aliases.join(", ")));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// A single setting as printed by `nix config show --json`
+#[derive(Deserialize)]
+struct DumpedSetting {
+    #[serde(default)]
+    description: String,
+    #[serde(default, rename = "defaultValue")]
+    default_value: Value,
+    #[serde(default)]
+    value: Value,
+    #[serde(default)]
+    aliases: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NixConfDatabase {
+    pub settings: HashMap<String, NixConfSettingDocumentation>,
+}
+
+impl Default for NixConfDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NixConfDatabase {
+    pub fn new() -> Self {
+        Self {
+            settings: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for NixConfDatabase {
+    const BUILT_FROM_NIXPKGS: bool = false;
+    const BUILT_FROM_NIX: bool = true;
+}
+
+impl DocSource for NixConfDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.settings.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.settings
+            .iter()
+            .filter(|(key, _)| starts_with_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::NixConfDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.settings
+            .iter()
+            .filter(|(key, _)| contains_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::NixConfDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.settings
+            .values()
+            .map(|value| DocEntry::NixConfDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let new = parse_dumped_settings(&dump_config()?)?;
+        let old = std::mem::replace(&mut self.settings, new);
+
+        Ok(!self.settings.keys().eq(old.keys()))
+    }
+}
+
+/// The settings dump has no type information, so it's inferred from the default value
+fn value_type(value: &Value) -> &'static str {
+    match value {
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "list of strings",
+        Value::Object(_) => "attribute set",
+        Value::Null => "null",
+    }
+}
+
+/// Renders a value the way it would be written in nix.conf
+fn format_value(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(" "),
+        Value::Bool(b) => b.to_string(),
+        other => other.to_string(),
+    }
+}
+
+fn parse_dumped_settings(
+    content: &[u8],
+) -> Result<HashMap<String, NixConfSettingDocumentation>, Errors> {
+    let dumped: HashMap<String, DumpedSetting> = serde_json::from_slice(content)?;
+
+    Ok(dumped
+        .into_iter()
+        .map(|(name, setting)| {
+            let doc = NixConfSettingDocumentation {
+                name: name.clone(),
+                description: setting.description,
+                setting_type: value_type(&setting.default_value).to_string(),
+                default: format_value(&setting.default_value),
+                value: format_value(&setting.value),
+                aliases: setting.aliases,
+            };
+            (name, doc)
+        })
+        .collect())
+}
+
+fn dump_config() -> Result<Vec<u8>, std::io::Error> {
+    // `nix show-config` was renamed to `nix config show`
+    for subcommand in [&["config", "show"][..], &["show-config"][..]] {
+        let output = run_nix(
+            Command::new("nix")
+                .arg("--extra-experimental-features")
+                .arg("nix-command")
+                .args(subcommand)
+                .arg("--json"),
+        )?;
+        if output.status.success() {
+            return Ok(output.stdout);
+        }
+    }
+
+    Err(std::io::Error::other("Failed to dump the Nix settings"))
+}
@@ -0,0 +1,130 @@
+use std::sync::Mutex;
+
+/// The full name of a nixpkgs channel, `unstable` and `24.05` standing for `nixos-unstable` and
+/// `nixos-24.05`. None for names that can't be ones of channels
+pub fn channel_name(channel: &str) -> Option<String> {
+    let valid = !channel.is_empty()
+        && !channel.starts_with('.')
+        && channel
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'));
+    if !valid {
+        return None;
+    }
+    if channel.starts_with("nixos-") || channel.starts_with("nixpkgs-") {
+        Some(channel.to_string())
+    } else {
+        Some(format!("nixos-{}", channel))
+    }
+}
+
+/// What the NIX_PATH takes for a nixpkgs given as a flake ref, a revision of nixpkgs, a path or
+/// anything the NIX_PATH takes already. GitHub refs with a revision become tarballs, which nix
+/// versions without flakes can fetch too
+pub fn nix_path_location(nixpkgs: &str) -> String {
+    let is_revision = |s: &str| s.len() == 40 && s.chars().all(|c| c.is_ascii_hexdigit());
+    if is_revision(nixpkgs) {
+        return format!(
+            "https://github.com/NixOS/nixpkgs/archive/{}.tar.gz",
+            nixpkgs
+        );
+    }
+    if let Some(repo) = nixpkgs.strip_prefix("github:") {
+        let parts = repo.split('/').collect::<Vec<_>>();
+        if let [owner, name, rev] = parts[..] {
+            if !rev.contains(['?', '#']) {
+                return format!(
+                    "https://github.com/{}/{}/archive/{}.tar.gz",
+                    owner, name, rev
+                );
+            }
+        }
+    }
+    let is_path = nixpkgs.starts_with(['/', '.', '~']);
+    let is_nix_path = ["flake:", "channel:", "http://", "https://"]
+        .iter()
+        .any(|prefix| nixpkgs.starts_with(prefix));
+    if is_path || is_nix_path {
+        nixpkgs.to_string()
+    } else {
+        format!("flake:{}", nixpkgs)
+    }
+}
+
+/// Entries the nix commands get ahead of the NIX_PATH manix runs with, which stays as it is
+static NIX_PATH_ENTRIES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Makes `<nixpkgs>` the given one for every nix command run from now on, the rest of the
+/// NIX_PATH staying as it is
+pub fn set_nixpkgs(location: &str) {
+    set_nix_path_entry("nixpkgs", location);
+}
+
+/// Makes `<NAME>` the given one for every nix command run from now on, ahead of the rest of the
+/// NIX_PATH. Setting it again replaces the location set before
+pub fn set_nix_path_entry(name: &str, location: &str) {
+    replace_entry(&mut NIX_PATH_ENTRIES.lock().unwrap(), name, location);
+}
+
+fn replace_entry(entries: &mut Vec<String>, name: &str, location: &str) {
+    let prefix = format!("{}=", name);
+    entries.retain(|entry| !entry.starts_with(&prefix));
+    entries.insert(0, format!("{}{}", prefix, location));
+}
+
+/// The NIX_PATH the nix commands run with, None when it's unset and no entry was set
+pub fn nix_path() -> Option<String> {
+    let inherited = std::env::var("NIX_PATH")
+        .ok()
+        .filter(|path| !path.is_empty());
+    NIX_PATH_ENTRIES
+        .lock()
+        .unwrap()
+        .iter()
+        .cloned()
+        .chain(inherited)
+        .reduce(|nix_path, entry| format!("{}:{}", nix_path, entry))
+}
+
+#[test]
+fn test_nix_path_location() {
+    let rev = "0c19708cf035f50d28eb4b2b8e7a79d4dc52f6bb";
+    assert_eq!(
+        nix_path_location(&format!("github:NixOS/nixpkgs/{}", rev)),
+        format!("https://github.com/NixOS/nixpkgs/archive/{}.tar.gz", rev)
+    );
+    assert_eq!(
+        nix_path_location(rev),
+        format!("https://github.com/NixOS/nixpkgs/archive/{}.tar.gz", rev)
+    );
+    assert_eq!(
+        nix_path_location("github:NixOS/nixpkgs"),
+        "flake:github:NixOS/nixpkgs"
+    );
+    assert_eq!(nix_path_location("/home/me/nixpkgs"), "/home/me/nixpkgs");
+    assert_eq!(
+        nix_path_location("channel:nixos-unstable"),
+        "channel:nixos-unstable"
+    );
+}
+
+#[test]
+fn test_channel_name() {
+    assert_eq!(channel_name("unstable").as_deref(), Some("nixos-unstable"));
+    assert_eq!(channel_name("24.05").as_deref(), Some("nixos-24.05"));
+    assert_eq!(
+        channel_name("nixpkgs-unstable").as_deref(),
+        Some("nixpkgs-unstable")
+    );
+    assert_eq!(channel_name("../unstable"), None);
+    assert_eq!(channel_name(""), None);
+}
+
+#[test]
+fn test_replace_entry() {
+    let mut entries = Vec::new();
+    replace_entry(&mut entries, "nixpkgs", "channel:nixos-unstable");
+    replace_entry(&mut entries, "home-manager", "/home-manager");
+    replace_entry(&mut entries, "nixpkgs", "/nixpkgs");
+    assert_eq!(entries, ["nixpkgs=/nixpkgs", "home-manager=/home-manager"]);
+}
@@ -1,5 +1,6 @@
 use crate::{
     contains_insensitive_ascii,
+    options_docsource::run_nix,
     starts_with_insensitive_ascii,
     Cache,
     DocEntry,
@@ -70,6 +71,12 @@ impl DocSource for NixpkgsTreeDatabase {
             .map(|k| DocEntry::NixpkgsTreeDoc(k.clone()))
             .collect()
     }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.keys
+            .iter()
+            .map(|k| DocEntry::NixpkgsTreeDoc(k.clone()))
+            .collect()
+    }
     fn update(&mut self) -> Result<bool, Errors> {
         let new_keys = gen_keys()?;
         let old = std::mem::replace(&mut self.keys, new_keys);
@@ -97,13 +104,14 @@ in
 (f (pkgs // { pkgs = {}; lib = {}; })) // { lib = f pkgs.lib; }
     "#;
 
-    let command = Command::new("nix-instantiate")
-        .arg("--json")
-        .arg("--strict")
-        .arg("--eval")
-        .arg("-E")
-        .arg(CODE)
-        .output()?;
+    let command = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--json")
+            .arg("--strict")
+            .arg("--eval")
+            .arg("-E")
+            .arg(CODE),
+    )?;
 
     let keys = serde_json::from_slice::<Keys>(&command.stdout)?;
 
@@ -0,0 +1,138 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::{
+        options_doc_args,
+        out_link_args,
+        run_nix,
+        try_from_file,
+        OptionDocumentation,
+        OptionsDatabaseType,
+    },
+    packages_docsource::{
+        parse_queried_packages,
+        PackageDocumentation,
+    },
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Command,
+};
+
+/// Packages and NixOS module options of the Nix User Repository
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NurDatabase {
+    pub packages: HashMap<String, PackageDocumentation>,
+    pub options: HashMap<String, OptionDocumentation>,
+}
+
+impl Default for NurDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NurDatabase {
+    pub fn new() -> Self {
+        Self {
+            packages: HashMap::new(),
+            options: HashMap::new(),
+        }
+    }
+
+    fn search_with(&self, matches: impl Fn(&[u8]) -> bool) -> Vec<DocEntry> {
+        let packages = self
+            .packages
+            .iter()
+            .filter(|(key, _)| matches(key.as_bytes()))
+            .map(|(_, d)| DocEntry::NurPackageDoc(d.clone()));
+        let options = self
+            .options
+            .iter()
+            .filter(|(key, d)| matches(key.as_bytes()) && d.is_shown())
+            .map(|(_, d)| DocEntry::OptionDoc(OptionsDatabaseType::Nur, d.clone()));
+
+        packages.chain(options).collect()
+    }
+}
+
+impl Cache for NurDatabase {
+    /// Fetched from the internet instead
+    const BUILT_FROM_NIXPKGS: bool = false;
+}
+
+impl DocSource for NurDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.packages
+            .keys()
+            .chain(self.options.keys())
+            .map(|x| x.as_str())
+            .collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.search_with(|key| starts_with_insensitive_ascii(key, query))
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.search_with(|key| contains_insensitive_ascii(key, query))
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        let packages = self
+            .packages
+            .values()
+            .map(|d| DocEntry::NurPackageDoc(d.clone()));
+        let options = self
+            .options
+            .values()
+            .filter(|d| d.is_shown())
+            .map(|d| DocEntry::OptionDoc(OptionsDatabaseType::Nur, d.clone()));
+        packages.chain(options).collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let docs_path = get_nur_docs_path()?;
+        let packages_path = docs_path.join("packages.json");
+        let packages = std::fs::read(&packages_path).map_err(|e| Errors::FileIo {
+            err: e,
+            filename: packages_path.display().to_string(),
+        })?;
+        let new_packages = parse_queried_packages(&packages)?;
+        let new_options = try_from_file(&docs_path.join("options.json"))?;
+
+        let old_packages = std::mem::replace(&mut self.packages, new_packages);
+        let old_options = std::mem::replace(&mut self.options, new_options);
+
+        Ok(!self.packages.keys().eq(old_packages.keys())
+            || !self.options.keys().eq(old_options.keys()))
+    }
+}
+
+pub fn get_nur_docs_path() -> Result<PathBuf, std::io::Error> {
+    let output = run_nix(
+        Command::new("nix-build")
+            .env("NIXPKGS_ALLOW_UNFREE", "1")
+            .env("NIXPKGS_ALLOW_BROKEN", "1")
+            .env("NIXPKGS_ALLOW_INSECURE", "1")
+            .args(out_link_args(include_str!("nix/nur.nix"), "--no-out-link"))
+            .args(options_doc_args())
+            .arg("-E")
+            .arg(include_str!("nix/nur.nix")),
+    )?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    let path = String::from_utf8_lossy(&output.stdout);
+    Ok(PathBuf::from(path.trim_end_matches('\n')))
+}
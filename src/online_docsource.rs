@@ -0,0 +1,292 @@
+use crate::{
+    options_docsource::{
+        enum_values,
+        OptionDocumentation,
+        OptionsDatabaseType,
+    },
+    packages_docsource::{
+        meta_strings,
+        PackageDocumentation,
+    },
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use serde::Deserialize;
+use serde_json::{
+    json,
+    Value,
+};
+use std::{
+    process::{
+        Command,
+        Output,
+    },
+    sync::Mutex,
+};
+
+/// Elasticsearch backend of search.nixos.org
+const SEARCH_URL: &str = "https://search.nixos.org/backend";
+/// Index searched first, the schema version changes whenever search.nixos.org reindexes
+const SEARCH_INDEX: &str = "latest-42-nixos-unstable";
+/// How many schema versions past the one of an index gone missing are tried
+const SCHEMA_VERSIONS_TRIED: u32 = 8;
+/// Exit code of `curl --fail` when the server answers with an HTTP error
+const CURL_HTTP_ERROR: i32 = 22;
+/// Public read-only credentials, the same ones the search.nixos.org frontend uses
+const SEARCH_CREDENTIALS: &str = "aWVSALXpZv:X8gPHnzL52wFEekuxsfQ9cSh";
+const RESULTS_MAX_LEN: usize = 50;
+
+#[derive(Deserialize)]
+struct SearchResponse {
+    hits: Hits,
+}
+
+#[derive(Deserialize)]
+struct Hits {
+    hits: Vec<Hit>,
+}
+
+#[derive(Deserialize)]
+struct Hit {
+    #[serde(rename = "_source")]
+    source: Value,
+}
+
+/// Queries search.nixos.org for options and packages instead of reading a local cache
+pub struct OnlineDocSource {
+    index: Mutex<String>,
+    /// Whether the index was given by `MANIX_SEARCH_INDEX` or the config, and is used as it is
+    configured: bool,
+}
+
+impl Default for OnlineDocSource {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+impl OnlineDocSource {
+    /// Searches `index`, or the one `MANIX_SEARCH_INDEX` names. Without either the index is found
+    /// on its own once search.nixos.org drops the one searched first
+    pub fn new(index: Option<String>) -> Self {
+        let index = std::env::var("MANIX_SEARCH_INDEX").ok().or(index);
+        Self {
+            configured: index.is_some(),
+            index: Mutex::new(index.unwrap_or_else(|| SEARCH_INDEX.into())),
+        }
+    }
+
+    fn query(&self, pattern: &str) -> Vec<DocEntry> {
+        match self.try_query(pattern) {
+            Ok(entries) => entries,
+            Err(e) => {
+                eprintln!("Failed to query search.nixos.org: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    fn try_query(&self, pattern: &str) -> Result<Vec<DocEntry>, Errors> {
+        let wildcard = |field: &str| json!({ "wildcard": { field: { "value": pattern, "case_insensitive": true } } });
+        let body = json!({
+            "size": RESULTS_MAX_LEN,
+            "query": {
+                "bool": {
+                    "should": [wildcard("option_name"), wildcard("package_attr_name")],
+                    "minimum_should_match": 1,
+                }
+            }
+        });
+
+        let index = self.index.lock().unwrap().clone();
+        let mut output = post_search(&index, &body)?;
+        if output.status.code() == Some(CURL_HTTP_ERROR) && !self.configured {
+            if let Some(found) = discover_index(&index) {
+                output = post_search(&found, &body)?;
+                *self.index.lock().unwrap() = found;
+            }
+        }
+        if !output.status.success() {
+            return Err(std::io::Error::other(String::from_utf8_lossy(&output.stderr)).into());
+        }
+
+        let response: SearchResponse = serde_json::from_slice(&output.stdout)?;
+        Ok(response
+            .hits
+            .hits
+            .into_iter()
+            .filter_map(|hit| entry_from_source(&hit.source))
+            .collect())
+    }
+}
+
+fn post_search(index: &str, body: &Value) -> std::io::Result<Output> {
+    Command::new("curl")
+        .arg("--silent")
+        .arg("--fail")
+        .arg("--user")
+        .arg(SEARCH_CREDENTIALS)
+        .arg("--header")
+        .arg("Content-Type: application/json")
+        .arg("--data")
+        .arg(body.to_string())
+        .arg(format!("{}/{}/_search", SEARCH_URL, index))
+        .output()
+}
+
+/// The schema version of indices named like `latest-42-nixos-unstable`, along with what comes
+/// before and after it
+fn split_schema_version(index: &str) -> Option<(&str, u32, &str)> {
+    let (prefix, rest) = index.split_once('-')?;
+    let (version, suffix) = rest.split_once('-')?;
+    Some((prefix, version.parse().ok()?, suffix))
+}
+
+/// The first index with a newer schema version that search.nixos.org answers for, the older ones
+/// being dropped after a reindex
+fn discover_index(missing: &str) -> Option<String> {
+    let (prefix, version, suffix) = split_schema_version(missing)?;
+    let probe = json!({ "size": 0 });
+    (version + 1..=version + SCHEMA_VERSIONS_TRIED)
+        .map(|version| format!("{}-{}-{}", prefix, version, suffix))
+        .find(|index| post_search(index, &probe).is_ok_and(|output| output.status.success()))
+}
+
+fn str_field(source: &Value, field: &str) -> Option<String> {
+    source.get(field).and_then(Value::as_str).map(String::from)
+}
+
+fn entry_from_source(source: &Value) -> Option<DocEntry> {
+    match source.get("type")?.as_str()? {
+        "option" => {
+            let name = str_field(source, "option_name")?;
+            let option_type = str_field(source, "option_type").unwrap_or_default();
+            let documentation = OptionDocumentation {
+                location: name.split('.').map(String::from).collect(),
+                description: strip_html(
+                    &str_field(source, "option_description").unwrap_or_default(),
+                ),
+                enum_values: enum_values(&option_type),
+                option_type,
+                default: str_field(source, "option_default"),
+                example: str_field(source, "option_example"),
+                ..Default::default()
+            };
+            Some(DocEntry::OptionDoc(
+                OptionsDatabaseType::NixOS,
+                documentation,
+            ))
+        }
+        "package" => {
+            let attr = str_field(source, "package_attr_name")?;
+            let name = match (
+                str_field(source, "package_pname"),
+                str_field(source, "package_pversion"),
+            ) {
+                (Some(pname), Some(version)) if !version.is_empty() => {
+                    format!("{}-{}", pname, version)
+                }
+                (Some(pname), _) => pname,
+                _ => attr.clone(),
+            };
+            let field = |f: &str| source.get(f).cloned().unwrap_or_default();
+            let documentation = PackageDocumentation {
+                attr,
+                name,
+                description: str_field(source, "package_description"),
+                homepage: meta_strings(&field("package_homepage"), &[])
+                    .into_iter()
+                    .next(),
+                license: meta_strings(&field("package_license"), &["fullName"]),
+                maintainers: meta_strings(&field("package_maintainers"), &["github", "name"]),
+                platforms: meta_strings(&field("package_platforms"), &[]),
+            };
+            Some(DocEntry::PackageDoc(documentation))
+        }
+        _ => None,
+    }
+}
+
+/// Option descriptions are indexed as rendered HTML
+fn strip_html(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' => in_tag = false,
+            c if !in_tag => text.push(c),
+            _ => (),
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+        .trim()
+        .to_string()
+}
+
+impl DocSource for OnlineDocSource {
+    fn all_keys(&self) -> Vec<&str> {
+        Vec::new()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.query(&format!("{}*", String::from_utf8_lossy(query.0)))
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.query(&format!("*{}*", String::from_utf8_lossy(query.0)))
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        // search.nixos.org can only be queried
+        Vec::new()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        Ok(false)
+    }
+}
+
+#[test]
+fn test_split_schema_version() {
+    assert_eq!(
+        split_schema_version(SEARCH_INDEX),
+        Some(("latest", 42, "nixos-unstable"))
+    );
+    assert_eq!(
+        split_schema_version("latest-43-nixos-24.05"),
+        Some(("latest", 43, "nixos-24.05"))
+    );
+    assert_eq!(split_schema_version("nixos-unstable"), None);
+}
+
+#[test]
+fn test_entry_from_source() {
+    let option = json!({
+        "type": "option",
+        "option_name": "services.openssh.enable",
+        "option_description": "<rendered-html><p>Whether to enable the OpenSSH secure shell daemon.</p></rendered-html>",
+        "option_type": "boolean",
+    });
+    let entry = entry_from_source(&option).unwrap();
+    assert_eq!(entry.name(), "services.openssh.enable");
+    assert!(entry
+        .pretty_printed()
+        .contains("Whether to enable the OpenSSH secure shell daemon."));
+
+    let package = json!({
+        "type": "package",
+        "package_attr_name": "ripgrep",
+        "package_pname": "ripgrep",
+        "package_pversion": "14.1.0",
+        "package_license": [{ "fullName": "MIT License", "url": "https://spdx.org/licenses/MIT.html" }],
+    });
+    match entry_from_source(&package).unwrap() {
+        DocEntry::PackageDoc(doc) => {
+            assert_eq!(doc.name, "ripgrep-14.1.0");
+            assert_eq!(doc.license, vec!["MIT License"]);
+        }
+        _ => panic!("expected a package entry"),
+    }
+}
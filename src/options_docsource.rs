@@ -1,42 +1,481 @@
 use crate::{
-    contains_insensitive_ascii, starts_with_insensitive_ascii, Cache, DocEntry, DocSource, Errors,
+    comments_docsource::find_nix_path_entry,
+    contains_insensitive_ascii,
+    markup,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
     Lowercase,
 };
 use colored::*;
-use serde::{Deserialize, Serialize};
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use std::{
     collections::HashMap,
-    io,
-    path::{Path, PathBuf},
-    process::Command,
+    ffi::OsString,
+    io::{
+        self,
+        Read,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::{
+        Command,
+        Output,
+        Stdio,
+    },
+    sync::{
+        atomic::{
+            AtomicBool,
+            Ordering,
+        },
+        Mutex,
+        OnceLock,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
 };
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+/// Names of `lib.types` along with how options.json describes them
+const TYPE_DESCRIPTIONS: &[(&str, &str)] = &[
+    ("anything", "anything"),
+    ("attrs", "attribute set"),
+    ("attrsOf", "attribute set of"),
+    ("bool", "boolean"),
+    ("enum", "one of"),
+    ("float", "floating point number"),
+    ("int", "signed integer"),
+    ("lazyAttrsOf", "lazy attribute set of"),
+    ("lines", "strings concatenated with \"\\n\""),
+    ("listOf", "list of"),
+    ("nullOr", "null or"),
+    ("port", "16 bit unsigned integer; between 0 and 65535"),
+    ("str", "string"),
+    ("unspecified", "unspecified value"),
+];
+
+static SHOW_HIDDEN: AtomicBool = AtomicBool::new(false);
+
+/// Whether searches return internal and invisible options, which are left out by default
+pub fn set_show_hidden(show: bool) {
+    SHOW_HIDDEN.store(show, Ordering::Relaxed);
+}
+
+static GC_ROOTS: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Where the outputs the options are built into get linked from, which makes them GC roots. They
+/// would go with the next `nix-collect-garbage` otherwise, along with the declarations they point
+/// to
+pub fn set_gc_roots_dir(dir: PathBuf) {
+    *GC_ROOTS.lock().unwrap() = Some(dir);
+}
+
+/// How the nix commands building and evaluating the options are run, set as `nix` in the config
+#[derive(Debug, Clone, Default, Deserialize, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct NixSettings {
+    /// Given to every command, `["--option", "substituters", "..."]` or `["--max-jobs", "4"]`
+    pub args: Vec<String>,
+    /// Set for every command, on top of the environment manix runs in
+    pub env: HashMap<String, String>,
+    /// How many seconds a command may take before it's killed, half an hour by default
+    pub timeout_secs: Option<u64>,
+}
+
+/// Long enough for the first build of the NixOS options on a slow machine
+const DEFAULT_NIX_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+static NIX_SETTINGS: Mutex<Option<NixSettings>> = Mutex::new(None);
+
+pub fn set_nix_settings(settings: NixSettings) {
+    *NIX_SETTINGS.lock().unwrap() = Some(settings);
+}
+
+/// Runs a nix command the way the settings say, killing it when it takes longer than the timeout
+/// so that a hung evaluation doesn't hang manix too
+pub(crate) fn run_nix(command: &mut Command) -> Result<Output, io::Error> {
+    let settings = NIX_SETTINGS.lock().unwrap().clone().unwrap_or_default();
+    if let Some(nix_path) = crate::nixpkgs::nix_path() {
+        command.env("NIX_PATH", nix_path);
+    }
+    run_nix_with(command, &settings)
+}
+
+fn run_nix_with(command: &mut Command, settings: &NixSettings) -> Result<Output, io::Error> {
+    let timeout = settings
+        .timeout_secs
+        .map_or(DEFAULT_NIX_TIMEOUT, Duration::from_secs);
+    let mut child = command
+        .args(&settings.args)
+        .envs(&settings.env)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    // not scoped, what nix started may keep the pipes open once it's killed
+    let read = |pipe: Option<Box<dyn Read + Send>>| {
+        std::thread::spawn(move || {
+            let mut content = Vec::new();
+            if let Some(mut pipe) = pipe {
+                pipe.read_to_end(&mut content)?;
+            }
+            Ok::<_, io::Error>(content)
+        })
+    };
+    let stdout = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+    let stderr = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "{} was killed after {} seconds, `nix.timeout_secs` in the config allows more",
+                    command.get_program().to_string_lossy(),
+                    timeout.as_secs()
+                ),
+            ));
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap()?,
+        stderr: stderr.join().unwrap()?,
+    })
+}
+
+/// The arguments of `nix build` or `nix-build` linking the output from the GC roots directory,
+/// under a name that stays the same for what's built so that rebuilding it replaces the link.
+/// `no_link` is the flag of the command building without any link, for when there's no directory
+pub(crate) fn out_link_args(built: &str, no_link: &str) -> Vec<OsString> {
+    out_link_args_in(GC_ROOTS.lock().unwrap().as_deref(), built, no_link)
+}
+
+fn out_link_args_in(gc_roots: Option<&Path>, built: &str, no_link: &str) -> Vec<OsString> {
+    match gc_roots {
+        Some(dir) => vec![
+            "--out-link".into(),
+            dir.join(format!("{:08x}", crc32fast::hash(built.as_bytes())))
+                .into(),
+        ],
+        None => vec![no_link.into()],
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
 pub struct OptionDocumentation {
     #[serde(default)]
-    description: String,
+    pub(crate) description: String,
 
     #[serde(default, rename(serialize = "readOnly", deserialize = "readOnly"))]
-    read_only: bool,
+    pub(crate) read_only: bool,
 
     #[serde(rename(serialize = "loc", deserialize = "loc"))]
-    location: Vec<String>,
+    pub(crate) location: Vec<String>,
 
     #[serde(rename(serialize = "type", deserialize = "type"))]
-    option_type: String,
+    pub(crate) option_type: String,
+
+    /// Rendered as text when reading options.json, see [try_from_file]
+    #[serde(default)]
+    pub(crate) default: Option<String>,
+
+    #[serde(default)]
+    pub(crate) example: Option<String>,
+
+    /// Files declaring the option, relative to nixpkgs or like `<home-manager/modules/...>`
+    #[serde(default)]
+    pub(crate) declarations: Vec<String>,
+
+    #[serde(default)]
+    pub(crate) related_packages: Option<String>,
+
+    #[serde(default)]
+    pub(crate) internal: bool,
+
+    #[serde(default)]
+    pub(crate) invisible: bool,
+
+    /// Values allowed by an enum type, as written in its description
+    #[serde(default)]
+    pub(crate) enum_values: Vec<String>,
+}
+
+/// Optional parts of the option documentation to print
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OptionDisplay {
+    pub defaults: bool,
+    pub declarations: bool,
+    pub urls: bool,
+}
+
+impl OptionDisplay {
+    /// Every optional part, for when a single option is shown
+    pub fn everything() -> Self {
+        Self {
+            defaults: true,
+            declarations: true,
+            urls: true,
+        }
+    }
 }
 
 impl OptionDocumentation {
     pub fn name(&self) -> String {
         self.location.join(".")
     }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.description
+    }
+    pub fn option_type(&self) -> &str {
+        &self.option_type
+    }
+    /// Whether the type of the option is or contains the type, written the way options.json
+    /// describes it or with the names of `lib.types`: `listOf str` matches `null or (list of
+    /// string)`
+    pub fn has_type(&self, typ: &str) -> bool {
+        let normalize = |s: &str| s.replace(['(', ')'], "").to_ascii_lowercase();
+        let described = typ
+            .split_whitespace()
+            .map(|word| {
+                let word = word.trim_matches(['(', ')']);
+                TYPE_DESCRIPTIONS
+                    .iter()
+                    .find(|(name, _)| *name == word)
+                    .map_or(word, |(_, description)| description)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        let option_type = normalize(&self.option_type);
+        option_type.contains(&normalize(&described)) || option_type.contains(&normalize(typ))
+    }
+    /// Read-only options are set by the modules declaring them, configurations can't
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+    /// Submodule options have their child options listed below their own name
+    pub fn is_submodule(&self) -> bool {
+        self.option_type.contains("submodule")
+    }
+    /// File of the first declaration found on disk, and the line declaring the option in it
+    pub fn declaration_path(&self) -> Option<(PathBuf, Option<usize>)> {
+        self.declarations
+            .iter()
+            .find_map(|declaration| declaration_location(declaration, &self.location))
+    }
+    pub(crate) fn is_shown(&self) -> bool {
+        !(self.internal || self.invisible) || SHOW_HIDDEN.load(Ordering::Relaxed)
+    }
     pub fn pretty_printed(&self) -> String {
-        format!(
-            "# {}\n{}\ntype: {}\n\n",
+        self.render(OptionDisplay::default(), None)
+    }
+    pub fn pretty_printed_with(&self, typ: OptionsDatabaseType, display: OptionDisplay) -> String {
+        let url = if display.urls {
+            typ.option_url(&self.name())
+        } else {
+            None
+        };
+        self.render(display, url)
+    }
+    /// The documentation below the header as Markdown, the type and values in code blocks
+    pub fn markdown(&self, typ: OptionsDatabaseType, display: OptionDisplay) -> String {
+        let mut output = format!(
+            "{}{}\n\n**Type:**\n\n```\n{}\n```\n\n",
+            if self.read_only { "*Read-only.* " } else { "" },
+            self.description.trim(),
+            self.option_type
+        );
+        if !self.enum_values.is_empty() {
+            output.push_str("**Allowed values:**\n\n");
+            for value in &self.enum_values {
+                output.push_str(&format!("- `{}`\n", value));
+            }
+            output.push('\n');
+        }
+        if display.defaults {
+            for (label, value) in [("Default", &self.default), ("Example", &self.example)] {
+                if let Some(text) = value {
+                    output.push_str(&format!("**{}:**\n\n```nix\n{}\n```\n\n", label, text));
+                }
+            }
+        }
+        if display.declarations && !self.declarations.is_empty() {
+            output.push_str("**Declared in:**\n\n");
+            for declaration in &self.declarations {
+                output.push_str(&format!("- `{}`\n", declaration));
+            }
+            output.push('\n');
+        }
+        if let Some(url) = typ.option_url(&self.name()).filter(|_| display.urls) {
+            output.push_str(&format!("<{}>\n\n", url));
+        }
+        output
+    }
+    fn render(&self, display: OptionDisplay, url: Option<String>) -> String {
+        let read_only = if self.read_only {
+            format!(" {}", "(read-only)".yellow())
+        } else {
+            String::new()
+        };
+        let mut output = format!(
+            "# {}{}\n{}\ntype: {}\n",
             self.name().blue().bold(),
-            self.description,
+            read_only,
+            markup::render(&self.description),
             self.option_type
-        )
+        );
+        if !self.enum_values.is_empty() {
+            output.push_str("allowed values:\n");
+            for value in &self.enum_values {
+                output.push_str(&format!("  {}\n", value.white()));
+            }
+        }
+        if let Some(related) = &self.related_packages {
+            output.push_str("related packages:\n");
+            for line in related.lines().filter(|line| !line.trim().is_empty()) {
+                output.push_str(&format!("  {}\n", line.trim()));
+            }
+        }
+        if display.defaults {
+            for (label, value) in [("default", &self.default), ("example", &self.example)] {
+                match value {
+                    Some(text) if text.contains('\n') => {
+                        output.push_str(&format!("{}:\n", label));
+                        for line in text.lines() {
+                            output.push_str(&format!("  {}\n", line.white()));
+                        }
+                    }
+                    Some(text) => output.push_str(&format!("{}: {}\n", label, text.white())),
+                    None => {}
+                }
+            }
+        }
+        if display.declarations {
+            for declaration in &self.declarations {
+                output.push_str(&format!("declared in: {}\n", declaration));
+                if let Some((path, line)) = declaration_location(declaration, &self.location) {
+                    let location = match line {
+                        Some(line) => format!("{}:{}", path.display(), line),
+                        None => path.display().to_string(),
+                    };
+                    output.push_str(&format!("  {}\n", location.underline()));
+                }
+            }
+        }
+        if let Some(url) = url {
+            output.push_str(&format!("{}\n", url.underline()));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// Where a declaration lives on disk, along with the line declaring the option when it's found
+fn declaration_location(
+    declaration: &str,
+    location: &[String],
+) -> Option<(PathBuf, Option<usize>)> {
+    let path = resolve_declaration(declaration)?;
+    let content = std::fs::read_to_string(&path).ok()?;
+    let line = declaration_line(&content, location);
+    Some((path, line))
+}
+
+fn resolve_declaration(declaration: &str) -> Option<PathBuf> {
+    // looking up the NIX_PATH takes a nix-instantiate call, so each entry is only looked up once
+    static ENTRIES: OnceLock<Mutex<HashMap<String, Option<PathBuf>>>> = OnceLock::new();
+    let lookup = |name: &str| {
+        ENTRIES
+            .get_or_init(Default::default)
+            .lock()
+            .ok()?
+            .entry(name.to_string())
+            .or_insert_with(|| find_nix_path_entry(name))
+            .clone()
+    };
+
+    let path = if let Some(inner) = declaration
+        .strip_prefix('<')
+        .and_then(|d| d.strip_suffix('>'))
+    {
+        let (name, rest) = inner.split_once('/').unwrap_or((inner, ""));
+        lookup(name)?.join(rest)
+    } else if Path::new(declaration).is_absolute() {
+        PathBuf::from(declaration)
+    } else {
+        lookup("nixpkgs")?.join(declaration)
+    };
+    path.exists().then_some(path)
+}
+
+/// Line number of the `name = mkOption` declaring the last named part of the option
+pub(crate) fn declaration_line(content: &str, location: &[String]) -> Option<usize> {
+    let name = location.iter().rev().find(|part| !part.starts_with('<'))?;
+    let assigned = |line: &str| {
+        let line = line.trim_start().trim_start_matches('"');
+        line.strip_prefix(name.as_str())
+            .map(|rest| rest.trim_start_matches('"').trim_start().starts_with('='))
+            .unwrap_or(false)
+    };
+    let lines = content.lines().collect::<Vec<_>>();
+    let found = lines
+        .iter()
+        .position(|line| assigned(line) && line.contains("mk") && line.contains("Option"))
+        .or_else(|| lines.iter().position(|line| assigned(line)))?;
+    Some(found + 1)
+}
+
+/// An option as found in options.json, where defaults and examples are either plain JSON values
+/// or `{ _type = "literalExpression"; text = "..."; }` style literals
+#[derive(Deserialize)]
+struct RawOption {
+    #[serde(flatten)]
+    documentation: OptionDocumentation,
+    default: Option<serde_json::Value>,
+    example: Option<serde_json::Value>,
+    #[serde(default)]
+    declarations: Vec<serde_json::Value>,
+    #[serde(rename = "relatedPackages")]
+    related_packages: Option<serde_json::Value>,
+    /// Recorded by the bundled expressions, nixosOptionsDoc drops hidden options otherwise
+    #[serde(default, rename = "isInternal")]
+    is_internal: bool,
+    #[serde(rename = "isVisible")]
+    is_visible: Option<bool>,
+}
+
+/// Declarations are plain paths in NixOS options and `{ name, url }` sets in home-manager ones
+fn render_declaration(value: serde_json::Value) -> String {
+    match value {
+        serde_json::Value::String(path) => path,
+        other => other
+            .get("name")
+            .and_then(serde_json::Value::as_str)
+            .map(String::from)
+            .unwrap_or_else(|| other.to_string()),
+    }
+}
+
+fn render_literal(value: serde_json::Value) -> String {
+    match value.get("text").and_then(serde_json::Value::as_str) {
+        Some(text) if value.get("_type").is_some() => text.to_string(),
+        _ => value.to_string(),
     }
 }
 
@@ -45,12 +484,81 @@ pub enum OptionsDatabaseType {
     NixOS,
     NixDarwin,
     HomeManager,
+    Flake,
+    Nur,
+    Devenv,
+    FlakeParts,
+    Nixvim,
+    Disko,
+    SopsNix,
+    Agenix,
+    TreefmtNix,
+    PreCommitHooks,
+    Impermanence,
+    Colmena,
+    DeployRs,
+    Custom,
+}
+
+impl OptionsDatabaseType {
+    /// Where the option can be looked up online, for the databases having such a page
+    pub fn option_url(&self, name: &str) -> Option<String> {
+        // anchors of the manuals replace the characters that aren't valid in ids,
+        // `users.users.<name>.home` becomes `opt-users.users._name_.home`
+        let anchor = name.replace(['*', '<', '>', '[', ']', ':', '"'], "_");
+        match self {
+            OptionsDatabaseType::NixOS => Some(format!(
+                "https://search.nixos.org/options?show={0}&query={0}",
+                name.replace('<', "%3C").replace('>', "%3E")
+            )),
+            OptionsDatabaseType::HomeManager => Some(format!(
+                "https://nix-community.github.io/home-manager/options.xhtml#opt-{}",
+                anchor
+            )),
+            OptionsDatabaseType::NixDarwin => Some(format!(
+                "https://nix-darwin.github.io/nix-darwin/manual/index.html#opt-{}",
+                anchor
+            )),
+            _ => None,
+        }
+    }
+
+    pub fn source_name(&self) -> &'static str {
+        match self {
+            OptionsDatabaseType::NixOS => "NixOS Options",
+            OptionsDatabaseType::NixDarwin => "NixDarwin Options",
+            OptionsDatabaseType::HomeManager => "HomeManager Options",
+            OptionsDatabaseType::Flake => "Flake Options",
+            OptionsDatabaseType::Nur => "NUR Options",
+            OptionsDatabaseType::Devenv => "Devenv Options",
+            OptionsDatabaseType::FlakeParts => "flake-parts Options",
+            OptionsDatabaseType::Nixvim => "Nixvim Options",
+            OptionsDatabaseType::Disko => "Disko Options",
+            OptionsDatabaseType::SopsNix => "sops-nix Options",
+            OptionsDatabaseType::Agenix => "agenix Options",
+            OptionsDatabaseType::TreefmtNix => "treefmt-nix Options",
+            OptionsDatabaseType::PreCommitHooks => "pre-commit-hooks Options",
+            OptionsDatabaseType::Impermanence => "Impermanence Options",
+            OptionsDatabaseType::Colmena => "Colmena Options",
+            OptionsDatabaseType::DeployRs => "deploy-rs Settings",
+            OptionsDatabaseType::Custom => "Custom Options",
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OptionsDatabase {
     pub typ: OptionsDatabaseType,
     pub options: HashMap<String, OptionDocumentation>,
+    /// Flake reference the options are built from, only used by [OptionsDatabaseType::Flake]
+    #[serde(skip)]
+    pub flake: Option<String>,
+    /// Nix file the options are built from, only used by [OptionsDatabaseType::Custom]
+    #[serde(skip)]
+    pub expression: Option<PathBuf>,
+    /// options.json the options are read from instead of being built, see [options_json_in]
+    #[serde(skip)]
+    pub options_json: Option<PathBuf>,
 }
 
 impl OptionsDatabase {
@@ -58,14 +566,108 @@ impl OptionsDatabase {
         Self {
             typ,
             options: HashMap::new(),
+            flake: None,
+            expression: None,
+            options_json: None,
+        }
+    }
+
+    /// Creates a database for the NixOS/home-manager modules exposed by a flake
+    pub fn for_flake(flake: String) -> Self {
+        Self {
+            flake: Some(flake),
+            ..Self::new(OptionsDatabaseType::Flake)
+        }
+    }
+
+    /// Creates a database for a user supplied nix file that builds an options.json
+    pub fn for_expression(expression: PathBuf) -> Self {
+        Self {
+            expression: Some(expression),
+            ..Self::new(OptionsDatabaseType::Custom)
+        }
+    }
+
+    /// Creates a database reading the options from an options.json that's built already
+    pub fn from_json(typ: OptionsDatabaseType, options_json: PathBuf) -> Self {
+        Self {
+            options_json: Some(options_json),
+            ..Self::new(typ)
         }
     }
 }
 
+/// The options.json of a path, which is either one or an output containing one, like
+/// `config.system.build.manual.optionsJSON`
+pub fn options_json_in(path: &Path) -> PathBuf {
+    let nested = [
+        "share/doc/nixos/options.json",
+        "share/doc/home-manager/options.json",
+        "share/doc/darwin/options.json",
+        "options.json",
+    ];
+    nested
+        .iter()
+        .map(|file| path.join(file))
+        .find(|file| file.is_file())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
 pub fn try_from_file(path: &PathBuf) -> Result<HashMap<String, OptionDocumentation>, Errors> {
-    let options: HashMap<String, OptionDocumentation> =
-        serde_json::from_slice(&std::fs::read(path)?)?;
-    Ok(options)
+    parse_options_json(&std::fs::read(path)?)
+}
+
+/// `null or one of "a", "b"` allows `"a"` and `"b"`
+pub(crate) fn enum_values(option_type: &str) -> Vec<String> {
+    let Some(start) = option_type.find("one of ") else {
+        return Vec::new();
+    };
+    let mut values = Vec::new();
+    let mut value = String::new();
+    let mut quoted = false;
+    for c in option_type[start + "one of ".len()..].chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                value.push(c);
+            }
+            ',' if !quoted => values.push(std::mem::take(&mut value).trim().to_string()),
+            // the enum is nested in another type, `list of (one of "a", "b")`
+            ')' if !quoted => break,
+            _ => value.push(c),
+        }
+    }
+    if !value.trim().is_empty() {
+        values.push(value.trim().to_string());
+    }
+    values
+}
+
+fn parse_options_json(content: &[u8]) -> Result<HashMap<String, OptionDocumentation>, Errors> {
+    let options: HashMap<String, RawOption> = serde_json::from_slice(content)?;
+    Ok(options
+        .into_iter()
+        .map(|(name, raw)| {
+            let documentation = OptionDocumentation {
+                default: raw.default.map(render_literal),
+                example: raw.example.map(render_literal),
+                declarations: raw
+                    .declarations
+                    .into_iter()
+                    .map(render_declaration)
+                    .collect(),
+                related_packages: raw.related_packages.map(|related| match related {
+                    serde_json::Value::String(text) => text,
+                    other => other.to_string(),
+                }),
+                internal: raw.is_internal,
+                invisible: raw.is_visible == Some(false),
+                enum_values: enum_values(&raw.documentation.option_type),
+                ..raw.documentation
+            };
+            (name, documentation)
+        })
+        .collect())
 }
 
 impl DocSource for OptionsDatabase {
@@ -75,58 +677,220 @@ impl DocSource for OptionsDatabase {
     fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
         self.options
             .iter()
-            .filter(|(key, _)| starts_with_insensitive_ascii(key.as_bytes(), query))
+            .filter(|(key, d)| starts_with_insensitive_ascii(key.as_bytes(), query) && d.is_shown())
             .map(|(_, d)| DocEntry::OptionDoc(self.typ, d.clone()))
             .collect()
     }
     fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
         self.options
             .iter()
-            .filter(|(key, _)| contains_insensitive_ascii(key.as_bytes(), query))
+            .filter(|(key, d)| contains_insensitive_ascii(key.as_bytes(), query) && d.is_shown())
             .map(|(_, d)| DocEntry::OptionDoc(self.typ, d.clone()))
             .collect()
     }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.options
+            .values()
+            .filter(|d| d.is_shown())
+            .map(|d| DocEntry::OptionDoc(self.typ, d.clone()))
+            .collect()
+    }
     fn update(&mut self) -> Result<bool, Errors> {
+        let opts = match &self.options_json {
+            Some(path) => try_from_file(&options_json_in(path))?,
+            None => self.build()?,
+        };
+
+        let old = std::mem::replace(&mut self.options, opts);
+
+        Ok(old.keys().eq(self.options.keys()))
+    }
+}
+
+impl OptionsDatabase {
+    /// Builds the options with nix
+    fn build(&self) -> Result<HashMap<String, OptionDocumentation>, Errors> {
         let opts = match self.typ {
             OptionsDatabaseType::NixOS => try_from_file(&get_nixos_json_doc_path()?)?,
             OptionsDatabaseType::NixDarwin => try_from_file(&get_nd_json_doc_path()?)?,
             OptionsDatabaseType::HomeManager => try_from_file(&get_hm_json_doc_path()?)?,
+            OptionsDatabaseType::Flake => {
+                let flake = self
+                    .flake
+                    .as_deref()
+                    .ok_or_else(|| io::Error::other("No flake reference given"))?;
+                try_from_file(&get_flake_json_doc_path(flake)?)?
+            }
+            OptionsDatabaseType::Custom => {
+                let expression = self
+                    .expression
+                    .as_deref()
+                    .ok_or_else(|| io::Error::other("No options expression given"))?;
+                try_from_file(&get_custom_json_doc_path(expression)?)?
+            }
+            OptionsDatabaseType::Nur => {
+                try_from_file(&crate::nur_docsource::get_nur_docs_path()?.join("options.json"))?
+            }
+            OptionsDatabaseType::Devenv => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/devenv-options.nix"),
+            )?)?,
+            OptionsDatabaseType::FlakeParts => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/flake-parts-options.nix"),
+            )?)?,
+            OptionsDatabaseType::Nixvim => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/nixvim-options.nix"),
+            )?)?,
+            OptionsDatabaseType::Disko => {
+                try_from_file(&get_flake_json_doc_path("github:nix-community/disko")?)?
+            }
+            OptionsDatabaseType::SopsNix => {
+                try_from_file(&get_flake_json_doc_path("github:Mic92/sops-nix")?)?
+            }
+            OptionsDatabaseType::Agenix => {
+                try_from_file(&get_flake_json_doc_path("github:ryantm/agenix")?)?
+            }
+            OptionsDatabaseType::TreefmtNix => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/treefmt-nix-options.nix"),
+            )?)?,
+            OptionsDatabaseType::PreCommitHooks => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/pre-commit-hooks-options.nix"),
+            )?)?,
+            OptionsDatabaseType::Impermanence => try_from_file(&get_flake_json_doc_path(
+                "github:nix-community/impermanence",
+            )?)?,
+            OptionsDatabaseType::Colmena => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/colmena-options.nix"),
+            )?)?,
+            OptionsDatabaseType::DeployRs => try_from_file(&get_bundled_json_doc_path(
+                include_str!("nix/deploy-rs-options.nix"),
+            )?)?,
         };
+        Ok(opts)
+    }
+}
 
-        let old = std::mem::replace(&mut self.options, opts);
+impl Cache for OptionsDatabase {}
 
-        Ok(old.keys().eq(self.options.keys()))
+/// Lets the options of packages that wouldn't evaluate otherwise be documented too
+const BUILD_ENV: &[(&str, &str)] = &[
+    ("NIXPKGS_ALLOW_UNFREE", "1"),
+    ("NIXPKGS_ALLOW_BROKEN", "1"),
+    ("NIXPKGS_ALLOW_INSECURE", "1"),
+    ("NIXPKGS_ALLOW_UNSUPPORTED_SYSTEM", "1"),
+];
+
+/// The function rendering the options.json of options the expressions take as `optionsDoc`,
+/// since nix doesn't resolve the imports of an expression given with `-E`
+const OPTIONS_DOC: &str = include_str!("nix/options-doc.nix");
+
+/// `--arg` arguments giving the expressions that are functions their `optionsDoc`, nix only
+/// passing them the ones they ask for
+pub(crate) fn options_doc_args() -> [&'static str; 3] {
+    ["--arg", "optionsDoc", OPTIONS_DOC]
+}
+
+/// Entries of the NIX_PATH the expressions look up, taken from the flake registry when the
+/// NIX_PATH doesn't have them, as on installations with flakes and no channels
+const REGISTRY_NIX_PATH: &[&str] = &["nixpkgs", "home-manager"];
+
+/// `-I` arguments taking the entries of the NIX_PATH the expression looks up from the flake
+/// registry, for the ones the NIX_PATH doesn't have
+fn registry_nix_path_args(expr: &str) -> Vec<String> {
+    REGISTRY_NIX_PATH
+        .iter()
+        .filter(|name| expr.contains(&format!("<{}", name)) && find_nix_path_entry(name).is_none())
+        .flat_map(|name| ["-I".to_string(), format!("{}=flake:{}", name, name)])
+        .collect()
+}
+
+/// Evaluates an expression without building anything, for `manix doctor`. The derivation it
+/// gives is instantiated when `derivation` is set, it's only evaluated otherwise
+pub(crate) fn check_expression(expr: &str, derivation: bool) -> Result<(), io::Error> {
+    let mut command = Command::new("nix-instantiate");
+    command
+        .envs(BUILD_ENV.iter().copied())
+        .arg("--extra-experimental-features")
+        .arg("nix-command flakes")
+        .args(registry_nix_path_args(expr))
+        .args(options_doc_args());
+    if derivation {
+        command.arg("-E").arg(expr);
+    } else {
+        command
+            .arg("--eval")
+            .arg("-E")
+            .arg(format!("builtins.seq ({}) null", expr));
     }
+    let output = run_nix(&mut command)?;
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(())
 }
 
-impl Cache for OptionsDatabase {}
+/// Builds an expression with `nix build`, falling back to `nix-build` for nix versions without
+/// the new CLI. Gives the output path
+fn build_expression(expr: &str, args: &[(&str, &str)]) -> Result<PathBuf, io::Error> {
+    let built = format!("{}{:?}", expr, args);
+    let mut command = Command::new("nix");
+    command
+        .envs(BUILD_ENV.iter().copied())
+        .arg("--extra-experimental-features")
+        .arg("nix-command flakes")
+        .arg("build")
+        .arg("--impure")
+        .args(out_link_args(&built, "--no-link"))
+        .arg("--print-out-paths");
+    command
+        .args(registry_nix_path_args(expr))
+        .args(options_doc_args());
+    for (name, value) in args {
+        command.arg("--argstr").arg(name).arg(value);
+    }
+    let error = match run_nix(command.arg("--expr").arg(expr)) {
+        Ok(output) if output.status.success() => return Ok(out_path(&output.stdout)),
+        Ok(output) => io::Error::other(String::from_utf8_lossy(&output.stderr).to_string()),
+        Err(err) => err,
+    };
+
+    let mut command = Command::new("nix-build");
+    command
+        .envs(BUILD_ENV.iter().copied())
+        .arg("--extra-experimental-features")
+        .arg("nix-command flakes")
+        .args(out_link_args(&built, "--no-out-link"))
+        .args(options_doc_args());
+    for (name, value) in args {
+        command.arg("--argstr").arg(name).arg(value);
+    }
+    match run_nix(command.arg("-E").arg(expr)) {
+        Ok(output) if output.status.success() => Ok(out_path(&output.stdout)),
+        // trying again would take as long
+        Err(err) if err.kind() == io::ErrorKind::TimedOut => Err(err),
+        // what the new CLI says is what matters on installations that only have it
+        _ => Err(error),
+    }
+}
+
+fn out_path(stdout: &[u8]) -> PathBuf {
+    let stdout = String::from_utf8_lossy(stdout);
+    PathBuf::from(stdout.lines().last().unwrap_or("").trim())
+}
 
 pub fn get_hm_json_doc_path() -> Result<PathBuf, std::io::Error> {
-    let base_path_res = Command::new("nix-build")
-        .env("NIXPKGS_ALLOW_UNFREE", "1")
-        .env("NIXPKGS_ALLOW_BROKEN", "1")
-        .env("NIXPKGS_ALLOW_INSECURE", "1")
-        .arg("-E")
-        .arg(include_str!("nix/hm-options.nix"))
-        .output()?;
-
-    let base_path_output = if base_path_res.status.success() {
-        let path = String::from_utf8_lossy(&base_path_res.stdout);
-        PathBuf::from(path.trim_end_matches('\n'))
-    } else {
-        // we may still get the options from profile if user set manual.json.enable
-        let path = std::env::var("HOME")
-            .map(|home| Path::new(&home).join(".nix-profile"))
-            .expect("HOME must be set");
+    let base_path_output = match build_expression(include_str!("nix/hm-options.nix"), &[]) {
+        Ok(path) => path,
+        Err(err) => {
+            // we may still get the options from profile if user set manual.json.enable
+            let path = std::env::var("HOME")
+                .map(|home| Path::new(&home).join(".nix-profile"))
+                .expect("HOME must be set");
 
-        if path.join("share/doc/home-manager/options.json").exists() {
-            path
-        } else {
-            // propagate error from
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                String::from_utf8_lossy(&base_path_res.stderr),
-            ));
+            if path.join("share/doc/home-manager/options.json").exists() {
+                path
+            } else {
+                return Err(err);
+            }
         }
     };
 
@@ -134,30 +898,312 @@ pub fn get_hm_json_doc_path() -> Result<PathBuf, std::io::Error> {
 }
 
 pub fn get_nixos_json_doc_path() -> Result<PathBuf, std::io::Error> {
-    let base_path_output = Command::new("nix-build")
-        .env("NIXPKGS_ALLOW_UNFREE", "1")
-        .env("NIXPKGS_ALLOW_BROKEN", "1")
-        .env("NIXPKGS_ALLOW_INSECURE", "1")
-        .arg("--no-out-link")
-        .arg("-E")
-        .arg(include_str!("nix/nixos-options.nix"))
-        .output()
-        .map(|o| String::from_utf8(o.stdout).unwrap())?;
-
-    Ok(PathBuf::from(base_path_output.trim_end_matches('\n')))
+    build_expression(include_str!("nix/nixos-options.nix"), &[])
 }
 
 pub fn get_nd_json_doc_path() -> Result<PathBuf, std::io::Error> {
-    let base_path_output = Command::new("nix-build")
-        .env("NIXPKGS_ALLOW_UNFREE", "1")
-        .env("NIXPKGS_ALLOW_BROKEN", "1")
-        .env("NIXPKGS_ALLOW_INSECURE", "1")
-        .env("NIXPKGS_ALLOW_UNSUPPORTED_SYSTEM", "1")
-        .arg("--no-out-link")
-        .arg("-E")
-        .arg(include_str!("nix/darwin-options.nix"))
-        .output()
-        .map(|o| String::from_utf8(o.stdout).unwrap())?;
-
-    Ok(PathBuf::from(base_path_output.trim_end_matches('\n')))
+    build_expression(include_str!("nix/darwin-options.nix"), &[])
+}
+
+pub fn get_flake_json_doc_path(flake: &str) -> Result<PathBuf, std::io::Error> {
+    // getFlake doesn't accept relative paths, so resolve local flakes first
+    let flake = match Path::new(flake).canonicalize() {
+        Ok(path) if path.join("flake.nix").exists() => path.display().to_string(),
+        _ => flake.to_string(),
+    };
+
+    build_expression(include_str!("nix/flake-options.nix"), &[("flake", &flake)])
+}
+
+/// Builds one of the options.json expressions bundled in `src/nix/`
+pub fn get_bundled_json_doc_path(expr: &str) -> Result<PathBuf, std::io::Error> {
+    build_expression(expr, &[])
+}
+
+/// Builds a user supplied expression, which may either produce an options.json file directly or
+/// an `optionsJSON` style output containing it
+pub fn get_custom_json_doc_path(expression: &Path) -> Result<PathBuf, std::io::Error> {
+    let base_path_res = run_nix(
+        Command::new("nix-build")
+            .args(out_link_args(
+                &expression.display().to_string(),
+                "--no-out-link",
+            ))
+            .arg(expression),
+    )?;
+
+    if !base_path_res.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(
+            &base_path_res.stderr,
+        )));
+    }
+
+    let path = String::from_utf8_lossy(&base_path_res.stdout);
+    Ok(options_json_in(Path::new(path.trim_end_matches('\n'))))
+}
+
+/// Nix expression selecting an option of an evaluated configuration, `config."services"."ssh"`.
+/// Options of attribute sets or lists of submodules like `users.users.<name>.home` can't be
+/// selected
+fn config_attr_path(location: &[String]) -> Option<String> {
+    if location
+        .iter()
+        .any(|part| part.starts_with('<') || part == "*")
+    {
+        return None;
+    }
+    Some(location.iter().fold(String::from("config"), |path, part| {
+        format!("{}.{:?}", path, part)
+    }))
+}
+
+/// Evaluates what the configuration of the running system or user sets an option to, as pretty
+/// printed JSON. Gives `None` for options that can't be evaluated this way
+pub fn get_configured_value(
+    typ: OptionsDatabaseType,
+    option: &OptionDocumentation,
+) -> Result<Option<String>, Errors> {
+    let Some(attr_path) = config_attr_path(&option.location) else {
+        return Ok(None);
+    };
+    let output = match typ {
+        OptionsDatabaseType::NixOS => eval_nixos_config(&attr_path)?,
+        OptionsDatabaseType::HomeManager => eval_hm_config(&attr_path)?,
+        _ => return Ok(None),
+    };
+
+    let value: serde_json::Value = serde_json::from_slice(&output)?;
+    Ok(Some(serde_json::to_string_pretty(&value)?))
+}
+
+/// Flake based systems are looked up by their hostname in /etc/nixos, others are evaluated from
+/// `<nixpkgs/nixos>` which picks up NIXOS_CONFIG or /etc/nixos/configuration.nix
+fn eval_nixos_config(attr_path: &str) -> Result<Vec<u8>, std::io::Error> {
+    let mut command;
+    if Path::new("/etc/nixos/flake.nix").exists() {
+        let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")?;
+        command = Command::new("nix");
+        command
+            .arg("--extra-experimental-features")
+            .arg("nix-command flakes")
+            .arg("eval")
+            .arg("--json")
+            .arg(format!(
+                "/etc/nixos#nixosConfigurations.{:?}.{}",
+                hostname.trim(),
+                attr_path
+            ));
+    } else {
+        command = Command::new("nix-instantiate");
+        command
+            .arg("--eval")
+            .arg("--strict")
+            .arg("--json")
+            .arg("-E")
+            .arg(format!("(import <nixpkgs/nixos> {{ }}).{}", attr_path));
+    }
+    let output = run_nix(&mut command)?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(output.stdout)
+}
+
+/// Standalone home-manager configurations and the `homeConfigurations` of a flake in
+/// ~/.config/home-manager are both supported
+fn eval_hm_config(attr_path: &str) -> Result<Vec<u8>, std::io::Error> {
+    let config_dir = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::var("HOME")
+            .map(|home| Path::new(&home).join(".config"))
+            .map_err(|_| io::Error::other("HOME must be set"))?,
+    }
+    .join("home-manager");
+    let hostname = std::fs::read_to_string("/proc/sys/kernel/hostname")?;
+    let username = std::env::var("USER").map_err(|_| io::Error::other("USER must be set"))?;
+
+    let output = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--extra-experimental-features")
+            .arg("nix-command flakes")
+            .arg("--eval")
+            .arg("--strict")
+            .arg("--json")
+            .arg("--argstr")
+            .arg("username")
+            .arg(username)
+            .arg("--argstr")
+            .arg("hostname")
+            .arg(hostname.trim())
+            .arg("--argstr")
+            .arg("configDir")
+            .arg(config_dir)
+            .arg("-E")
+            .arg(format!(
+                "{{ username, hostname, configDir }}@args: (({}) args).{}",
+                include_str!("nix/hm-config.nix"),
+                attr_path
+            )),
+    )?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(String::from_utf8_lossy(&output.stderr)));
+    }
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_run_nix() {
+    let settings = NixSettings {
+        args: vec!["extra".to_string()],
+        env: HashMap::from([("MANIX_TEST".to_string(), "set".to_string())]),
+        timeout_secs: Some(1),
+    };
+    let mut echo = Command::new("sh");
+    echo.arg("-c").arg("echo $MANIX_TEST $0");
+    let output = run_nix_with(&mut echo, &settings).unwrap();
+    assert_eq!(output.stdout, b"set extra\n");
+    let started = Instant::now();
+    let mut sleep = Command::new("sh");
+    sleep.arg("-c").arg("sleep 10");
+    let err = run_nix_with(&mut sleep, &settings).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[test]
+fn test_options_json_in() {
+    let dir = std::env::temp_dir().join(format!("manix-options-json-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("share/doc/nixos")).unwrap();
+    assert_eq!(options_json_in(&dir), dir);
+    std::fs::write(dir.join("share/doc/nixos/options.json"), b"{}").unwrap();
+    assert_eq!(
+        options_json_in(&dir),
+        dir.join("share/doc/nixos/options.json")
+    );
+    let file = dir.join("share/doc/nixos/options.json");
+    assert_eq!(options_json_in(&file), file);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_out_link_args() {
+    assert_eq!(
+        out_link_args_in(None, "<nixpkgs>", "--no-link"),
+        vec!["--no-link"]
+    );
+    let dir = Some(Path::new("/cache/gcroots"));
+    let args = out_link_args_in(dir, "<nixpkgs>", "--no-link");
+    assert_eq!(args[0], "--out-link");
+    assert!(Path::new(&args[1]).starts_with("/cache/gcroots"));
+    assert_eq!(out_link_args_in(dir, "<nixpkgs>", "--no-out-link"), args);
+    assert_ne!(out_link_args_in(dir, "<home-manager>", "--no-link"), args);
+}
+
+#[test]
+fn test_parse_options_json() {
+    let options = parse_options_json(
+        br#"{
+            "services.foo.port": {
+                "description": "Port to listen on.",
+                "loc": ["services", "foo", "port"],
+                "type": "16 bit unsigned integer",
+                "isInternal": true,
+                "default": { "_type": "literalExpression", "text": "8080" },
+                "example": 443,
+                "declarations": ["nixos/modules/services/foo.nix"]
+            },
+            "services.foo.enable": {
+                "loc": ["services", "foo", "enable"],
+                "type": "boolean",
+                "declarations": [{ "name": "<home-manager/modules/foo.nix>", "url": "https://example.org" }]
+            }
+        }"#,
+    )
+    .unwrap();
+    let port = &options["services.foo.port"];
+    assert_eq!(port.default.as_deref(), Some("8080"));
+    assert_eq!(port.example.as_deref(), Some("443"));
+    assert_eq!(options["services.foo.enable"].default, None);
+    assert!(!port.is_shown());
+    assert!(options["services.foo.enable"].is_shown());
+    assert_eq!(port.declarations, vec!["nixos/modules/services/foo.nix"]);
+    assert_eq!(
+        options["services.foo.enable"].declarations,
+        vec!["<home-manager/modules/foo.nix>"]
+    );
+
+    let module = "{\n  options.services.foo = {\n    enable = lib.mkEnableOption \"foo\";\n    port = lib.mkOption {\n";
+    assert_eq!(declaration_line(module, &port.location), Some(4));
+
+    assert_eq!(
+        OptionsDatabaseType::HomeManager
+            .option_url("programs.git.includes.*.path")
+            .as_deref(),
+        Some("https://nix-community.github.io/home-manager/options.xhtml#opt-programs.git.includes._.path")
+    );
+    assert_eq!(
+        OptionsDatabaseType::NixOS.option_url("users.users.<name>.home").as_deref(),
+        Some("https://search.nixos.org/options?show=users.users.%3Cname%3E.home&query=users.users.%3Cname%3E.home")
+    );
+}
+
+#[test]
+fn test_has_type() {
+    let option = |typ: &str| OptionDocumentation {
+        option_type: typ.to_string(),
+        ..Default::default()
+    };
+    assert!(option("null or (list of string)").has_type("listOf str"));
+    assert!(option("attribute set of list of string").has_type("attrsOf (listOf str)"));
+    assert!(
+        option("16 bit unsigned integer; between 0 and 65535 (both inclusive)").has_type("port")
+    );
+    assert!(option("list of string").has_type("List of"));
+    assert!(!option("list of signed integer").has_type("listOf str"));
+    assert!(!option("boolean").has_type("str"));
+}
+
+#[test]
+fn test_read_only_marker() {
+    colored::control::set_override(false);
+    let option = OptionDocumentation {
+        location: vec!["system".to_string(), "build".to_string()],
+        option_type: "submodule".to_string(),
+        read_only: true,
+        ..Default::default()
+    };
+    assert!(option
+        .pretty_printed()
+        .starts_with("# system.build (read-only)\n"));
+    assert!(option
+        .markdown(OptionsDatabaseType::NixOS, OptionDisplay::default())
+        .starts_with("*Read-only.* "));
+}
+
+#[test]
+fn test_enum_values() {
+    assert_eq!(
+        enum_values(r#"null or one of "yes", "without-password", "no""#),
+        vec![r#""yes""#, r#""without-password""#, r#""no""#]
+    );
+    assert_eq!(
+        enum_values(r#"list of (one of "a, b", 1)"#),
+        vec![r#""a, b""#, "1"]
+    );
+    assert!(enum_values("string").is_empty());
+}
+
+#[test]
+fn test_config_attr_path() {
+    let location = |parts: &[&str]| parts.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        config_attr_path(&location(&["services", "openssh", "enable"])).as_deref(),
+        Some(r#"config."services"."openssh"."enable""#)
+    );
+    assert_eq!(
+        config_attr_path(&location(&["users", "users", "<name>", "home"])),
+        None
+    );
 }
@@ -0,0 +1,272 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PackageDocumentation {
+    pub(crate) attr: String,
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) homepage: Option<String>,
+    pub(crate) license: Vec<String>,
+    pub(crate) maintainers: Vec<String>,
+    pub(crate) platforms: Vec<String>,
+}
+
+impl PackageDocumentation {
+    pub fn name(&self) -> String {
+        self.attr.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        self.description.as_deref().unwrap_or_default()
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {} ({})\n", self.attr.blue().bold(), self.name.cyan());
+        if let Some(description) = &self.description {
+            output.push_str(&format!("{}\n", description));
+        }
+        output.push('\n');
+        if let Some(homepage) = &self.homepage {
+            output.push_str(&format!("{}: {}\n", "homepage".green(), homepage));
+        }
+        if !self.license.is_empty() {
+            output.push_str(&format!(
+                "{}: {}\n",
+                "license".green(),
+                self.license.join(", ")
+            ));
+        }
+        if !self.maintainers.is_empty() {
+            output.push_str(&format!(
+                "{}: {}\n",
+                "maintainers".green(),
+                self.maintainers.join(", ")
+            ));
+        }
+        if !self.platforms.is_empty() {
+            output.push_str(&format!(
+                "{}: {}\n",
+                "platforms".green(),
+                self.platforms.join(", ")
+            ));
+        }
+        output.push('\n');
+        output
+    }
+}
+
+/// A single package as printed by `nix-env -qa --json --meta`
+#[derive(Deserialize)]
+struct QueriedPackage {
+    name: String,
+    #[serde(default)]
+    meta: QueriedMeta,
+}
+
+#[derive(Deserialize, Default)]
+struct QueriedMeta {
+    description: Option<String>,
+    #[serde(default)]
+    homepage: Value,
+    #[serde(default)]
+    license: Value,
+    #[serde(default)]
+    maintainers: Value,
+    #[serde(default)]
+    platforms: Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackagesDatabase {
+    pub packages: HashMap<String, PackageDocumentation>,
+    /// Package set like `python3Packages` to query instead of the top level of nixpkgs
+    #[serde(skip)]
+    pub set: Option<String>,
+}
+
+impl Default for PackagesDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackagesDatabase {
+    pub fn new() -> Self {
+        Self {
+            packages: HashMap::new(),
+            set: None,
+        }
+    }
+
+    pub fn for_set(set: &str) -> Self {
+        Self {
+            set: Some(set.to_string()),
+            ..Self::new()
+        }
+    }
+}
+
+impl Cache for PackagesDatabase {}
+
+impl DocSource for PackagesDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.packages.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.packages
+            .iter()
+            .filter(|(key, _)| starts_with_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::PackageDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.packages
+            .iter()
+            .filter(|(key, value)| {
+                contains_insensitive_ascii(key.as_bytes(), query)
+                    || value
+                        .description
+                        .as_ref()
+                        .is_some_and(|d| contains_insensitive_ascii(d.as_bytes(), query))
+            })
+            .map(|(_, value)| DocEntry::PackageDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.packages
+            .values()
+            .map(|value| DocEntry::PackageDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let mut new = parse_queried_packages(&query_packages(self.set.as_deref())?)?;
+        if let Some(set) = &self.set {
+            // keep the attribute paths usable as is, `python3Packages.requests`
+            new = new
+                .into_iter()
+                .map(|(attr, mut doc)| {
+                    if !attr.starts_with(&format!("{}.", set)) {
+                        doc.attr = format!("{}.{}", set, attr);
+                    }
+                    (doc.attr.clone(), doc)
+                })
+                .collect();
+        }
+        let old = std::mem::replace(&mut self.packages, new);
+
+        Ok(!self.packages.keys().eq(old.keys()))
+    }
+}
+
+/// Flattens a meta value that may be a string, an attrset or a list of either into strings,
+/// preferring the first of `keys` found in attrsets
+pub(crate) fn meta_strings(value: &Value, keys: &[&str]) -> Vec<String> {
+    match value {
+        Value::String(s) => vec![s.clone()],
+        Value::Array(values) => values.iter().flat_map(|v| meta_strings(v, keys)).collect(),
+        Value::Object(attrs) => keys
+            .iter()
+            .find_map(|key| attrs.get(*key).and_then(Value::as_str))
+            .map(|s| vec![s.to_string()])
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn parse_queried_packages(
+    content: &[u8],
+) -> Result<HashMap<String, PackageDocumentation>, Errors> {
+    let queried: HashMap<String, QueriedPackage> = serde_json::from_slice(content)?;
+
+    Ok(queried
+        .into_iter()
+        .map(|(attr, package)| {
+            let meta = package.meta;
+            let doc = PackageDocumentation {
+                attr: attr.clone(),
+                name: package.name,
+                description: meta.description,
+                homepage: meta_strings(&meta.homepage, &[]).into_iter().next(),
+                license: meta_strings(&meta.license, &["spdxId", "shortName", "fullName"]),
+                maintainers: meta_strings(&meta.maintainers, &["github", "name", "email"]),
+                platforms: meta_strings(&meta.platforms, &[]),
+            };
+            (attr, doc)
+        })
+        .collect())
+}
+
+fn query_packages(set: Option<&str>) -> Result<Vec<u8>, std::io::Error> {
+    let mut command = Command::new("nix-env");
+    command
+        .env("NIXPKGS_ALLOW_UNFREE", "1")
+        .env("NIXPKGS_ALLOW_BROKEN", "1")
+        .env("NIXPKGS_ALLOW_INSECURE", "1")
+        .arg("-f")
+        .arg("<nixpkgs>")
+        .arg("-qa")
+        .arg("--json")
+        .arg("--meta");
+    if let Some(set) = set {
+        command.arg("-A").arg(set);
+    }
+    let output = run_nix(&mut command)?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+#[test]
+fn test_parse_queried_packages() {
+    let queried = br#"{
+        "ripgrep": {
+            "name": "ripgrep-14.1.0",
+            "meta": {
+                "description": "A utility that combines the usability of The Silver Searcher with the raw speed of grep",
+                "homepage": "https://github.com/BurntSushi/ripgrep",
+                "license": [{ "spdxId": "MIT", "fullName": "MIT License" }, { "shortName": "unlicense" }],
+                "maintainers": [{ "github": "globin", "name": "Robin Gloster" }],
+                "platforms": ["x86_64-linux", "aarch64-darwin"]
+            }
+        },
+        "hello": { "name": "hello-2.12.1", "meta": { "license": "GPL-3.0-or-later" } }
+    }"#;
+    let packages = parse_queried_packages(queried).unwrap();
+
+    let ripgrep = &packages["ripgrep"];
+    assert_eq!(ripgrep.license, vec!["MIT", "unlicense"]);
+    assert_eq!(ripgrep.maintainers, vec!["globin"]);
+    assert_eq!(ripgrep.platforms.len(), 2);
+    assert_eq!(
+        ripgrep.homepage.as_deref(),
+        Some("https://github.com/BurntSushi/ripgrep")
+    );
+
+    let hello = &packages["hello"];
+    assert_eq!(hello.license, vec!["GPL-3.0-or-later"]);
+    assert!(hello.description.is_none());
+}
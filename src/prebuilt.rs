@@ -0,0 +1,147 @@
+use crate::Errors;
+use serde::Deserialize;
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+};
+
+/// Where `manix update --fetch` downloads the caches from instead of building them, set as
+/// `prebuilt_caches` in the config
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct PrebuiltCaches {
+    /// The caches are looked for below `URL/MANIX_VERSION/NIXPKGS_REVISION/`, listed in a
+    /// `manifest.json` there
+    pub url: String,
+    /// The minisign public key the manifest and every cache are signed with
+    pub public_key: String,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    files: Vec<String>,
+}
+
+impl PrebuiltCaches {
+    /// Where the caches built by this version of manix from the nixpkgs of the revision are,
+    /// which are the only ones that wouldn't be rebuilt right away when it's the one in use
+    pub fn base_url(&self, nixpkgs_revision: &str) -> String {
+        format!(
+            "{}/{}/{}",
+            self.url.trim_end_matches('/'),
+            env!("CARGO_PKG_VERSION"),
+            nixpkgs_revision
+        )
+    }
+
+    /// Downloads the caches of the nixpkgs revision into the directory, none of them being put
+    /// there before its signature is checked. Returns the names of the cache files
+    pub fn fetch(&self, dir: &Path, nixpkgs_revision: &str) -> Result<Vec<String>, Errors> {
+        let base_url = self.base_url(nixpkgs_revision);
+        let download_dir = dir.join(".fetch");
+        std::fs::create_dir_all(&download_dir).map_err(|err| Errors::FileIo {
+            filename: download_dir.display().to_string(),
+            err,
+        })?;
+
+        let manifest = self.download(&base_url, "manifest.json", &download_dir)?;
+        let content = std::fs::read(&manifest).map_err(|err| Errors::FileIo {
+            filename: manifest.display().to_string(),
+            err,
+        })?;
+        let files = cache_files(serde_json::from_slice(&content)?)?;
+        let downloaded = files
+            .iter()
+            .map(|file| self.download(&base_url, file, &download_dir))
+            .collect::<Result<Vec<_>, _>>()?;
+        for (file, path) in files.iter().zip(downloaded) {
+            let destination = dir.join(file);
+            std::fs::rename(&path, &destination).map_err(|err| Errors::FileIo {
+                filename: destination.display().to_string(),
+                err,
+            })?;
+        }
+        let _ = std::fs::remove_dir_all(&download_dir);
+        Ok(files)
+    }
+
+    /// Downloads a file along with its signature and checks it
+    fn download(&self, base_url: &str, file: &str, dir: &Path) -> Result<PathBuf, Errors> {
+        let path = dir.join(file);
+        let signature = dir.join(format!("{}.minisig", file));
+        curl(&format!("{}/{}", base_url, file), &path)?;
+        curl(&format!("{}/{}.minisig", base_url, file), &signature)?;
+
+        let output = Command::new("minisign")
+            .arg("-V")
+            .arg("-q")
+            .arg("-P")
+            .arg(&self.public_key)
+            .arg("-m")
+            .arg(&path)
+            .arg("-x")
+            .arg(&signature)
+            .output()?;
+        if !output.status.success() {
+            return Err(std::io::Error::other(format!(
+                "The signature of {} doesn't match: {}",
+                file,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ))
+            .into());
+        }
+        Ok(path)
+    }
+}
+
+fn curl(url: &str, path: &Path) -> Result<(), Errors> {
+    let output = Command::new("curl")
+        .arg("--silent")
+        .arg("--fail")
+        .arg("--location")
+        .arg("--output")
+        .arg(path)
+        .arg(url)
+        .output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "Failed to download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(())
+}
+
+/// Whether the name is one of a cache file, a plain file name that stays in the cache directory
+pub(crate) fn is_cache_file_name(name: &str) -> bool {
+    !name.starts_with('.') && !name.contains(['/', '\\']) && name.ends_with(".bin")
+}
+
+/// The files of the manifest, which have to be names of cache files
+fn cache_files(manifest: Manifest) -> Result<Vec<String>, Errors> {
+    for file in &manifest.files {
+        if !is_cache_file_name(file) {
+            return Err(std::io::Error::other(format!("Unexpected cache file {:?}", file)).into());
+        }
+    }
+    Ok(manifest.files)
+}
+
+#[test]
+fn test_cache_files() {
+    let manifest = |files: &[&str]| Manifest {
+        files: files.iter().map(|file| file.to_string()).collect(),
+    };
+    assert_eq!(
+        cache_files(manifest(&["options_nixos_database.bin", "comments.bin"])).unwrap(),
+        vec!["options_nixos_database.bin", "comments.bin"]
+    );
+    assert!(cache_files(manifest(&["../config.json"])).is_err());
+    assert!(cache_files(manifest(&["/etc/passwd.bin"])).is_err());
+    assert!(cache_files(manifest(&["..bin"])).is_err());
+}
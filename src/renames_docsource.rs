@@ -0,0 +1,343 @@
+use crate::{
+    comments_docsource::{
+        find_nix_path_entry,
+        get_nixpkgs_root,
+    },
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use rayon::prelude::*;
+use rnix::{
+    ast::{
+        Apply,
+        AttrSet,
+        Expr,
+        HasEntry,
+        InterpolPart,
+    },
+    Root,
+};
+use rowan::ast::AstNode;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+};
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum RenameKind {
+    Renamed,
+    Aliased,
+    Changed,
+    Merged,
+    Removed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OptionRenameDocumentation {
+    from: String,
+    to: Vec<String>,
+    kind: RenameKind,
+    release: Option<String>,
+    message: Option<String>,
+    module_system: String,
+}
+
+impl OptionRenameDocumentation {
+    pub fn name(&self) -> String {
+        self.from.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        self.message.as_deref().unwrap_or_default()
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let to = self
+            .to
+            .iter()
+            .map(|to| to.white().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let mut output = format!(
+            "# {}\n{} option ",
+            self.from.blue().bold(),
+            self.module_system
+        );
+        output.push_str(&match self.kind {
+            RenameKind::Renamed => format!("renamed to {}", to),
+            RenameKind::Aliased => format!("alias of {}", to),
+            RenameKind::Changed => format!("replaced by {}", to),
+            RenameKind::Merged => format!("merged into {}", to),
+            RenameKind::Removed => "removed".to_string(),
+        });
+        if let Some(release) = &self.release {
+            output.push_str(&format!(" in release {}", release));
+        }
+        output.push('\n');
+        if let Some(message) = &self.message {
+            output.push_str(&format!("{}\n", message.trim()));
+        }
+        output.push('\n');
+        output
+    }
+
+    /// Matches the old name itself and the options below it, `services.foo.enable` of a renamed
+    /// `services.foo`
+    fn applies_to(&self, query: &Lowercase) -> bool {
+        let from = self.from.to_ascii_lowercase().into_bytes();
+        query.0 == from.as_slice()
+            || (query.0.starts_with(&from) && query.0.get(from.len()) == Some(&b'.'))
+    }
+}
+
+/// Options of NixOS and home-manager that were renamed or removed by `mkRenamedOptionModule` and
+/// friends, so looking up an old name says where it went
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenamesDatabase {
+    pub renames: HashMap<String, OptionRenameDocumentation>,
+}
+
+impl Default for RenamesDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RenamesDatabase {
+    pub fn new() -> Self {
+        Self {
+            renames: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for RenamesDatabase {}
+
+impl DocSource for RenamesDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.renames.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.renames
+            .values()
+            .filter(|rename| rename.applies_to(query))
+            .map(|rename| DocEntry::OptionRenameDoc(rename.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        // substrings of old names would drown the results in renames
+        self.search(query)
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.renames
+            .values()
+            .map(|value| DocEntry::OptionRenameDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let mut roots = vec![(get_nixpkgs_root().join("nixos/modules"), "NixOS")];
+        if let Some(home_manager) = find_nix_path_entry("home-manager") {
+            roots.push((home_manager.join("modules"), "Home Manager"));
+        }
+
+        let files = roots
+            .iter()
+            .flat_map(|(root, module_system)| {
+                walkdir::WalkDir::new(root)
+                    .into_iter()
+                    .filter_map(Result::ok)
+                    .filter(|e| e.path().extension().and_then(|s| s.to_str()) == Some("nix"))
+                    .map(move |e| (e.into_path(), *module_system))
+            })
+            .collect::<Vec<_>>();
+        let new = files
+            .par_iter()
+            .flat_map(|(path, module_system)| parse_renames_file(path, module_system))
+            .map(|rename| (rename.from.clone(), rename))
+            .collect::<HashMap<_, _>>();
+        let old = std::mem::replace(&mut self.renames, new);
+
+        Ok(!self.renames.keys().eq(old.keys()))
+    }
+}
+
+fn parse_renames_file(path: &Path, module_system: &str) -> Vec<OptionRenameDocumentation> {
+    match std::fs::read_to_string(path) {
+        Ok(content) if content.contains("OptionModule") => parse_renames(&content, module_system),
+        _ => Vec::new(),
+    }
+}
+
+/// Literal strings only, `"${name}"` can't be known without evaluating
+fn literal_str(expr: &Expr) -> Option<String> {
+    let Expr::Str(s) = expr else {
+        return None;
+    };
+    let mut text = String::new();
+    for part in s.normalized_parts() {
+        match part {
+            InterpolPart::Literal(literal) => text.push_str(&literal),
+            InterpolPart::Interpolation(_) => return None,
+        }
+    }
+    Some(text)
+}
+
+/// `[ "services" "foo" ]` is `services.foo`
+fn option_path(expr: &Expr) -> Option<String> {
+    let Expr::List(list) = expr else {
+        return None;
+    };
+    let parts = list
+        .items()
+        .map(|item| literal_str(&item))
+        .collect::<Option<Vec<_>>>()?;
+    (!parts.is_empty()).then(|| parts.join("."))
+}
+
+fn attr_value(set: &AttrSet, name: &str) -> Option<Expr> {
+    set.attrpath_values()
+        .find(|entry| {
+            entry
+                .attrpath()
+                .map(|path| path.to_string() == name)
+                .unwrap_or(false)
+        })?
+        .value()
+}
+
+/// `sinceRelease = 2305` is 23.05
+fn release_of(expr: &Expr) -> Option<String> {
+    let release = expr.syntax().text().to_string();
+    if release.len() != 4 || !release.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(format!("{}.{}", &release[..2], &release[2..]))
+}
+
+/// The function applied and its arguments, `f a b` is nested as `(f a) b`
+fn flatten_apply(apply: &Apply) -> Option<(String, Vec<Expr>)> {
+    let mut args = vec![apply.argument()?];
+    let mut lambda = apply.lambda()?;
+    while let Expr::Apply(inner) = lambda {
+        args.push(inner.argument()?);
+        lambda = inner.lambda()?;
+    }
+    args.reverse();
+    let name = match lambda {
+        Expr::Ident(ident) => ident.to_string(),
+        Expr::Select(select) => select.attrpath()?.attrs().last()?.to_string(),
+        _ => return None,
+    };
+    Some((name, args))
+}
+
+fn parse_renames(content: &str, module_system: &str) -> Vec<OptionRenameDocumentation> {
+    let Ok(ast) = Root::parse(content).ok() else {
+        return Vec::new();
+    };
+
+    let rename =
+        |from: String, to: Vec<String>, kind, release, message| OptionRenameDocumentation {
+            from,
+            to,
+            kind,
+            release,
+            message,
+            module_system: module_system.to_string(),
+        };
+
+    let mut renames = Vec::new();
+    for apply in ast.syntax().descendants().filter_map(Apply::cast) {
+        let Some((function, args)) = flatten_apply(&apply) else {
+            continue;
+        };
+        match (function.as_str(), args.as_slice()) {
+            ("mkRenamedOptionModule", [from, to]) => {
+                if let (Some(from), Some(to)) = (option_path(from), option_path(to)) {
+                    renames.push(rename(from, vec![to], RenameKind::Renamed, None, None));
+                }
+            }
+            ("mkRenamedOptionModuleWith", [Expr::AttrSet(set)]) => {
+                let from = attr_value(set, "from").and_then(|e| option_path(&e));
+                let to = attr_value(set, "to").and_then(|e| option_path(&e));
+                let release = attr_value(set, "sinceRelease").and_then(|e| release_of(&e));
+                if let (Some(from), Some(to)) = (from, to) {
+                    renames.push(rename(from, vec![to], RenameKind::Renamed, release, None));
+                }
+            }
+            ("mkAliasOptionModule", [from, to]) => {
+                if let (Some(from), Some(to)) = (option_path(from), option_path(to)) {
+                    renames.push(rename(from, vec![to], RenameKind::Aliased, None, None));
+                }
+            }
+            ("mkChangedOptionModule", [from, to, _]) => {
+                if let (Some(from), Some(to)) = (option_path(from), option_path(to)) {
+                    renames.push(rename(from, vec![to], RenameKind::Changed, None, None));
+                }
+            }
+            ("mkMergedOptionModule", [Expr::List(froms), to, _]) => {
+                if let Some(to) = option_path(to) {
+                    for from in froms.items().filter_map(|from| option_path(&from)) {
+                        renames.push(rename(
+                            from,
+                            vec![to.clone()],
+                            RenameKind::Merged,
+                            None,
+                            None,
+                        ));
+                    }
+                }
+            }
+            ("mkRemovedOptionModule", [from, message]) => {
+                if let Some(from) = option_path(from) {
+                    let message = literal_str(message).filter(|m| !m.trim().is_empty());
+                    renames.push(rename(from, Vec::new(), RenameKind::Removed, None, message));
+                }
+            }
+            _ => {}
+        }
+    }
+    renames
+}
+
+#[test]
+fn test_parse_renames() {
+    let content = r#"{ lib, ... }: with lib; {
+  imports = [
+    (mkRenamedOptionModule [ "services" "foo" ] [ "services" "bar" ])
+    (lib.mkRenamedOptionModuleWith {
+      sinceRelease = 2305;
+      from = [ "programs" "old" ];
+      to = [ "programs" "new" ];
+    })
+    (mkRemovedOptionModule [ "services" "gone" "enable" ] "The service was removed.")
+    (mkMergedOptionModule [ [ "a" "x" ] [ "a" "y" ] ] [ "a" "z" ] (config: null))
+    (mkRenamedOptionModule [ "services" name ] [ "services" "other" ])
+  ];
+}"#;
+    let renames = parse_renames(content, "NixOS");
+    let find = |from: &str| renames.iter().find(|r| r.from == from);
+    assert_eq!(renames.len(), 5);
+    assert_eq!(find("services.foo").unwrap().to, vec!["services.bar"]);
+    assert_eq!(
+        find("programs.old").unwrap().release.as_deref(),
+        Some("23.05")
+    );
+    let gone = find("services.gone.enable").unwrap();
+    assert_eq!(gone.kind, RenameKind::Removed);
+    assert_eq!(gone.message.as_deref(), Some("The service was removed."));
+    assert_eq!(find("a.y").unwrap().to, vec!["a.z"]);
+
+    let foo = find("services.foo").unwrap();
+    assert!(foo.applies_to(&Lowercase(b"services.foo.enable")));
+    assert!(!foo.applies_to(&Lowercase(b"services.foobar")));
+}
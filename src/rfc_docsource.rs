@@ -0,0 +1,205 @@
+use crate::{
+    contains_insensitive_ascii,
+    options_docsource::run_nix,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    process::Command,
+};
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RfcDocumentation {
+    number: u32,
+    feature: String,
+    summary: String,
+    file: String,
+}
+
+impl RfcDocumentation {
+    pub fn name(&self) -> String {
+        format!("RFC {} {}", self.number, self.feature)
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.summary
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        format!(
+            "# {} {}\n{}\n{}\n\n",
+            format!("RFC {}", self.number).blue().bold(),
+            self.feature.white(),
+            self.summary,
+            format!(
+                "https://github.com/NixOS/rfcs/blob/master/rfcs/{}.md",
+                self.file
+            )
+            .underline()
+        )
+    }
+}
+
+/// Accepted Nix RFCs, keyed by `RFC <number> <feature>`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RfcDatabase {
+    pub rfcs: HashMap<String, RfcDocumentation>,
+}
+
+impl Default for RfcDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RfcDatabase {
+    pub fn new() -> Self {
+        Self {
+            rfcs: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for RfcDatabase {
+    /// Fetched from the internet instead
+    const BUILT_FROM_NIXPKGS: bool = false;
+}
+
+impl DocSource for RfcDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.rfcs.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.rfcs
+            .iter()
+            .filter(|(key, value)| {
+                starts_with_insensitive_ascii(key.as_bytes(), query)
+                    || starts_with_insensitive_ascii(value.feature.as_bytes(), query)
+            })
+            .map(|(_, value)| DocEntry::RfcDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.rfcs
+            .iter()
+            .filter(|(key, value)| {
+                // features are kebab-case, so `doc comment` should find `doc-comment-standard`
+                contains_insensitive_ascii(key.replace('-', " ").as_bytes(), query)
+                    || contains_insensitive_ascii(key.as_bytes(), query)
+                    || contains_insensitive_ascii(value.summary.as_bytes(), query)
+            })
+            .map(|(_, value)| DocEntry::RfcDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.rfcs
+            .values()
+            .map(|value| DocEntry::RfcDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let rfcs_dir = get_rfcs_path()?.join("rfcs");
+        let mut new = HashMap::new();
+        for entry in std::fs::read_dir(&rfcs_dir).map_err(|e| Errors::FileIo {
+            err: e,
+            filename: rfcs_dir.display().to_string(),
+        })? {
+            let path = entry?.path();
+            let Some(stem) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            if let Some(rfc) = parse_rfc(stem, &content) {
+                new.insert(rfc.name(), rfc);
+            }
+        }
+        let old = std::mem::replace(&mut self.rfcs, new);
+
+        Ok(!self.rfcs.keys().eq(old.keys()))
+    }
+}
+
+/// RFC files are named `0145-doc-comment-standard.md` and start with a `# Summary` section
+fn parse_rfc(stem: &str, content: &str) -> Option<RfcDocumentation> {
+    let (number, slug) = stem.split_once('-')?;
+    let number = number.parse().ok()?;
+    let feature = content
+        .lines()
+        .find_map(|line| line.strip_prefix("feature:"))
+        .map(|feature| feature.trim().to_string())
+        .unwrap_or_else(|| slug.to_string());
+
+    let summary = content
+        .lines()
+        .skip_while(|line| !line.trim().eq_ignore_ascii_case("# summary"))
+        .skip(1)
+        .take_while(|line| !line.starts_with('#'))
+        // skip the `[summary]: #summary` anchors
+        .filter(|line| !line.starts_with('['))
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_string();
+
+    Some(RfcDocumentation {
+        number,
+        feature,
+        summary,
+        file: stem.to_string(),
+    })
+}
+
+fn get_rfcs_path() -> Result<PathBuf, std::io::Error> {
+    let output = run_nix(
+        Command::new("nix-instantiate")
+            .arg("--eval")
+            .arg("--json")
+            .arg("-E")
+            .arg(include_str!("nix/rfcs.nix")),
+    )?;
+
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(
+            &output.stderr,
+        )));
+    }
+
+    let path: String = serde_json::from_slice(&output.stdout)?;
+    Ok(PathBuf::from(path))
+}
+
+#[test]
+fn test_parse_rfc() {
+    let content = "---
+feature: doc-comment-standard
+start-date: 2022-09-12
+---
+
+# Summary
+[summary]: #summary
+
+Propose a standard format for doc comments.
+
+# Motivation
+[motivation]: #motivation
+
+Nixpkgs has no standard.
+";
+    let rfc = parse_rfc("0145-doc-comment-standard", content).unwrap();
+    assert_eq!(rfc.name(), "RFC 145 doc-comment-standard");
+    assert_eq!(rfc.summary, "Propose a standard format for doc comments.");
+    assert!(parse_rfc("README", "").is_none());
+}
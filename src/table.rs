@@ -0,0 +1,124 @@
+const GAP: &str = "  ";
+const MAX_TYPE_WIDTH: usize = 24;
+const MIN_KEY_WIDTH: usize = 20;
+const MIN_DESCRIPTION_WIDTH: usize = 16;
+
+/// A row of the table, only the first line of the description is shown
+pub struct Row {
+    pub key: String,
+    pub typ: String,
+    pub description: String,
+}
+
+fn width_of(s: &str) -> usize {
+    s.chars().count()
+}
+
+/// Pads the cell to the width, cutting it short with an ellipsis when it doesn't fit
+fn cell(s: &str, width: usize) -> String {
+    if width_of(s) <= width {
+        format!("{:width$}", s, width = width)
+    } else {
+        let cut = s.chars().take(width.saturating_sub(1)).collect::<String>();
+        format!("{}…", cut)
+    }
+}
+
+/// Aligns the rows into columns of key, type and description. With a width, the columns are
+/// shrunk to fit in it, leaving the description out when there's no room for it
+pub fn render_table(rows: &[Row], width: Option<usize>) -> String {
+    let descriptions = rows
+        .iter()
+        .map(|row| row.description.lines().next().unwrap_or("").trim())
+        .collect::<Vec<_>>();
+    let header = Row {
+        key: "KEY".to_string(),
+        typ: "TYPE".to_string(),
+        description: "DESCRIPTION".to_string(),
+    };
+    let mut key_width = rows
+        .iter()
+        .chain([&header])
+        .map(|row| width_of(&row.key))
+        .max()
+        .unwrap_or(0);
+    let mut type_width = rows
+        .iter()
+        .chain([&header])
+        .map(|row| width_of(&row.typ))
+        .max()
+        .unwrap_or(0);
+    // unlimited without a width
+    let mut description_width = None;
+    let mut show_description = true;
+    if let Some(width) = width {
+        type_width = type_width.min(MAX_TYPE_WIDTH);
+        let available = width.saturating_sub(type_width + 2 * GAP.len());
+        // keys matter more than descriptions, which get what's left
+        key_width = key_width.min(
+            available
+                .saturating_sub(MIN_DESCRIPTION_WIDTH)
+                .max(MIN_KEY_WIDTH),
+        );
+        let width = available.saturating_sub(key_width);
+        description_width = Some(width);
+        show_description = width >= MIN_DESCRIPTION_WIDTH;
+    }
+
+    let mut output = String::new();
+    let mut push_row = |key: &str, typ: &str, description: &str| {
+        let mut line = format!("{}{}{}", cell(key, key_width), GAP, cell(typ, type_width));
+        if show_description {
+            line.push_str(GAP);
+            match description_width {
+                Some(width) => line.push_str(&cell(description, width)),
+                None => line.push_str(description),
+            }
+        }
+        output.push_str(line.trim_end());
+        output.push('\n');
+    };
+    push_row(&header.key, &header.typ, &header.description);
+    for (row, description) in rows.iter().zip(descriptions) {
+        push_row(&row.key, &row.typ, description);
+    }
+    output
+}
+
+#[test]
+fn test_render_table() {
+    let rows = [
+        Row {
+            key: "services.openssh.enable".to_string(),
+            typ: "boolean".to_string(),
+            description: "Whether to enable the OpenSSH secure shell daemon.\nMore".to_string(),
+        },
+        Row {
+            key: "lib.mapAttrs".to_string(),
+            typ: "lib_function".to_string(),
+            description: String::new(),
+        },
+    ];
+    assert_eq!(
+        render_table(&rows, None),
+        "KEY                      TYPE          DESCRIPTION
+services.openssh.enable  boolean       Whether to enable the OpenSSH secure shell daemon.
+lib.mapAttrs             lib_function
+"
+    );
+    assert_eq!(
+        render_table(&rows, Some(60)),
+        "KEY                      TYPE          DESCRIPTION
+services.openssh.enable  boolean       Whether to enable th…
+lib.mapAttrs             lib_function
+"
+    );
+    // too narrow for descriptions
+    assert_eq!(
+        render_table(&rows, Some(30)),
+        "KEY                   TYPE
+services.openssh.en…  boolean
+lib.mapAttrs          lib_function
+"
+    );
+}
@@ -0,0 +1,66 @@
+/// Splits keys and queries into lowercase words, on dots, other punctuation and whitespace and
+/// where camelCase words start: `lib.attrsets.mapAttrs` is `lib attrsets map attrs`
+pub fn tokenize(s: &str) -> Vec<String> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if !c.is_alphanumeric() {
+            if !current.is_empty() {
+                tokens.push(std::mem::take(&mut current));
+            }
+            continue;
+        }
+        let previous = i.checked_sub(1).map(|i| chars[i]);
+        let next = chars.get(i + 1);
+        // `mapAttrs` and `XMLHttp`, where the last capital of a run starts the next word
+        let word_start = c.is_uppercase()
+            && previous.is_some_and(|p| {
+                p.is_lowercase()
+                    || p.is_ascii_digit()
+                    || (p.is_uppercase() && next.is_some_and(|n| n.is_lowercase()))
+            });
+        if word_start && !current.is_empty() {
+            tokens.push(std::mem::take(&mut current));
+        }
+        current.extend(c.to_lowercase());
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Whether the words of the query follow each other in the key, starting at one of its words:
+/// `merge attrs` matches `mergeAttrs` and `attr sets` matches `lib.attrsets.filterAttrs`
+pub fn matches_tokens(key: &str, query: &str) -> bool {
+    let query = tokenize(query).concat();
+    if query.is_empty() {
+        return false;
+    }
+    let tokens = tokenize(key);
+    (0..tokens.len()).any(|start| tokens[start..].concat().starts_with(&query))
+}
+
+#[test]
+fn test_tokenize() {
+    assert_eq!(
+        tokenize("lib.attrsets.mapAttrs"),
+        vec!["lib", "attrsets", "map", "attrs"]
+    );
+    assert_eq!(
+        tokenize("XMLHttpRequest2x"),
+        vec!["xml", "http", "request2x"]
+    );
+    assert_eq!(tokenize("merge attrs"), vec!["merge", "attrs"]);
+    assert!(tokenize(" .. ").is_empty());
+}
+
+#[test]
+fn test_matches_tokens() {
+    assert!(matches_tokens("lib.mergeAttrs", "merge attrs"));
+    assert!(matches_tokens("lib.attrsets.filterAttrs", "attr sets"));
+    assert!(matches_tokens("services.openssh.enable", "openssh enable"));
+    assert!(!matches_tokens("lib.mapAttrs", "ap attrs"));
+    assert!(!matches_tokens("lib.mapAttrs", " "));
+}
@@ -0,0 +1,218 @@
+use crate::{
+    contains_insensitive_ascii,
+    starts_with_insensitive_ascii,
+    Cache,
+    DocEntry,
+    DocSource,
+    Errors,
+    Lowercase,
+};
+use colored::*;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    process::Command,
+};
+
+const WIKI_URL: &str = "https://wiki.nixos.org";
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WikiPageDocumentation {
+    title: String,
+    summary: String,
+    pub(crate) url: String,
+}
+
+impl WikiPageDocumentation {
+    pub fn name(&self) -> String {
+        self.title.clone()
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.summary
+    }
+
+    pub fn pretty_printed(&self) -> String {
+        let mut output = format!("# {}\n", self.title.blue().bold());
+        if !self.summary.is_empty() {
+            output.push_str(&format!("{}\n", self.summary));
+        }
+        output.push_str(&format!("{}\n\n", self.url.underline()));
+        output
+    }
+}
+
+/// Titles and introductions of the NixOS wiki articles
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WikiDatabase {
+    pub pages: HashMap<String, WikiPageDocumentation>,
+}
+
+impl Default for WikiDatabase {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WikiDatabase {
+    pub fn new() -> Self {
+        Self {
+            pages: HashMap::new(),
+        }
+    }
+}
+
+impl Cache for WikiDatabase {
+    /// Fetched from the internet instead
+    const BUILT_FROM_NIXPKGS: bool = false;
+}
+
+impl DocSource for WikiDatabase {
+    fn all_keys(&self) -> Vec<&str> {
+        self.pages.keys().map(|x| x.as_str()).collect()
+    }
+    fn search(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.pages
+            .iter()
+            .filter(|(key, _)| starts_with_insensitive_ascii(key.as_bytes(), query))
+            .map(|(_, value)| DocEntry::WikiDoc(value.clone()))
+            .collect()
+    }
+    fn search_liberal(&self, query: &Lowercase) -> Vec<DocEntry> {
+        self.pages
+            .iter()
+            .filter(|(key, value)| {
+                contains_insensitive_ascii(key.as_bytes(), query)
+                    || contains_insensitive_ascii(value.summary.as_bytes(), query)
+            })
+            .map(|(_, value)| DocEntry::WikiDoc(value.clone()))
+            .collect()
+    }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.pages
+            .values()
+            .map(|value| DocEntry::WikiDoc(value.clone()))
+            .collect()
+    }
+    fn update(&mut self) -> Result<bool, Errors> {
+        let mut new = HashMap::new();
+        let mut continuation: Vec<(String, String)> = Vec::new();
+        loop {
+            let response = query_wiki(&continuation)?;
+            collect_pages(&response, &mut new);
+            // the API hands back the parameters to pass along with the next request
+            match response.get("continue").and_then(Value::as_object) {
+                Some(next) => {
+                    continuation = next
+                        .iter()
+                        .map(|(k, v)| match v {
+                            Value::String(s) => (k.clone(), s.clone()),
+                            other => (k.clone(), other.to_string()),
+                        })
+                        .collect()
+                }
+                None => break,
+            }
+        }
+        let old = std::mem::replace(&mut self.pages, new);
+
+        Ok(!self.pages.keys().eq(old.keys()))
+    }
+}
+
+/// Extracts are handed out in smaller batches than pages, so a page can show up several times
+/// before its summary does
+fn collect_pages(response: &Value, pages: &mut HashMap<String, WikiPageDocumentation>) {
+    let found = response
+        .pointer("/query/pages")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten();
+    for page in found {
+        let Some(title) = page.get("title").and_then(Value::as_str) else {
+            continue;
+        };
+        let summary = page
+            .get("extract")
+            .and_then(Value::as_str)
+            .map(|s| s.trim().to_string())
+            .unwrap_or_default();
+        let entry = pages
+            .entry(title.to_string())
+            .or_insert_with(|| WikiPageDocumentation {
+                title: title.to_string(),
+                summary: String::new(),
+                url: format!("{}/wiki/{}", WIKI_URL, title.replace(' ', "_")),
+            });
+        if !summary.is_empty() {
+            entry.summary = summary;
+        }
+    }
+}
+
+fn query_wiki(continuation: &[(String, String)]) -> Result<Value, Errors> {
+    let params = [
+        ("action", "query"),
+        ("format", "json"),
+        ("formatversion", "2"),
+        ("generator", "allpages"),
+        ("gapnamespace", "0"),
+        ("gapfilterredir", "nonredirects"),
+        ("gaplimit", "max"),
+        ("prop", "extracts"),
+        ("exintro", "1"),
+        ("explaintext", "1"),
+        ("exsentences", "2"),
+        ("exlimit", "max"),
+    ];
+
+    let mut command = Command::new("curl");
+    command.arg("--silent").arg("--fail").arg("--get");
+    for (key, value) in params
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .chain(continuation.iter().cloned())
+    {
+        command
+            .arg("--data-urlencode")
+            .arg(format!("{}={}", key, value));
+    }
+    let output = command.arg(format!("{}/w/api.php", WIKI_URL)).output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(String::from_utf8_lossy(&output.stderr)).into());
+    }
+
+    Ok(serde_json::from_slice(&output.stdout)?)
+}
+
+#[test]
+fn test_collect_pages() {
+    let mut pages = HashMap::new();
+    let first: Value = serde_json::from_str(
+        r#"{ "query": { "pages": [
+            { "pageid": 1, "ns": 0, "title": "Secure Boot" },
+            { "pageid": 2, "ns": 0, "title": "Flakes", "extract": "Flakes are an experimental feature." }
+        ] } }"#,
+    )
+    .unwrap();
+    let second: Value = serde_json::from_str(
+        r#"{ "query": { "pages": [
+            { "pageid": 1, "ns": 0, "title": "Secure Boot", "extract": "Secure Boot can be enabled with lanzaboote. " }
+        ] } }"#,
+    )
+    .unwrap();
+    collect_pages(&first, &mut pages);
+    collect_pages(&second, &mut pages);
+
+    assert_eq!(pages.len(), 2);
+    let secure_boot = &pages["Secure Boot"];
+    assert_eq!(
+        secure_boot.summary,
+        "Secure Boot can be enabled with lanzaboote."
+    );
+    assert_eq!(secure_boot.url, "https://wiki.nixos.org/wiki/Secure_Boot");
+}
@@ -1,5 +1,6 @@
 use crate::{
     contains_insensitive_ascii,
+    options_docsource::run_nix,
     starts_with_insensitive_ascii,
     Cache,
     DocEntry,
@@ -37,6 +38,10 @@ impl XmlFuncDocumentation {
         self.name.to_string()
     }
 
+    pub(crate) fn text(&self) -> &str {
+        &self.description
+    }
+
     pub fn pretty_printed(&self) -> String {
         let mut output = String::new();
         if let Some(function_type) = &self.fn_type {
@@ -72,7 +77,7 @@ impl XmlFuncDocumentation {
             .find(|n| {
                 is_tag(n, "subtitle")
                     && n.first_element_child()
-                        .map_or(false, |n| is_tag(&n, "literal"))
+                        .is_some_and(|n| is_tag(&n, "literal"))
             })
             .and_then(|n| n.first_element_child())
             .and_then(|n| n.text())
@@ -159,6 +164,12 @@ impl DocSource for XmlFuncDocDatabase {
             .map(|(_, value)| DocEntry::XmlFuncDoc(value.clone()))
             .collect()
     }
+    fn all_entries(&self) -> Vec<DocEntry> {
+        self.functions
+            .values()
+            .map(|value| DocEntry::XmlFuncDoc(value.clone()))
+            .collect()
+    }
     fn update(&mut self) -> Result<bool, Errors> {
         let doc_path = &generate_docs();
         let mut result = Vec::new();
@@ -176,10 +187,10 @@ impl DocSource for XmlFuncDocDatabase {
                 .descendants()
                 .filter(|x| is_tag(x, "section"))
                 .filter(|x| {
-                    x.first_element_child().map_or(false, |c| {
+                    x.first_element_child().is_some_and(|c| {
                         is_tag(&c, "title")
                             && c.first_element_child()
-                                .map_or(false, |f| is_tag(&f, "function"))
+                                .is_some_and(|f| is_tag(&f, "function"))
                     })
                 })
                 .filter_map(|node| XmlFuncDocumentation::from_function_section_node(&node))
@@ -209,12 +220,13 @@ fn xml_files_in(path: &PathBuf) -> Vec<PathBuf> {
 }
 
 fn generate_docs() -> PathBuf {
-    let doc_path = Command::new("nix-build")
-        .arg("--no-out-link")
-        .arg("<nixpkgs/doc/doc-support/default.nix>")
-        .output()
-        .ok()
-        .and_then(|o| String::from_utf8(o.stdout).ok())
-        .unwrap();
+    let doc_path = run_nix(
+        Command::new("nix-build")
+            .arg("--no-out-link")
+            .arg("<nixpkgs/doc/doc-support/default.nix>"),
+    )
+    .ok()
+    .and_then(|o| String::from_utf8(o.stdout).ok())
+    .unwrap();
     PathBuf::from(doc_path.trim_end_matches('\n')).join("function-docs")
 }